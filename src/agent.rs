@@ -0,0 +1,232 @@
+use anyhow::{bail, Context, Result};
+use futures::future::try_join_all;
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver};
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tonic::Request;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::daemon::Daemon;
+use crate::grpc::{CheckReport, CollectorClient, Probe};
+
+/// Number of results kept while the collector cannot be reached, the newer ones being dropped.
+const BACKLOG: usize = 64;
+/// Delays between the reconnections to the collector, doubled after each failure.
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+/// Timeout of the connections to the collector, and interval of the HTTP/2 keep-alives
+/// noticing a collector gone.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Agent streaming the results of its checks to a central collector over gRPC.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Agent {
+    /// URL of the collector, over TLS with `https://`
+    pub controller: Url,
+    /// Identity of the probe reported along with the results [default: the hostname]
+    pub probe_id: Option<String>,
+    /// Token sent to the collector as a bearer token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// PEM file of the CA certificates of the collector, trusted along with the system ones
+    pub ca_cert: Option<PathBuf>,
+    /// PEM files of the client certificate and key, authenticating the agent with mutual TLS
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+fn hostname() -> String {
+    hostname::get()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+impl Agent {
+    pub fn new(controller: Url) -> Self {
+        Agent {
+            controller,
+            probe_id: None,
+            token: None,
+            ca_cert: None,
+            cert: None,
+            key: None,
+        }
+    }
+
+    pub fn probe(&self) -> Probe {
+        let hostname = hostname();
+        Probe {
+            id: self.probe_id.clone().unwrap_or_else(|| hostname.clone()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname,
+        }
+    }
+
+    /// Endpoint of the collector, checking the TLS files.
+    pub fn endpoint(&self) -> Result<Endpoint> {
+        let endpoint = Endpoint::from_shared(self.controller.to_string())
+            .with_context(|| format!("invalid collector URL {}", self.controller))?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+            .keep_alive_while_idle(true);
+        let tls = self.ca_cert.is_some() || self.cert.is_some() || self.key.is_some();
+        match self.controller.scheme() {
+            "https" => {}
+            "http" if tls => bail!("the TLS certificates require an https:// collector URL"),
+            "http" => return Ok(endpoint),
+            scheme => bail!("unsupported collector URL scheme `{}`", scheme),
+        }
+
+        let mut config = ClientTlsConfig::new().with_native_roots();
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("failed to read the CA certificate {}", path.display()))?;
+            config = config.ca_certificate(Certificate::from_pem(pem));
+        }
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                let cert = std::fs::read(cert).with_context(|| {
+                    format!("failed to read the client certificate {}", cert.display())
+                })?;
+                let key = std::fs::read(key)
+                    .with_context(|| format!("failed to read the client key {}", key.display()))?;
+                config = config.identity(Identity::from_pem(cert, key));
+            }
+            (None, None) => {}
+            _ => bail!("the client certificate and key are required together"),
+        }
+        Ok(endpoint.tls_config(config)?)
+    }
+
+    /// Stream the reports over one connection to the collector, starting with the pending one,
+    /// until the agent stops. The report which could not be sent is left pending.
+    async fn stream(
+        &self,
+        channel: Channel,
+        pending: &mut Option<CheckReport>,
+        reports: &mut Receiver<CheckReport>,
+    ) -> Result<u64> {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let mut request = Request::new(stream::poll_fn(move |cx| receiver.poll_recv(cx)));
+        if let Some(token) = &self.token {
+            let value = MetadataValue::try_from(format!("Bearer {}", token))
+                .context("invalid collector token")?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        let mut client = CollectorClient::new(channel);
+        let call = client.report(request);
+        tokio::pin!(call);
+
+        loop {
+            let report = match pending.take() {
+                Some(report) => report,
+                None => tokio::select! {
+                    response = &mut call => {
+                        response?;
+                        bail!("the collector ended the stream");
+                    }
+                    report = reports.recv() => match report {
+                        Some(report) => report,
+                        // Once stopped, the collector acknowledges the stream when it ends
+                        None => {
+                            drop(sender);
+                            return Ok(call.await?.into_inner().received);
+                        }
+                    },
+                },
+            };
+            debug!(verdict = report.verdict, "Reporting to the collector");
+            if let Err(e) = sender.send(report).await {
+                *pending = Some(e.0);
+                call.await?;
+                bail!("the collector closed the stream");
+            }
+        }
+    }
+
+    /// Report the results to the collector until the agent stops, reconnecting to it after the
+    /// failures.
+    async fn report(&self, mut reports: Receiver<CheckReport>) -> Result<()> {
+        let endpoint = self.endpoint()?;
+        let mut pending = None;
+        let mut delay = RECONNECT_MIN;
+        loop {
+            let streamed = match endpoint.connect().await {
+                Ok(channel) => {
+                    info!("Connected to the collector {}", self.controller);
+                    delay = RECONNECT_MIN;
+                    self.stream(channel, &mut pending, &mut reports).await
+                }
+                Err(e) => Err(e.into()),
+            };
+            match streamed {
+                Ok(received) => {
+                    debug!(received, "The collector acknowledged the reports");
+                    return Ok(());
+                }
+                Err(e) if reports.is_closed() => {
+                    let dropped = reports.len() + usize::from(pending.is_some());
+                    warn!(
+                        "Failed to report to the collector {}: {:#}, dropped {} results",
+                        self.controller, e, dropped
+                    );
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to report to the collector {}: {:#}, reconnecting in {}s",
+                        self.controller,
+                        e,
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RECONNECT_MAX);
+                }
+            }
+        }
+    }
+
+    /// Run the checks of the daemons and stream their results to the collector. The probe of
+    /// a profile is identified as `<probe id>/<profile>`.
+    pub async fn run(self, daemons: Vec<Daemon>) -> Result<()> {
+        // Fail before the first check on invalid TLS files
+        self.endpoint()?;
+        let (sender, receiver) = mpsc::channel(BACKLOG);
+        let daemons = daemons.into_iter().map(|daemon| {
+            let mut probe = self.probe();
+            if let Some(profile) = daemon.profile() {
+                probe.id = format!("{}/{}", probe.id, profile);
+            }
+            info!(
+                probe = probe.id,
+                "Reporting to the collector {}", self.controller
+            );
+            let sender = sender.clone();
+            daemon.run(move |result, _| {
+                info!("{}: {}", result.verdict, result.reason);
+                match sender.try_send(CheckReport::new(probe.clone(), result)?) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        warn!(
+                            "Dropped the result, {} results are waiting for the collector",
+                            BACKLOG
+                        )
+                    }
+                    Err(TrySendError::Closed(_)) => {}
+                }
+                Ok(())
+            })
+        });
+        let daemons = try_join_all(daemons.collect::<Vec<_>>());
+        drop(sender);
+        tokio::try_join!(daemons, self.report(receiver))?;
+        Ok(())
+    }
+}
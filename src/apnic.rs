@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::verdict::Verdict;
+
+/// Base URL of the ROV measurements of APNIC Labs, published per ASN.
+pub const DEFAULT_URL: &str = "https://stats.labs.apnic.net/rpki/";
+/// Share of the samples of an ASN filtering the RPKI invalid routes from which APNIC Labs sees
+/// it enforcing ROV, and below which not enforcing it.
+const ENFORCED_FILTERED: f64 = 90.0;
+const NOT_ENFORCED_FILTERED: f64 = 10.0;
+
+/// ROV measurement of an ASN by APNIC Labs, from the users of the ASN fetching an RPKI invalid
+/// resource in ads.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Score {
+    pub asn: u32,
+    /// Number of users measured
+    pub samples: u64,
+    /// Percentage of the users not reaching the RPKI invalid resource
+    #[serde(alias = "rpki_filtered", alias = "rov_filtering")]
+    pub filtered: f64,
+}
+
+impl Score {
+    /// Verdict of APNIC Labs on the ASN, INCONCLUSIVE if only some of its users are filtering.
+    pub fn verdict(&self) -> Verdict {
+        match self.filtered {
+            filtered if filtered >= ENFORCED_FILTERED => Verdict::Enforced,
+            filtered if filtered <= NOT_ENFORCED_FILTERED => Verdict::NotEnforced,
+            _ => Verdict::Inconclusive,
+        }
+    }
+}
+
+/// Local data plane verdict side by side with the APNIC Labs measurement of the ASN.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Comparison {
+    pub asn: u32,
+    /// ASN seen by the beacons, if another one was compared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seen_asn: Option<u32>,
+    pub verdict: Verdict,
+    pub reason: String,
+    pub apnic: Score,
+    pub apnic_verdict: Verdict,
+    /// Whether both verdicts agree, unknown if either is INCONCLUSIVE
+    pub agrees: Option<bool>,
+}
+
+impl Comparison {
+    pub fn new(
+        asn: u32,
+        seen_asn: Option<u32>,
+        verdict: Verdict,
+        reason: String,
+        apnic: Score,
+    ) -> Self {
+        let apnic_verdict = apnic.verdict();
+        let agrees = verdict.agrees(apnic_verdict);
+        Comparison {
+            asn,
+            seen_asn: seen_asn.filter(|seen| *seen != asn),
+            verdict,
+            reason,
+            apnic,
+            apnic_verdict,
+            agrees,
+        }
+    }
+}
+
+/// ROV measurement of an ASN published by APNIC Labs.
+#[instrument(level = "debug", name = "apnic", skip(client, url))]
+pub async fn score(client: &Client, url: &Url, asn: u32) -> Result<Score> {
+    let url = url.join(&format!("AS{}.json", asn))?;
+    let response = client.get(url).send().await?.error_for_status()?;
+    let score: Score = response
+        .json()
+        .await
+        .context("invalid APNIC Labs response")?;
+    debug!(
+        samples = score.samples,
+        filtered = score.filtered,
+        "Fetched the APNIC Labs measurement"
+    );
+    Ok(score)
+}
@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument};
+use url::Url;
+
+/// Base URL of the RIPEstat data API.
+pub const RIPESTAT_URL: &str = "https://stat.ripe.net/data/";
+/// Base URL of the PeeringDB API.
+pub const PEERINGDB_URL: &str = "https://www.peeringdb.com/api/";
+/// How long the metadata of an ASN is cached, the holders of the ASNs rarely changing.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 3600);
+
+/// Lookup of the holder, country and PeeringDB record of the ASN seen by the beacons.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsnLookup {
+    #[serde(default = "default_ripestat_url")]
+    pub ripestat_url: Url,
+    #[serde(default = "default_peeringdb_url")]
+    pub peeringdb_url: Url,
+}
+
+fn default_ripestat_url() -> Url {
+    Url::parse(RIPESTAT_URL).unwrap()
+}
+
+fn default_peeringdb_url() -> Url {
+    Url::parse(PEERINGDB_URL).unwrap()
+}
+
+impl Default for AsnLookup {
+    fn default() -> Self {
+        AsnLookup {
+            ripestat_url: default_ripestat_url(),
+            peeringdb_url: default_peeringdb_url(),
+        }
+    }
+}
+
+/// Metadata of an ASN: its holder, country and PeeringDB record.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AsnInfo {
+    pub asn: u32,
+    /// Holder of the ASN registered at its RIR
+    pub holder: Option<String>,
+    /// Country of the ASN registered at its RIR
+    pub country: Option<String>,
+    pub peeringdb: Option<PeeringDb>,
+    pub error: Option<String>,
+}
+
+/// Network record of an ASN in PeeringDB.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeeringDb {
+    pub id: u64,
+    pub name: String,
+    /// Type of the network, e.g. `Cable/DSL/ISP` or `NSP`
+    #[serde(default)]
+    pub info_type: String,
+    #[serde(default)]
+    pub website: String,
+    /// Peering policy, e.g. `Open` or `Selective`
+    #[serde(default)]
+    pub policy_general: String,
+}
+
+impl AsnInfo {
+    /// Name and country of the network, e.g. `ExampleNet, DE`.
+    pub fn describe(&self) -> String {
+        let name = match (&self.peeringdb, &self.holder) {
+            (Some(peeringdb), _) => Some(peeringdb.name.as_str()),
+            (None, Some(holder)) => Some(holder.as_str()),
+            (None, None) => None,
+        };
+        [name, self.country.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Deserialize)]
+struct Ripestat<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Overview {
+    #[serde(default)]
+    holder: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CountryStats {
+    #[serde(default)]
+    located_resources: Vec<LocatedResource>,
+}
+
+#[derive(Deserialize)]
+struct LocatedResource {
+    location: String,
+}
+
+#[derive(Deserialize)]
+struct PeeringDbNetworks {
+    data: Vec<PeeringDb>,
+}
+
+async fn get<T: DeserializeOwned>(client: &Client, url: Url) -> Result<T> {
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    response
+        .json()
+        .await
+        .with_context(|| format!("invalid response from {}", url.path()))
+}
+
+impl AsnLookup {
+    fn ripestat(&self, call: &str, asn: u32) -> Result<Url> {
+        let mut url = self.ripestat_url.join(&format!("{}/data.json", call))?;
+        url.query_pairs_mut()
+            .append_pair("resource", &format!("AS{}", asn))
+            .append_pair("sourceapp", "rovcheck");
+        Ok(url)
+    }
+
+    /// Metadata of an ASN, only failing if its holder cannot be looked up as the country
+    /// and PeeringDB records are optional.
+    async fn lookup(&self, client: &Client, asn: u32) -> Result<AsnInfo> {
+        let mut peeringdb = self.peeringdb_url.join("net")?;
+        peeringdb
+            .query_pairs_mut()
+            .append_pair("asn", &asn.to_string());
+        let (overview, country, networks) = tokio::join!(
+            get::<Ripestat<Overview>>(client, self.ripestat("as-overview", asn)?),
+            get::<Ripestat<CountryStats>>(client, self.ripestat("rir-stats-country", asn)?),
+            get::<PeeringDbNetworks>(client, peeringdb)
+        );
+        let country = country
+            .inspect_err(|e| debug!("Failed to look up the country: {}", e))
+            .ok()
+            .and_then(|country| country.data.located_resources.into_iter().next());
+        let network = networks
+            .inspect_err(|e| debug!("Failed to look up the PeeringDB record: {}", e))
+            .ok()
+            .and_then(|networks| networks.data.into_iter().next());
+        Ok(AsnInfo {
+            asn,
+            holder: overview?.data.holder,
+            country: country.map(|resource| resource.location),
+            peeringdb: network,
+            error: None,
+        })
+    }
+}
+
+/// Cache of the ASN metadata, shared across the checks.
+#[derive(Clone, Debug, Default)]
+pub struct AsnCache {
+    lookup: AsnLookup,
+    entries: Arc<Mutex<HashMap<u32, (Instant, AsnInfo)>>>,
+}
+
+impl AsnCache {
+    pub fn new(lookup: AsnLookup) -> Self {
+        AsnCache {
+            lookup,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Metadata of an ASN from the RIPEstat and PeeringDB APIs, unless cached.
+    /// The failed lookups are not cached.
+    #[instrument(level = "debug", name = "asn", skip(self, client))]
+    pub async fn lookup(&self, client: &Client, asn: u32) -> AsnInfo {
+        if let Some((fetched_at, info)) = self.entries.lock().unwrap().get(&asn) {
+            if fetched_at.elapsed() < CACHE_TTL {
+                return info.clone();
+            }
+        }
+        match self.lookup.lookup(client, asn).await {
+            Ok(info) => {
+                debug!(
+                    holder = info.holder,
+                    country = info.country,
+                    "Looked up the ASN"
+                );
+                let mut entries = self.entries.lock().unwrap();
+                entries.insert(asn, (Instant::now(), info.clone()));
+                info
+            }
+            Err(e) => AsnInfo {
+                asn,
+                holder: None,
+                country: None,
+                peeringdb: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
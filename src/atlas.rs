@@ -0,0 +1,292 @@
+use anyhow::{bail, Context, Result};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+use url::Url;
+
+use crate::beacon::BeaconPair;
+use crate::resolver::AddressFamily;
+use crate::verdict::Verdict;
+
+/// Base URL of the RIPE Atlas API.
+pub const DEFAULT_URL: &str = "https://atlas.ripe.net/api/v2/";
+/// Interval between the polls of the status of the measurements.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// RIPE Atlas API, tracing the paths to the beacons from external vantage points.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Atlas {
+    /// API key allowed to create measurements
+    pub key: String,
+    #[serde(default = "default_url")]
+    pub url: Url,
+}
+
+fn default_url() -> Url {
+    Url::parse(DEFAULT_URL).unwrap()
+}
+
+/// Probes the measurements are run from.
+#[derive(Clone, Debug)]
+pub enum Probes {
+    /// Up to `count` probes of an ASN
+    Asn { asn: u32, count: u32 },
+    /// Probes by ID
+    Ids(Vec<u32>),
+}
+
+/// Verdict of a RIPE Atlas probe, from its traceroutes to the beacons.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProbeVerdict {
+    pub probe: u32,
+    /// Public address of the probe
+    pub from: Option<String>,
+    pub valid_reached: bool,
+    pub invalid_reached: bool,
+    pub verdict: Verdict,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AtlasResult {
+    pub name: String,
+    pub valid_measurement: u64,
+    pub invalid_measurement: u64,
+    pub probes: Vec<ProbeVerdict>,
+}
+
+impl AtlasResult {
+    /// Number of probes having a verdict.
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.probes
+            .iter()
+            .filter(|probe| probe.verdict == verdict)
+            .count()
+    }
+}
+
+#[derive(Deserialize)]
+struct Created {
+    measurements: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+struct Measurement {
+    status: Status,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TracerouteResult {
+    prb_id: u32,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    dst_addr: Option<String>,
+    #[serde(default)]
+    result: Vec<HopResult>,
+}
+
+#[derive(Deserialize)]
+struct HopResult {
+    #[serde(default)]
+    result: Vec<Reply>,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    #[serde(default)]
+    from: Option<String>,
+}
+
+impl TracerouteResult {
+    /// Whether the traceroute reached its destination.
+    fn reached(&self) -> bool {
+        let Some(destination) = &self.dst_addr else {
+            return false;
+        };
+        self.result
+            .iter()
+            .flat_map(|hop| &hop.result)
+            .any(|reply| reply.from.as_ref() == Some(destination))
+    }
+}
+
+/// Send a request to the API, its error being reported along with the detail of the response.
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("RIPE Atlas API returned {}: {}", status, body.trim());
+    }
+    Ok(response.json().await?)
+}
+
+impl Atlas {
+    fn definition(
+        &self,
+        pair: &BeaconPair,
+        url: &Url,
+        family: AddressFamily,
+    ) -> Result<serde_json::Value> {
+        let host = url.host_str().context("beacon URL without host")?;
+        Ok(json!({
+            "type": "traceroute",
+            "af": match family {
+                AddressFamily::Ipv4 => 4,
+                AddressFamily::Ipv6 => 6,
+            },
+            "target": host,
+            "resolve_on_probe": true,
+            "protocol": "ICMP",
+            "description": format!("rovcheck {} beacon {}", pair.name, host),
+        }))
+    }
+
+    /// Create one-off traceroute measurements to the valid and invalid beacons of a pair,
+    /// returning their IDs.
+    async fn create(
+        &self,
+        client: &Client,
+        pair: &BeaconPair,
+        probes: &Probes,
+        family: AddressFamily,
+    ) -> Result<(u64, u64)> {
+        let probes = match probes {
+            Probes::Asn { asn, count } => json!({
+                "type": "asn",
+                "value": asn.to_string(),
+                "requested": count,
+            }),
+            Probes::Ids(ids) => json!({
+                "type": "probes",
+                "value": ids.iter().map(u32::to_string).collect::<Vec<_>>().join(","),
+                "requested": ids.len(),
+            }),
+        };
+        let body = json!({
+            "definitions": [
+                self.definition(pair, &pair.valid_url, family)?,
+                self.definition(pair, &pair.invalid_url, family)?,
+            ],
+            "probes": [probes],
+            "is_oneoff": true,
+        });
+        let request = client
+            .post(self.url.join("measurements/")?)
+            .header(AUTHORIZATION, format!("Key {}", self.key))
+            .json(&body);
+        let created: Created = send(request).await?;
+        match created.measurements.as_slice() {
+            [valid, invalid] => Ok((*valid, *invalid)),
+            measurements => bail!("unexpected measurements {:?} created", measurements),
+        }
+    }
+
+    /// Whether a measurement is over: stopped, or failed to be scheduled.
+    async fn stopped(&self, client: &Client, id: u64) -> Result<bool> {
+        let url = self.url.join(&format!("measurements/{}/", id))?;
+        let measurement: Measurement = send(client.get(url)).await?;
+        debug!(measurement = id, status = %measurement.status.name, "Measurement status");
+        Ok(!matches!(
+            measurement.status.name.as_str(),
+            "Specified" | "Scheduled" | "Ongoing"
+        ))
+    }
+
+    async fn results(&self, client: &Client, id: u64) -> Result<Vec<TracerouteResult>> {
+        let url = self.url.join(&format!("measurements/{}/results/", id))?;
+        send(client.get(url)).await
+    }
+
+    /// Trace the paths to the beacons of a pair from RIPE Atlas probes, waiting up to `wait`
+    /// for the measurements to be over, and give the verdict of each probe.
+    pub async fn measure(
+        &self,
+        client: &Client,
+        pair: &BeaconPair,
+        probes: &Probes,
+        family: AddressFamily,
+        wait: Duration,
+    ) -> Result<AtlasResult> {
+        let (valid_measurement, invalid_measurement) =
+            self.create(client, pair, probes, family).await?;
+        info!(
+            "Created RIPE Atlas measurements {} and {}, waiting for their results",
+            valid_measurement, invalid_measurement
+        );
+
+        let start = Instant::now();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL.min(wait.saturating_sub(start.elapsed()))).await;
+            let (valid, invalid) = tokio::try_join!(
+                self.stopped(client, valid_measurement),
+                self.stopped(client, invalid_measurement)
+            )?;
+            if valid && invalid {
+                break;
+            }
+            if start.elapsed() >= wait {
+                info!("Measurements still running, using their results so far");
+                break;
+            }
+        }
+
+        let (valid, invalid) = tokio::try_join!(
+            self.results(client, valid_measurement),
+            self.results(client, invalid_measurement)
+        )?;
+        // Whether each probe reached the valid and invalid beacons
+        let mut reached = BTreeMap::<u32, (Option<String>, Option<bool>, Option<bool>)>::new();
+        for result in &valid {
+            let entry = reached.entry(result.prb_id).or_default();
+            entry.0 = entry.0.take().or(result.from.clone());
+            entry.1 = Some(result.reached());
+        }
+        for result in &invalid {
+            let entry = reached.entry(result.prb_id).or_default();
+            entry.0 = entry.0.take().or(result.from.clone());
+            entry.2 = Some(result.reached());
+        }
+        let probes = reached
+            .into_iter()
+            .map(|(probe, (from, valid, invalid))| {
+                let (verdict, reason) = match (valid, invalid) {
+                    (Some(valid), Some(invalid)) => {
+                        let (verdict, reason) = Verdict::of_pair(valid, invalid);
+                        (verdict, reason.to_string())
+                    }
+                    _ => (
+                        Verdict::Inconclusive,
+                        "missing the result of a beacon".to_string(),
+                    ),
+                };
+                ProbeVerdict {
+                    probe,
+                    from,
+                    valid_reached: valid.unwrap_or(false),
+                    invalid_reached: invalid.unwrap_or(false),
+                    verdict,
+                    reason,
+                }
+            })
+            .collect();
+        Ok(AtlasResult {
+            name: pair.name.clone(),
+            valid_measurement,
+            invalid_measurement,
+            probes,
+        })
+    }
+}
@@ -0,0 +1,637 @@
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder, Version};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, debug_span, Instrument};
+use url::Url;
+
+use crate::dns;
+use crate::http3;
+use crate::icmp;
+use crate::matcher::Matcher;
+use crate::prefix_info::PrefixInfo;
+use crate::probe::{self, Connect, Timings};
+use crate::ratelimit::RateLimit;
+use crate::resolver::Resolution;
+use crate::rpki::BeaconRpki;
+use crate::tls::Fingerprint;
+use crate::traceroute::Traceroute;
+
+/// Default `User-Agent` of the beacon requests, identifying the probes to the beacon operators.
+pub const USER_AGENT: &str = concat!("rovcheck/", env!("CARGO_PKG_VERSION"));
+
+/// Header added to the beacon requests.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RequestHeader {
+    pub name: HeaderName,
+    pub value: HeaderValue,
+}
+
+impl RequestHeader {
+    pub fn new(name: HeaderName, value: &str) -> Result<Self, String> {
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value `{}` of header {}", value, name))?;
+        Ok(RequestHeader { name, value })
+    }
+}
+
+impl FromStr for RequestHeader {
+    type Err = String;
+
+    /// `Name: value`, like the option of curl.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, value)) = s.split_once(':') else {
+            return Err(format!("invalid header `{}`, expected NAME: VALUE", s));
+        };
+        let name = HeaderName::from_str(name.trim())
+            .map_err(|_| format!("invalid header name `{}`", name.trim()))?;
+        RequestHeader::new(name, value.trim())
+    }
+}
+
+impl TryFrom<String> for RequestHeader {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RequestHeader> for String {
+    fn from(header: RequestHeader) -> Self {
+        header.to_string()
+    }
+}
+
+impl std::fmt::Display for RequestHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.name,
+            String::from_utf8_lossy(self.value.as_bytes())
+        )
+    }
+}
+
+/// How a beacon response is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BeaconKind {
+    /// isbgpsafeyet JSON response, requested with a random ID appended to the URL
+    IsBgpSafeYet,
+    /// Any successful HTTP response
+    Http,
+    /// Any response of an authoritative DNS server, queried at a `dns://SERVER/NAME` URL
+    Dns,
+}
+
+/// How the invalid beacon of a pair is RPKI invalid, many networks only dropping some of the
+/// invalid routes.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Invalidity {
+    /// Announced from an origin not authorized by the ROAs of its prefix
+    #[default]
+    Origin,
+    /// Announced more specific than the maximum length of the ROA of its origin
+    MaxLength,
+    /// Covered by an AS0 ROA, authorizing no origin
+    As0,
+    /// Not covered by any ROA, the beacon being RPKI unknown rather than invalid and only
+    /// unreachable in the networks dropping more than the invalid routes
+    Unknown,
+}
+
+impl FromStr for Invalidity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "origin" => Ok(Invalidity::Origin),
+            "max-length" => Ok(Invalidity::MaxLength),
+            "as0" => Ok(Invalidity::As0),
+            "unknown" => Ok(Invalidity::Unknown),
+            _ => bail!("unknown invalidity `{}`", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Invalidity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Invalidity::Origin => write!(f, "origin"),
+            Invalidity::MaxLength => write!(f, "max-length"),
+            Invalidity::As0 => write!(f, "as0"),
+            Invalidity::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl Invalidity {
+    /// Routes of the invalid beacons of this type, e.g. `max-length-invalids`.
+    pub fn routes(&self) -> &'static str {
+        match self {
+            Invalidity::Origin => "origin-invalids",
+            Invalidity::MaxLength => "max-length-invalids",
+            Invalidity::As0 => "AS0-invalids",
+            Invalidity::Unknown => "RPKI-unknowns",
+        }
+    }
+}
+
+/// A pair of beacons, one in an RPKI-valid prefix and one in an RPKI-invalid prefix.
+#[derive(Clone, Debug, Serialize)]
+pub struct BeaconPair {
+    pub name: String,
+    pub kind: BeaconKind,
+    pub valid_url: Url,
+    pub invalid_url: Url,
+    /// How the invalid beacon is RPKI invalid
+    pub invalidity: Invalidity,
+    /// Overrides the global requests timeout
+    pub timeout: Option<Duration>,
+    /// Overrides the global proxy
+    pub proxy: Option<Url>,
+    /// Name sent as SNI and `Host` to the beacons given by a literal IP address
+    pub sni: Option<String>,
+    /// Fingerprints of the certificates expected in the chain of the valid beacon
+    pub pins: Vec<Fingerprint>,
+    /// How the responses of `http` beacons are told successful [default: any success status]
+    pub matcher: Option<Matcher>,
+}
+
+/// Retry policy applied to each beacon request.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub retries: u32,
+    /// Base delay of the exponential backoff, growing up to 1 minute
+    pub backoff: Duration,
+}
+
+/// Longest delay the backoff grows to, unless its base delay is longer.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+impl RetryPolicy {
+    /// Delay before the given retry (starting at 0), with a jitter of +/- 50%.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_BACKOFF.max(self.backoff));
+        let jitter = rand::random_range(0.5..1.5);
+        Duration::try_from_secs_f64(delay.as_secs_f64() * jitter).unwrap_or(delay)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IsBgpSafeYet {
+    status: String,
+    asn: u32,
+    name: String,
+    blackholed: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BeaconResult {
+    pub url: String,
+    pub success: bool,
+    pub attempts: u32,
+    pub trials: u32,
+    pub successes: u32,
+    pub asn: Option<u32>,
+    pub name: Option<String>,
+    /// Status reported by an isbgpsafeyet beacon, `valid` or `invalid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Whether an isbgpsafeyet beacon reported the requests as blackholed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub blackholed: bool,
+    pub error: Option<String>,
+    pub timings: Option<Timings>,
+    /// HTTP version of the beacon response, e.g. `HTTP/2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_version: Option<String>,
+    /// Fingerprints of the certificate chain presented by the beacon, leaf first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub certificates: Vec<Fingerprint>,
+    /// Whether none of the certificates presented by the beacon is pinned,
+    /// a TLS middlebox answering on its behalf
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub intercepted: bool,
+    /// Resolution of the beacon hostname, unless reached through a proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Resolution>,
+    /// Path to the beacon, traced if its pair is not enforcing ROV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceroute: Option<Traceroute>,
+    /// RPKI state of the routes to the beacon, verified before judging ROV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpki: Option<BeaconRpki>,
+    /// Prefix announced covering the beacon according to bgp.tools or BGPView
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix_info: Option<PrefixInfo>,
+    /// Results of the requests to each address the beacon hostname resolves to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<AddressResult>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Result of the requests to one of the addresses of a beacon.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AddressResult {
+    pub ip: IpAddr,
+    pub success: bool,
+    pub attempts: u32,
+    pub error: Option<String>,
+    pub duration_ms: f64,
+}
+
+impl BeaconResult {
+    pub fn duration(&self) -> Duration {
+        (self.finished_at - self.started_at)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+/// How the beacon requests are sent.
+#[derive(Clone)]
+pub enum BeaconClient {
+    Http(Client),
+    /// HTTP/3, over a new QUIC connection for each request
+    Http3 {
+        connect: Box<Connect>,
+        timeout: Duration,
+        headers: Vec<RequestHeader>,
+    },
+    /// TCP connects only, on the given port
+    Tcp {
+        connect: Box<Connect>,
+        port: u16,
+        timeout: Duration,
+    },
+    /// ICMP echo requests
+    Icmp {
+        connect: Box<Connect>,
+        timeout: Duration,
+    },
+    /// DNS queries, of the DNS beacons
+    Dns {
+        connect: Box<Connect>,
+        timeout: Duration,
+    },
+}
+
+fn request(client: &Client, url: Url, timeout: Option<Duration>) -> RequestBuilder {
+    let request = client.get(url);
+    match timeout {
+        Some(timeout) => request.timeout(timeout),
+        None => request,
+    }
+}
+
+/// Error message of a failed request, telling a connect timeout, as expected when the route
+/// to a beacon is dropped, apart from a slow response.
+fn describe(e: &(dyn std::error::Error + 'static)) -> String {
+    match e.downcast_ref::<reqwest::Error>() {
+        Some(e) if e.is_connect() && e.is_timeout() => format!("connect timed out: {}", e),
+        Some(e) if e.is_timeout() => format!("timed out: {}", e),
+        // Tell name resolution failures apart from unreachable beacons
+        Some(e) if e.is_connect() => {
+            let mut description = e.to_string();
+            let mut source = std::error::Error::source(e);
+            while let Some(e) = source {
+                description.push_str(&format!(": {}", e));
+                source = e.source();
+            }
+            description
+        }
+        _ => e.to_string(),
+    }
+}
+
+fn version_name(version: Version) -> String {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => return format!("{:?}", version),
+    }
+    .to_string()
+}
+
+async fn get_url(
+    request: RequestBuilder,
+    version: &mut Option<String>,
+) -> Result<IsBgpSafeYet, Box<dyn std::error::Error>> {
+    let response = request.send().await?;
+    *version = Some(version_name(response.version()));
+    let isbgpsafeyet = response.json::<IsBgpSafeYet>().await?;
+    Ok(isbgpsafeyet)
+}
+
+async fn fetch_url(
+    request: RequestBuilder,
+    version: &mut Option<String>,
+    matcher: Option<&Matcher>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let response = request.send().await?;
+    *version = Some(version_name(response.version()));
+    match matcher {
+        Some(matcher) => {
+            let status = response.status().as_u16();
+            let body = response.bytes().await?;
+            matcher
+                .check(status, &body)
+                .map_err(|e| format!("{:#}", e))?;
+        }
+        None => {
+            response.error_for_status()?;
+        }
+    }
+    Ok(())
+}
+
+async fn get_http3(
+    url: &Url,
+    kind: BeaconKind,
+    matcher: Option<&Matcher>,
+    timeout: Duration,
+    connect: &Connect,
+    headers: &[RequestHeader],
+    version: &mut Option<String>,
+) -> Result<Option<IsBgpSafeYet>, Box<dyn std::error::Error>> {
+    let response = http3::get(url, timeout, connect, headers)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    *version = Some("HTTP/3".to_string());
+    match (kind, matcher) {
+        (BeaconKind::IsBgpSafeYet, _) => Ok(Some(serde_json::from_slice(&response.body)?)),
+        (BeaconKind::Http, Some(matcher)) => {
+            matcher
+                .check(response.status, &response.body)
+                .map_err(|e| format!("{:#}", e))?;
+            Ok(None)
+        }
+        (BeaconKind::Http | BeaconKind::Dns, _) if response.status >= 400 => {
+            Err(format!("HTTP status {} for url ({})", response.status, url).into())
+        }
+        (BeaconKind::Http | BeaconKind::Dns, _) => Ok(None),
+    }
+}
+
+pub async fn check_beacon(
+    client: &BeaconClient,
+    pair: &BeaconPair,
+    url: Url,
+    retry: RetryPolicy,
+    limit: Option<&RateLimit>,
+) -> anyhow::Result<BeaconResult> {
+    let started_at = Utc::now();
+    let mut result = BeaconResult {
+        url: url.to_string(),
+        success: false,
+        attempts: 0,
+        trials: 1,
+        successes: 0,
+        asn: None,
+        name: None,
+        status: None,
+        blackholed: false,
+        error: None,
+        timings: None,
+        http_version: None,
+        certificates: Vec::new(),
+        intercepted: false,
+        dns: None,
+        traceroute: None,
+        rpki: None,
+        prefix_info: None,
+        addresses: Vec::new(),
+        started_at,
+        finished_at: started_at,
+    };
+
+    while result.attempts <= retry.retries {
+        if result.attempts > 0 {
+            let delay = retry.delay(result.attempts - 1);
+            debug!("Retrying {} in {:?}", result.url, delay);
+            tokio::time::sleep(delay).await;
+        }
+        // Every attempt counts against the rate limit of the host
+        if let (Some(limit), Some(host)) = (limit, url.host_str()) {
+            limit.acquire(host).await?;
+        }
+        result.attempts += 1;
+
+        let span = debug_span!("request", attempt = result.attempts);
+        let response = match client {
+            BeaconClient::Http(client) => {
+                let request = request(client, url.clone(), pair.timeout);
+                let version = &mut result.http_version;
+                match pair.kind {
+                    BeaconKind::IsBgpSafeYet => {
+                        get_url(request, version).instrument(span).await.map(Some)
+                    }
+                    BeaconKind::Http | BeaconKind::Dns => {
+                        fetch_url(request, version, pair.matcher.as_ref())
+                            .instrument(span)
+                            .await
+                            .map(|_| None)
+                    }
+                }
+            }
+            BeaconClient::Http3 {
+                connect,
+                timeout,
+                headers,
+            } => {
+                let timeout = pair.timeout.unwrap_or(*timeout);
+                let version = &mut result.http_version;
+                let matcher = pair.matcher.as_ref();
+                get_http3(&url, pair.kind, matcher, timeout, connect, headers, version)
+                    .instrument(span)
+                    .await
+            }
+            BeaconClient::Tcp {
+                connect,
+                port,
+                timeout,
+            } => {
+                let timeout = pair.timeout.unwrap_or(*timeout);
+                match probe::tcp(&url, *port, timeout, connect)
+                    .instrument(span)
+                    .await
+                {
+                    Ok(addr) => {
+                        debug!("Connected to {}", addr);
+                        Ok(None)
+                    }
+                    Err(e) => Err(format!("{:#}", e).into()),
+                }
+            }
+            BeaconClient::Dns { connect, timeout } => {
+                let timeout = pair.timeout.unwrap_or(*timeout);
+                match dns::query_beacon(&url, timeout, connect)
+                    .instrument(span)
+                    .await
+                {
+                    Ok(rcode) => {
+                        debug!("DNS response: {}", rcode);
+                        Ok(None)
+                    }
+                    Err(e) => Err(format!("{:#}", e).into()),
+                }
+            }
+            BeaconClient::Icmp { connect, timeout } => {
+                let timeout = pair.timeout.unwrap_or(*timeout);
+                match icmp::ping(&url, timeout, connect).instrument(span).await {
+                    Ok((ip, rtt)) => {
+                        debug!("Echo reply from {} in {:?}", ip, rtt);
+                        Ok(None)
+                    }
+                    Err(e) => Err(format!("{:#}", e).into()),
+                }
+            }
+        };
+
+        match response {
+            Ok(response) => {
+                debug!("Response: {:?}", response);
+                result.success = true;
+                result.successes = 1;
+                result.error = None;
+                if let Some(response) = response {
+                    result.asn = Some(response.asn);
+                    result.name = Some(response.name);
+                    result.status = Some(response.status);
+                    result.blackholed = response.blackholed;
+                }
+                break;
+            }
+
+            Err(e) => {
+                debug!("Error: {}", e);
+                result.error = Some(describe(e.as_ref()));
+            }
+        }
+    }
+
+    result.finished_at = Utc::now();
+    debug!(
+        beacon = %pair.name,
+        url = %result.url,
+        success = result.success,
+        attempts = result.attempts,
+        duration_ms = result.duration().as_millis() as u64,
+        "Beacon request finished"
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_headers() {
+        let header: RequestHeader = "X-Probe:  ams1 ".parse().unwrap();
+        assert_eq!(header.name, "x-probe");
+        assert_eq!(header.value, "ams1");
+        assert_eq!(header.to_string(), "x-probe: ams1");
+        // Only split on the first colon
+        let header: RequestHeader = "Referer: https://example.com/".parse().unwrap();
+        assert_eq!(header.value, "https://example.com/");
+        let header: RequestHeader = "X-Empty:".parse().unwrap();
+        assert_eq!(header.value, "");
+
+        assert_eq!(
+            "X-Probe".parse::<RequestHeader>().unwrap_err(),
+            "invalid header `X-Probe`, expected NAME: VALUE"
+        );
+        assert_eq!(
+            "X Probe: ams1".parse::<RequestHeader>().unwrap_err(),
+            "invalid header name `X Probe`"
+        );
+        assert_eq!(
+            ": ams1".parse::<RequestHeader>().unwrap_err(),
+            "invalid header name ``"
+        );
+        assert_eq!(
+            "X-Probe: ams\n1".parse::<RequestHeader>().unwrap_err(),
+            "invalid value `ams\n1` of header x-probe"
+        );
+    }
+
+    #[test]
+    fn retry_delays() {
+        let retry = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(500),
+        };
+        for (attempt, base) in [(0, 500), (1, 1000), (2, 2000), (3, 4000)] {
+            for _ in 0..100 {
+                let delay = retry.delay(attempt).as_millis();
+                assert!(
+                    delay >= base / 2 && delay < base * 3 / 2,
+                    "{}: {}",
+                    attempt,
+                    delay
+                );
+            }
+        }
+        // Capped at a minute, without overflowing
+        for attempt in [7, 8, 32, 64, u32::MAX] {
+            let delay = retry.delay(attempt);
+            assert!(delay >= Duration::from_secs(30) && delay < Duration::from_secs(90));
+        }
+        // Unless the base delay is longer
+        let retry = RetryPolicy {
+            retries: 1,
+            backoff: Duration::MAX,
+        };
+        assert!(retry.delay(u32::MAX) >= Duration::MAX / 2);
+        assert_eq!(RetryPolicy::default().delay(10), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limited() {
+        let state =
+            std::env::temp_dir().join(format!("rovcheck-test-retries-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&state);
+        let limit = RateLimit {
+            per_minute: Some(10),
+            state: Some(state.clone()),
+            ..Default::default()
+        };
+        let pair = crate::preset::get("cloudflare").unwrap();
+        let retry = RetryPolicy {
+            retries: 2,
+            backoff: Duration::ZERO,
+        };
+        let client = BeaconClient::Http(Client::new());
+        let url = Url::parse("http://127.0.0.1:1/").unwrap();
+        let result = check_beacon(&client, &pair, url, retry, Some(&limit))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert_eq!(result.attempts, 3);
+        // Each attempt is accounted for, not only the first one
+        let state: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&state).unwrap()).unwrap();
+        assert_eq!(state["requests"]["127.0.0.1"].as_array().unwrap().len(), 3);
+        std::fs::remove_file(limit.state.unwrap()).unwrap();
+    }
+}
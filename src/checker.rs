@@ -0,0 +1,1871 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
+use futures::future::{join_all, try_join_all};
+use nanoid::nanoid;
+use reqwest::header::USER_AGENT as USER_AGENT_HEADER;
+use reqwest::{Certificate, Client, Proxy};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, instrument, Instrument};
+use url::{Host, Url};
+
+use crate::asn::{AsnCache, AsnInfo, AsnLookup};
+use crate::beacon::{
+    check_beacon, AddressResult, BeaconClient, BeaconKind, BeaconPair, BeaconResult, Invalidity,
+    RequestHeader, RetryPolicy, USER_AGENT,
+};
+use crate::confidence::{self, Confidence};
+use crate::control::{Control, ControlKind, ControlResult};
+use crate::dns::{self, NameServer};
+use crate::looking_glass::{LookingGlass, LookingGlassResult};
+use crate::netns;
+use crate::prefix_info::PrefixLookup;
+use crate::preflight::{self, Preflight, PreflightResult};
+use crate::preset;
+use crate::probe;
+use crate::radar::{Radar, RadarResult};
+use crate::ratelimit::RateLimit;
+use crate::resolver::{AddressFamily, ResolveOverride, Resolver};
+use crate::ris::{self, Ris, RisResult};
+use crate::rpki::{self, Rpki, Validator, Validity};
+use crate::signing::{self, Signature};
+use crate::tls::{self, Fingerprint, TlsVersion};
+use crate::traceroute::{self, PathAnalysis, TracerouteMode};
+use crate::verdict::{Quorum, Verdict};
+
+/// Names of the labels of the metrics, which the labels of the results cannot take.
+const RESERVED_LABELS: &[&str] = &[
+    "beacon", "validity", "family", "uplink", "protocol", "verdict",
+];
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The beacon pairs to check
+    pub pairs: Vec<BeaconPair>,
+    /// Alphabet to use for generating the ID
+    pub alphabet: String,
+    /// Requests timeout
+    pub timeout: Duration,
+    /// TCP connect timeout, bounded by the requests timeout
+    pub connect_timeout: Option<Duration>,
+    /// Proxy to reach the beacons through, unless overridden by the beacon pair
+    pub proxy: Option<Url>,
+    /// Local address the connections to the beacons are bound to
+    pub source_ip: Option<IpAddr>,
+    /// Network interfaces the connections to the beacons are bound to (Linux only),
+    /// the beacons being checked through each of them
+    pub uplinks: Vec<String>,
+    /// VRF device the connections to the beacons are bound to (Linux only),
+    /// to egress through its routing table
+    pub vrf: Option<String>,
+    /// DNS server the beacon hostnames are resolved with, instead of the system resolver
+    pub dns_server: Option<NameServer>,
+    /// Addresses overriding the resolution of beacon hosts and ports
+    pub resolve: Vec<ResolveOverride>,
+    /// Network namespace the beacons are checked from (Linux only)
+    pub netns: Option<String>,
+    /// Retry policy of each beacon request
+    pub retry: RetryPolicy,
+    /// Force the address family used to reach the beacons
+    pub family: Option<AddressFamily>,
+    /// Check the beacons over both IPv4 and IPv6, overrides `family`
+    pub dual_stack: bool,
+    /// Measure the duration of each phase of the beacon requests
+    pub timings: bool,
+    /// Also request the beacons at each address their hostname resolves to
+    pub all_ips: bool,
+    /// Also request the beacons over HTTP/3, for a separate verdict
+    pub http3: bool,
+    /// How the beacons are checked
+    pub mode: CheckMode,
+    /// Number of times each beacon is probed
+    pub trials: u32,
+    /// Fraction of successful trials for a beacon to be considered reachable,
+    /// defaults to a strict majority
+    pub trial_threshold: Option<f64>,
+    /// Quorum policy across beacon pairs, defaults to requiring all of them
+    pub quorum: Option<Quorum>,
+    /// Reuse of the connections to the beacons
+    pub connection_reuse: ConnectionReuse,
+    /// HTTP version the beacons are requested with, negotiated if not set
+    pub http_version: Option<HttpVersion>,
+    /// `User-Agent` of the beacon requests
+    pub user_agent: String,
+    /// Headers added to the beacon requests
+    pub headers: Vec<RequestHeader>,
+    /// Lowest TLS version accepted from the beacons
+    pub tls_min_version: Option<TlsVersion>,
+    /// PEM file of the CA certificates trusted along with the system ones
+    pub cacert: Option<PathBuf>,
+    /// Accept any certificate from the beacons
+    pub insecure: bool,
+    /// Record the fingerprints of the certificate chains of the valid beacons
+    pub certificates: bool,
+    /// Fingerprints of the certificates expected in the chain of every valid beacon,
+    /// along with the pins of the beacon pair
+    pub pins: Vec<Fingerprint>,
+    /// ASNs the beacons are expected to see the requests from, a pair seeing another one
+    /// being INCONCLUSIVE
+    pub expect_asn: Vec<u32>,
+    /// Judge the pairs whose isbgpsafeyet beacons report an unexpected status or blackholed
+    /// requests INCONCLUSIVE, instead of only warning
+    pub strict: bool,
+    /// Fetch checked before judging ROV, the verdict being INCONCLUSIVE if it fails
+    pub preflight: Option<Preflight>,
+    /// Trace the paths to the beacons of the pairs not enforcing ROV or inconclusive
+    pub traceroute: Option<TracerouteMode>,
+    /// Verify that the routes to the valid beacons are RPKI valid and the ones to the invalid
+    /// beacons RPKI invalid, the verdict of a pair being INCONCLUSIVE otherwise
+    pub rpki: Option<Rpki>,
+    /// Looking glasses the routes to the beacons are looked up on
+    pub looking_glasses: Vec<LookingGlass>,
+    /// Look up the invalid beacon prefix in RIS when its beacon is unreachable,
+    /// the verdict being INCONCLUSIVE if the prefix is not announced
+    pub ris: Option<Ris>,
+    /// Fetch the invalid beacon from a control vantage when its beacon is unreachable,
+    /// the verdict being INCONCLUSIVE if the control vantage cannot reach it either
+    pub control: Option<Control>,
+    /// Look up the holder, country and PeeringDB record of the ASN seen by the beacons
+    pub asn_info: Option<AsnLookup>,
+    /// Look up the ROV classification of the ASN seen by the beacons on Cloudflare Radar,
+    /// reported apart from the verdict
+    pub radar: Option<Radar>,
+    /// Look up the prefixes announced covering the beacons on bgp.tools or BGPView,
+    /// reported apart from the verdict
+    pub prefix_info: Option<PrefixLookup>,
+    /// Sign the results with this ed25519 key
+    pub signing_key: Option<SigningKey>,
+    /// Labels attached to the results, along with the `hostname`
+    pub labels: BTreeMap<String, String>,
+    /// Limits of the requests to the beacons
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            pairs: vec![preset::get("cloudflare").unwrap()],
+            alphabet: "1234567890abcdef".to_string(),
+            timeout: Duration::from_secs(3),
+            connect_timeout: None,
+            proxy: None,
+            source_ip: None,
+            uplinks: Vec::new(),
+            vrf: None,
+            dns_server: None,
+            resolve: Vec::new(),
+            netns: None,
+            retry: RetryPolicy::default(),
+            family: None,
+            dual_stack: false,
+            timings: false,
+            all_ips: false,
+            http3: false,
+            mode: CheckMode::default(),
+            trials: 1,
+            trial_threshold: None,
+            quorum: None,
+            connection_reuse: ConnectionReuse::default(),
+            http_version: None,
+            user_agent: USER_AGENT.to_string(),
+            headers: Vec::new(),
+            tls_min_version: None,
+            cacert: None,
+            insecure: false,
+            certificates: false,
+            pins: Vec::new(),
+            expect_asn: Vec::new(),
+            strict: false,
+            preflight: None,
+            traceroute: None,
+            rpki: None,
+            looking_glasses: Vec::new(),
+            ris: None,
+            control: None,
+            asn_info: None,
+            radar: None,
+            prefix_info: None,
+            signing_key: None,
+            labels: BTreeMap::new(),
+            rate_limit: None,
+        }
+    }
+}
+
+/// Reuse of the connections to the beacons, pooled connections surviving routing changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionReuse {
+    /// Open a new connection for each request
+    Never,
+    /// Reuse the connections across the trials and retries of a check, not across checks
+    #[default]
+    Check,
+    /// Keep the connections open across checks
+    Always,
+}
+
+impl FromStr for ConnectionReuse {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "never" => Ok(ConnectionReuse::Never),
+            "check" => Ok(ConnectionReuse::Check),
+            "always" => Ok(ConnectionReuse::Always),
+            _ => bail!("unknown connection reuse `{}`", s),
+        }
+    }
+}
+
+/// How the beacons are checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMode {
+    /// HTTP requests, the responses being interpreted according to the beacon kind
+    #[default]
+    Http,
+    /// TCP connects to port 443 of the beacon hosts, unless their URL has another port
+    Tcp,
+    /// ICMP echo requests to the beacon hosts
+    Icmp,
+}
+
+impl FromStr for CheckMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "http" => Ok(CheckMode::Http),
+            "tcp" => Ok(CheckMode::Tcp),
+            "icmp" => Ok(CheckMode::Icmp),
+            _ => bail!("unknown check mode `{}`", s),
+        }
+    }
+}
+
+impl std::fmt::Display for CheckMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckMode::Http => write!(f, "http"),
+            CheckMode::Tcp => write!(f, "tcp"),
+            CheckMode::Icmp => write!(f, "icmp"),
+        }
+    }
+}
+
+/// HTTP version the beacons are requested with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HttpVersion {
+    #[serde(rename = "1.1")]
+    Http11,
+    #[serde(rename = "2")]
+    Http2,
+}
+
+impl FromStr for HttpVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.1" => Ok(HttpVersion::Http11),
+            "2" => Ok(HttpVersion::Http2),
+            _ => bail!("unsupported HTTP version `{}`, expected 1.1 or 2", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PairResult {
+    pub name: String,
+    pub family: Option<AddressFamily>,
+    /// Network interface the beacons were checked through, if several uplinks were checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uplink: Option<String>,
+    /// Whether the beacons were requested over HTTP/3
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub http3: bool,
+    /// How the invalid beacon is RPKI invalid
+    #[serde(default)]
+    pub invalidity: Invalidity,
+    pub verdict: Verdict,
+    pub reason: String,
+    /// Unexpected contents of the beacon responses
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    pub valid: BeaconResult,
+    pub invalid: BeaconResult,
+    /// Routes to the invalid beacon prefix seen by RIS, looked up if ROV looked enforced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ris: Option<RisResult>,
+    /// Reachability of the invalid beacon from the control vantage, fetched if ROV looked
+    /// enforced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control: Option<ControlResult>,
+    /// Routes to the beacons seen by the looking glasses
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub looking_glasses: Vec<LookingGlassResult>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FamilyVerdict {
+    pub family: AddressFamily,
+    pub verdict: Verdict,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InvalidityVerdict {
+    pub invalidity: Invalidity,
+    pub verdict: Verdict,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UplinkVerdict {
+    pub uplink: String,
+    pub verdict: Verdict,
+    pub reason: String,
+    /// Verdicts per address family through this uplink
+    pub families: Vec<FamilyVerdict>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Http3Verdict {
+    pub verdict: Verdict,
+    pub reason: String,
+    /// Verdicts per address family over HTTP/3
+    pub families: Vec<FamilyVerdict>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RovResult {
+    pub verdict: Verdict,
+    pub reason: String,
+    /// Verdicts per address family, empty if no address family was forced
+    pub families: Vec<FamilyVerdict>,
+    /// Verdicts per uplink, empty unless several uplinks were checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uplinks: Vec<UplinkVerdict>,
+    /// Verdicts per type of invalid beacon, empty unless several types were checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalidities: Vec<InvalidityVerdict>,
+    /// VRF the beacons were checked through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrf: Option<String>,
+    /// Verdict of the beacon requests over HTTP/3, apart from the main verdict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http3: Option<Http3Verdict>,
+    /// Preflight fetched before judging ROV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preflight: Option<PreflightResult>,
+    /// Holder, country and PeeringDB record of the ASN seen by the beacons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn_info: Option<AsnInfo>,
+    /// ROV classification of the ASN by Cloudflare Radar, advisory only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radar: Option<RadarResult>,
+    /// Confidence in the verdict, none if it is INCONCLUSIVE
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    pub pairs: Vec<PairResult>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Labels of the probe, e.g. `site=ams1`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Signature of the result by the probe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+}
+
+/// Paths to the beacons of a pair, analyzed from an egress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PairPaths {
+    pub name: String,
+    pub family: Option<AddressFamily>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uplink: Option<String>,
+    pub valid: PathAnalysis,
+    pub invalid: PathAnalysis,
+}
+
+/// Way out to the beacons: the uplink and address family they are checked through.
+#[derive(Clone)]
+struct Egress {
+    uplink: Option<String>,
+    /// Network device the connections are bound to: the uplink or the VRF
+    interface: Option<String>,
+    family: Option<AddressFamily>,
+    /// Clients of the valid and invalid beacons of each pair
+    clients: Vec<(BeaconClient, BeaconClient)>,
+}
+
+#[derive(Clone)]
+pub struct RovChecker {
+    config: Config,
+    egresses: Vec<Egress>,
+    /// TLS configuration of the probe and HTTP/3 connections
+    tls: Arc<tokio_rustls::rustls::ClientConfig>,
+    /// Metadata of the ASNs seen by the beacons, cached across the checks
+    asn_cache: AsnCache,
+}
+
+/// Headers of the beacon requests, starting with the `User-Agent` unless given among the headers.
+fn headers(config: &Config) -> Result<Vec<RequestHeader>> {
+    let mut headers = Vec::new();
+    if !config
+        .headers
+        .iter()
+        .any(|header| header.name == USER_AGENT_HEADER)
+    {
+        headers.push(
+            RequestHeader::new(USER_AGENT_HEADER, &config.user_agent)
+                .map_err(anyhow::Error::msg)?,
+        );
+    }
+    headers.extend(config.headers.iter().cloned());
+    Ok(headers)
+}
+
+fn build_client(
+    config: &Config,
+    interface: Option<&str>,
+    family: Option<AddressFamily>,
+    proxy: Option<&Url>,
+    overrides: Vec<(String, Vec<IpAddr>)>,
+) -> Result<Client> {
+    let mut builder = Client::builder().timeout(config.timeout).default_headers(
+        headers(config)?
+            .into_iter()
+            .map(|header| (header.name, header.value))
+            .collect(),
+    );
+    if config.connection_reuse == ConnectionReuse::Never {
+        builder = builder.pool_max_idle_per_host(0);
+    }
+    // Over TLS, only the pinned version is offered with ALPN
+    builder = match config.http_version {
+        Some(HttpVersion::Http11) => builder.http1_only(),
+        Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+        None => builder,
+    };
+    if let Some(proxy) = proxy {
+        builder = builder
+            .proxy(Proxy::all(proxy.as_str()).with_context(|| format!("invalid proxy {}", proxy))?);
+    }
+    if let Some(connect_timeout) = config.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(version) = config.tls_min_version {
+        builder = builder.tls_version_min(version.into());
+    }
+    if let Some(cacert) = &config.cacert {
+        for certificate in tls::certificates(cacert)? {
+            builder = builder.add_root_certificate(Certificate::from_der(&certificate)?);
+        }
+    }
+    if config.insecure {
+        builder = builder.tls_danger_accept_invalid_certs(true);
+    }
+    if let Some(interface) = interface {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        {
+            builder = builder.interface(interface);
+        }
+        #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+        bail!(
+            "binding to interface {} is not supported on this platform",
+            interface
+        );
+    }
+    // Only resolve the beacons to addresses of the family of the source address
+    let family = family.or(config.source_ip.as_ref().map(AddressFamily::of));
+    if let Some(family) = family {
+        builder = builder.local_address(config.source_ip.unwrap_or(family.unspecified()));
+    }
+    if family.is_some() || config.dns_server.is_some() || !overrides.is_empty() {
+        builder = builder.dns_resolver(Arc::new(resolver(config, family, overrides)));
+    }
+    Ok(builder.build()?)
+}
+
+impl RovResult {
+    /// ASN reported by the first beacon that answered.
+    pub fn asn(&self) -> Option<u32> {
+        self.pairs
+            .iter()
+            .flat_map(|pair| [&pair.valid, &pair.invalid])
+            .find_map(|beacon| beacon.asn)
+    }
+
+    /// ASN seen by the beacons along with its name and country if looked up,
+    /// e.g. `AS64500 (ExampleNet, DE)`.
+    pub fn network(&self) -> Option<String> {
+        let info = self.asn_info.as_ref()?;
+        match info.describe().as_str() {
+            "" => Some(format!("AS{}", info.asn)),
+            description => Some(format!("AS{} ({})", info.asn, description)),
+        }
+    }
+}
+
+impl PairResult {
+    /// Name of the pair along with the address family, uplink and protocol it was checked through,
+    /// e.g. `cloudflare (ipv6, eth1, HTTP/3)`.
+    pub fn label(&self) -> String {
+        let through = self
+            .family
+            .map(|family| family.to_string())
+            .into_iter()
+            .chain(self.uplink.clone())
+            .chain(self.http3.then(|| "HTTP/3".to_string()))
+            .collect::<Vec<_>>();
+        label(&self.name, &through)
+    }
+}
+
+impl PairPaths {
+    /// Name of the pair along with the address family and uplink the paths were analyzed from.
+    pub fn label(&self) -> String {
+        let through = self
+            .family
+            .map(|family| family.to_string())
+            .into_iter()
+            .chain(self.uplink.clone())
+            .collect::<Vec<_>>();
+        label(&self.name, &through)
+    }
+}
+
+fn label(name: &str, through: &[String]) -> String {
+    if through.is_empty() {
+        return name.to_string();
+    }
+    format!("{} ({})", name, through.join(", "))
+}
+
+/// Combined verdict of several pair results, along with its reason, the pairs excluded from the
+/// quorum being listed in it.
+fn combine(
+    pairs: &[&PairResult],
+    quorum: Option<Quorum>,
+    excluded: &[&PairResult],
+) -> (Verdict, String) {
+    let verdicts = pairs.iter().map(|pair| pair.verdict);
+    if let Some(quorum) = quorum {
+        let verdict = quorum.verdict(verdicts);
+        let enforced = pairs
+            .iter()
+            .filter(|pair| pair.verdict == Verdict::Enforced)
+            .count();
+        let mut reason = format!(
+            "{}/{} beacon pairs enforcing ROV (quorum {})",
+            enforced,
+            pairs.len(),
+            quorum
+        );
+        if !excluded.is_empty() {
+            let labels = excluded
+                .iter()
+                .map(|pair| pair.label())
+                .collect::<Vec<_>>()
+                .join(", ");
+            reason.push_str(&format!(", RPKI-unknown {} excluded", labels));
+        }
+        return (verdict, reason);
+    }
+
+    let verdict = Verdict::combine(verdicts);
+    if let [pair] = pairs {
+        return (verdict, pair.reason.clone());
+    }
+
+    let labels = pairs
+        .iter()
+        .filter(|pair| pair.verdict == verdict)
+        .map(|pair| pair.label())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let reason = match verdict {
+        Verdict::Enforced => "ROV enforced for all beacon pairs".to_string(),
+        Verdict::NotEnforced => format!("invalid beacon reachable for {}", labels),
+        Verdict::Inconclusive => format!("valid beacon unreachable for {}", labels),
+    };
+    (verdict, reason)
+}
+
+/// Pairs judging ROV along with the excluded ones: the RPKI-unknown beacons are expected reachable
+/// whether ROV is enforced or not, so they are judged only if all of the pairs are.
+fn judged<T: Copy>(pairs: &[T], invalidity: impl Fn(T) -> Invalidity) -> (Vec<T>, Vec<T>) {
+    let (judged, excluded): (Vec<_>, Vec<_>) = pairs
+        .iter()
+        .copied()
+        .partition(|pair| invalidity(*pair) != Invalidity::Unknown);
+    if judged.is_empty() {
+        return (excluded, Vec::new());
+    }
+    (judged, excluded)
+}
+
+/// Verdict across the types of invalid beacons, ROV being partially enforced if only some of the
+/// invalid routes are dropped, the RPKI-unknown beacons only telling about dropping more.
+fn combine_invalidities(
+    verdict: Verdict,
+    reason: String,
+    invalidities: &[InvalidityVerdict],
+) -> (Verdict, String) {
+    let routes = |verdict| {
+        invalidities
+            .iter()
+            .filter(|type_verdict| {
+                type_verdict.invalidity != Invalidity::Unknown && type_verdict.verdict == verdict
+            })
+            .map(|type_verdict| type_verdict.invalidity.routes())
+            .collect::<Vec<_>>()
+    };
+    let (dropped, accepted) = (routes(Verdict::Enforced), routes(Verdict::NotEnforced));
+    let (verdict, mut reason) = if !dropped.is_empty() && !accepted.is_empty() {
+        let reason = format!(
+            "ROV partially enforced, drops {} but not {}",
+            dropped.join(" and "),
+            accepted.join(" or ")
+        );
+        (Verdict::NotEnforced, reason)
+    } else {
+        (verdict, reason)
+    };
+    if invalidities.iter().any(|type_verdict| {
+        type_verdict.invalidity == Invalidity::Unknown && type_verdict.verdict == Verdict::Enforced
+    }) {
+        reason += ", RPKI-unknown beacon unreachable too";
+    }
+    (verdict, reason)
+}
+
+/// Combined verdict of several labeled verdicts (address families, uplinks),
+/// along with the reasons of the ones having it.
+fn combine_labeled(verdicts: &[(String, Verdict, &str)]) -> (Verdict, String) {
+    let verdict = Verdict::combine(verdicts.iter().map(|(_, verdict, _)| *verdict));
+    let reason = verdicts
+        .iter()
+        .filter(|(_, other, _)| *other == verdict)
+        .map(|(label, _, reason)| format!("{}: {}", label, reason))
+        .collect::<Vec<_>>()
+        .join("; ");
+    (verdict, reason)
+}
+
+/// Combined verdict of several address family verdicts, along with its reason.
+fn combine_families(families: &[FamilyVerdict]) -> (Verdict, String) {
+    let verdicts = families
+        .iter()
+        .map(|family| {
+            (
+                family.family.to_string(),
+                family.verdict,
+                family.reason.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    combine_labeled(&verdicts)
+}
+
+/// Resolver of the beacon hostnames for the address family they are checked over.
+fn resolver(
+    config: &Config,
+    family: Option<AddressFamily>,
+    overrides: Vec<(String, Vec<IpAddr>)>,
+) -> Resolver {
+    Resolver::new(family, config.dns_server.clone(), config.timeout).with_overrides(overrides)
+}
+
+fn literal_ip(url: &Url) -> Option<IpAddr> {
+    match url.host()? {
+        Host::Ipv4(ip) => Some(IpAddr::V4(ip)),
+        Host::Ipv6(ip) => Some(IpAddr::V6(ip)),
+        Host::Domain(_) => None,
+    }
+}
+
+/// URL a beacon is requested at, a literal IP address being replaced by the SNI name of the pair.
+fn target(pair: &BeaconPair, url: &Url) -> Url {
+    let mut target = url.clone();
+    if let (Some(sni), Some(_)) = (&pair.sni, literal_ip(url)) {
+        // The name was validated when creating the checker
+        let _ = target.set_host(Some(sni));
+    }
+    target
+}
+
+/// Addresses overriding the resolution of the host of a beacon: the literal IP address
+/// replaced by the SNI name, or the `resolve` overrides of its host and port.
+fn overrides(config: &Config, pair: &BeaconPair, url: &Url) -> Vec<(String, Vec<IpAddr>)> {
+    if let (Some(sni), Some(ip)) = (&pair.sni, literal_ip(url)) {
+        return vec![(sni.clone(), vec![ip])];
+    }
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Vec::new();
+    };
+    config
+        .resolve
+        .iter()
+        .filter(|resolve| resolve.host.eq_ignore_ascii_case(host) && resolve.port == port)
+        .map(|resolve| (resolve.host.clone(), resolve.addrs.clone()))
+        .collect()
+}
+
+/// Address families the beacons are checked over, `None` leaving the choice to the resolver.
+fn families(config: &Config) -> Vec<Option<AddressFamily>> {
+    if config.dual_stack {
+        vec![Some(AddressFamily::Ipv4), Some(AddressFamily::Ipv6)]
+    } else {
+        vec![config.family]
+    }
+}
+
+/// Clients of each uplink and address family.
+fn egresses(config: &Config) -> Result<Vec<Egress>> {
+    let uplinks = match config.uplinks.as_slice() {
+        [] => vec![None],
+        uplinks => uplinks.iter().cloned().map(Some).collect(),
+    };
+    let mut egresses = Vec::new();
+    for uplink in uplinks {
+        for family in families(config) {
+            let interface = uplink.clone().or(config.vrf.clone());
+            let default = build_client(
+                config,
+                interface.as_deref(),
+                family,
+                config.proxy.as_ref(),
+                Vec::new(),
+            )?;
+            let clients = config
+                .pairs
+                .iter()
+                .map(|pair| {
+                    // Beacons with a proxy or address overrides get their own client
+                    let client = |url: &Url| {
+                        let overrides = overrides(config, pair, url);
+                        if pair.proxy.is_none() && overrides.is_empty() {
+                            return Ok(BeaconClient::Http(default.clone()));
+                        }
+                        let proxy = pair.proxy.as_ref().or(config.proxy.as_ref());
+                        build_client(config, interface.as_deref(), family, proxy, overrides)
+                            .map(BeaconClient::Http)
+                    };
+                    Ok((client(&pair.valid_url)?, client(&pair.invalid_url)?))
+                })
+                .collect::<Result<_>>()?;
+            egresses.push(Egress {
+                uplink: uplink.clone(),
+                interface,
+                family,
+                clients,
+            });
+        }
+    }
+
+    Ok(egresses)
+}
+
+impl RovChecker {
+    pub fn new(config: Config) -> Result<Self> {
+        if config.pairs.is_empty() {
+            bail!("no beacon pairs configured");
+        }
+        for name in config.labels.keys() {
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                bail!("invalid label name `{}`", name);
+            }
+            if RESERVED_LABELS.contains(&name.as_str()) {
+                bail!("label name `{}` is reserved", name);
+            }
+        }
+        if config
+            .rate_limit
+            .as_ref()
+            .and_then(|limit| limit.per_minute)
+            == Some(0)
+        {
+            bail!("the rate limit must allow at least one request per minute");
+        }
+        if let Some(quorum) = config.quorum {
+            let pairs = config.pairs.iter().collect::<Vec<_>>();
+            let (judged, excluded) = judged(&pairs, |pair| pair.invalidity);
+            if quorum.total != judged.len() {
+                let excluded = match excluded.len() {
+                    0 => String::new(),
+                    excluded => format!(", excluding {} RPKI-unknown ones", excluded),
+                };
+                bail!(
+                    "quorum {} does not match the {} configured beacon pairs{}",
+                    quorum,
+                    judged.len(),
+                    excluded
+                );
+            }
+        }
+
+        if let Some(source_ip) = config.source_ip {
+            if config.dual_stack {
+                bail!("a source IP cannot be used to check both IPv4 and IPv6");
+            }
+            if let Some(family) = config
+                .family
+                .filter(|family| *family != AddressFamily::of(&source_ip))
+            {
+                bail!("source IP {} is not an {} address", source_ip, family);
+            }
+        }
+
+        if let Some(uplink) = config
+            .uplinks
+            .iter()
+            .enumerate()
+            .find_map(|(index, uplink)| config.uplinks[..index].contains(uplink).then_some(uplink))
+        {
+            bail!("uplink {} listed more than once", uplink);
+        }
+        for pair in &config.pairs {
+            let Some(sni) = &pair.sni else {
+                continue;
+            };
+            if !matches!(Host::parse(sni), Ok(Host::Domain(_))) {
+                bail!("invalid SNI name {} for beacon pair {}", sni, pair.name);
+            }
+            if literal_ip(&pair.valid_url).is_none() && literal_ip(&pair.invalid_url).is_none() {
+                bail!(
+                    "SNI name {} set for beacon pair {} without literal IP addresses",
+                    sni,
+                    pair.name
+                );
+            }
+        }
+        for pair in config
+            .pairs
+            .iter()
+            .filter(|pair| pair.kind == BeaconKind::Dns)
+        {
+            dns::BeaconQuery::parse(&pair.valid_url)?;
+            dns::BeaconQuery::parse(&pair.invalid_url)?;
+            if pair.proxy.is_some() || config.proxy.is_some() {
+                bail!(
+                    "DNS beacon pair {} cannot be checked through a proxy",
+                    pair.name
+                );
+            }
+        }
+        if let Some(pair) = config
+            .pairs
+            .iter()
+            .find(|pair| pair.matcher.is_some() && pair.kind != BeaconKind::Http)
+        {
+            bail!(
+                "the matcher of beacon pair {} only applies to http beacons",
+                pair.name
+            );
+        }
+        if config.vrf.is_some() && !config.uplinks.is_empty() {
+            bail!("the connections cannot be bound to both a VRF and an interface");
+        }
+        if config.http3
+            && (config.proxy.is_some() || config.pairs.iter().any(|pair| pair.proxy.is_some()))
+        {
+            bail!("the HTTP/3 requests cannot go through a proxy");
+        }
+        if config.mode != CheckMode::Http {
+            if config.http3 {
+                bail!(
+                    "the beacons cannot be requested over HTTP/3 in {} mode",
+                    config.mode
+                );
+            }
+            if config.proxy.is_some() || config.pairs.iter().any(|pair| pair.proxy.is_some()) {
+                bail!(
+                    "the beacons cannot be checked through a proxy in {} mode",
+                    config.mode
+                );
+            }
+        }
+
+        let egresses = egresses(&config)?;
+        let tls = tls::client_config(
+            config.tls_min_version,
+            config.cacert.as_deref(),
+            config.insecure,
+        )?;
+        let asn_cache = AsnCache::new(config.asn_info.clone().unwrap_or_default());
+        Ok(RovChecker {
+            config,
+            egresses,
+            tls: Arc::new(tls),
+            asn_cache,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Combined verdict of the pair results of an uplink, along with its reason
+    /// and the verdicts per address family.
+    fn verdict<'a>(&self, pairs: &[&'a PairResult]) -> (Verdict, String, Vec<FamilyVerdict>) {
+        let (pairs, excluded) = judged(pairs, |pair| pair.invalidity);
+        let families = families(&self.config)
+            .into_iter()
+            .flatten()
+            .map(|family| {
+                let of_family = |pairs: &[&'a PairResult]| {
+                    pairs
+                        .iter()
+                        .copied()
+                        .filter(|pair| pair.family == Some(family))
+                        .collect::<Vec<_>>()
+                };
+                let (verdict, reason) = combine(
+                    &of_family(&pairs),
+                    self.config.quorum,
+                    &of_family(&excluded),
+                );
+                FamilyVerdict {
+                    family,
+                    verdict,
+                    reason,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let (verdict, reason) = if families.len() > 1 {
+            combine_families(&families)
+        } else {
+            combine(&pairs, self.config.quorum, &excluded)
+        };
+        (verdict, reason, families)
+    }
+
+    /// Verdict of each type of invalid beacon, if several were checked.
+    fn invalidities(&self, pairs: &[PairResult]) -> Vec<InvalidityVerdict> {
+        let mut invalidities = pairs.iter().map(|pair| pair.invalidity).collect::<Vec<_>>();
+        invalidities.sort();
+        invalidities.dedup();
+        if invalidities.len() < 2 {
+            return Vec::new();
+        }
+        invalidities
+            .into_iter()
+            .map(|invalidity| {
+                let pairs = pairs
+                    .iter()
+                    .filter(|pair| pair.invalidity == invalidity)
+                    .collect::<Vec<_>>();
+                let (verdict, reason) = combine(&pairs, None, &[]);
+                InvalidityVerdict {
+                    invalidity,
+                    verdict,
+                    reason,
+                }
+            })
+            .collect()
+    }
+
+    fn beacon_url(&self, url: &Url, kind: BeaconKind) -> Result<Url> {
+        match kind {
+            BeaconKind::IsBgpSafeYet => {
+                let alphabet = self.config.alphabet.chars().collect::<Vec<char>>();
+                let mut id = String::new();
+                if !alphabet.is_empty() {
+                    id = nanoid!(10, &alphabet);
+                }
+                Ok(url.join(&id)?)
+            }
+            BeaconKind::Http | BeaconKind::Dns => Ok(url.clone()),
+        }
+    }
+
+    /// Whether a beacon is considered reachable given its number of successful trials.
+    fn reachable(&self, successes: u32, trials: u32) -> bool {
+        match self.config.trial_threshold {
+            Some(threshold) => successes as f64 >= threshold * trials as f64,
+            None => successes * 2 > trials,
+        }
+    }
+
+    /// Resolver of the host of a beacon, as used by the clients of an egress.
+    fn resolver(&self, egress: &Egress, pair: &BeaconPair, url: &Url) -> Resolver {
+        let family = egress
+            .family
+            .or(self.config.source_ip.as_ref().map(AddressFamily::of));
+        resolver(&self.config, family, overrides(&self.config, pair, url))
+    }
+
+    /// How the direct connections to the host of a beacon are established from an egress.
+    fn connect(&self, egress: &Egress, pair: &BeaconPair, url: &Url) -> probe::Connect {
+        probe::Connect {
+            resolver: self.resolver(egress, pair, url),
+            timeout: self.config.connect_timeout,
+            source_ip: self.config.source_ip,
+            interface: egress.interface.clone(),
+            tls: self.tls.clone(),
+        }
+    }
+
+    /// HTTP/3 client of a beacon.
+    fn http3_client(&self, egress: &Egress, pair: &BeaconPair, url: &Url) -> Result<BeaconClient> {
+        Ok(BeaconClient::Http3 {
+            connect: Box::new(self.connect(egress, pair, url)),
+            timeout: self.config.timeout,
+            headers: headers(&self.config)?,
+        })
+    }
+
+    /// Client of a beacon not requested over HTTP, in the tcp and icmp check modes or for the DNS
+    /// beacons, if any.
+    fn direct_client(&self, egress: &Egress, pair: &BeaconPair, url: &Url) -> Option<BeaconClient> {
+        let connect = || Box::new(self.connect(egress, pair, url));
+        let timeout = self.config.timeout;
+        match (self.config.mode, pair.kind) {
+            (CheckMode::Tcp, _) => Some(BeaconClient::Tcp {
+                connect: connect(),
+                port: url.port().unwrap_or(443),
+                timeout,
+            }),
+            (CheckMode::Icmp, _) => Some(BeaconClient::Icmp {
+                connect: connect(),
+                timeout,
+            }),
+            (CheckMode::Http, BeaconKind::Dns) => Some(BeaconClient::Dns {
+                connect: connect(),
+                timeout,
+            }),
+            (CheckMode::Http, _) => None,
+        }
+    }
+
+    /// Request a beacon at each of the addresses its hostname resolved to, over new clients.
+    #[instrument(level = "debug", name = "addresses", skip_all)]
+    async fn check_addresses(
+        &self,
+        egress: &Egress,
+        pair: &BeaconPair,
+        target: &Url,
+        host: &str,
+        addrs: &[IpAddr],
+    ) -> Vec<AddressResult> {
+        let checks = addrs.iter().copied().map(|ip| async move {
+            let request = async {
+                let overrides = vec![(host.to_string(), vec![ip])];
+                let interface = egress.interface.as_deref();
+                let client = build_client(&self.config, interface, egress.family, None, overrides)
+                    .map(BeaconClient::Http)?;
+                let url = self.beacon_url(target, pair.kind)?;
+                let limit = self.config.rate_limit.as_ref();
+                check_beacon(&client, pair, url, self.config.retry, limit).await
+            };
+            match request.await {
+                Ok(result) => AddressResult {
+                    ip,
+                    success: result.success,
+                    attempts: result.attempts,
+                    error: result.error.clone(),
+                    duration_ms: result.duration().as_secs_f64() * 1000.0,
+                },
+                Err(e) => AddressResult {
+                    ip,
+                    success: false,
+                    attempts: 0,
+                    error: Some(format!("{:#}", e)),
+                    duration_ms: 0.0,
+                },
+            }
+        });
+        join_all(checks).await
+    }
+
+    #[instrument(level = "debug", name = "beacon", skip_all, fields(url = %url))]
+    async fn check_beacon(
+        &self,
+        egress: &Egress,
+        client: &BeaconClient,
+        pair: &BeaconPair,
+        url: &Url,
+    ) -> Result<BeaconResult> {
+        let trials = self.config.trials.max(1);
+        let target = target(pair, url);
+        let mut results = Vec::new();
+        for _ in 0..trials {
+            let url = self.beacon_url(&target, pair.kind)?;
+            let limit = self.config.rate_limit.as_ref();
+            results.push(check_beacon(client, pair, url, self.config.retry, limit).await?);
+        }
+
+        let successes = results.iter().filter(|result| result.success).count() as u32;
+        let mut result = results
+            .iter()
+            .find(|result| result.success)
+            .unwrap_or(&results[results.len() - 1])
+            .clone();
+        result.success = self.reachable(successes, trials);
+        result.trials = trials;
+        result.successes = successes;
+        result.attempts = results.iter().map(|result| result.attempts).sum();
+        result.started_at = results[0].started_at;
+        result.finished_at = results[results.len() - 1].finished_at;
+
+        // The resolution, timings and addresses are measured over a direct connection,
+        // the timings and addresses being those of the regular requests
+        let direct = pair.proxy.is_none() && self.config.proxy.is_none();
+        let http = matches!(client, BeaconClient::Http(_));
+        // The IPv4 addresses of the URLs of non-special schemes, like `dns://`, are opaque hosts
+        let host = match target.host() {
+            Some(Host::Domain(host)) if host.parse::<IpAddr>().is_err() => Some(host),
+            _ => None,
+        };
+        if let (true, Some(host)) = (direct, host) {
+            let dns = self.resolver(egress, pair, url).resolve(host).await;
+            debug!("{} resolution: {:?}", host, dns);
+            if self.config.all_ips && http {
+                result.addresses = self
+                    .check_addresses(egress, pair, &target, host, &dns.addrs)
+                    .await;
+            }
+            result.dns = Some(dns);
+        }
+        let pins = pair
+            .pins
+            .iter()
+            .chain(&self.config.pins)
+            .collect::<Vec<_>>();
+        let capture = self.config.certificates || !pins.is_empty();
+        if capture && direct && http && url == &pair.valid_url && target.scheme() == "https" {
+            let timeout = pair.timeout.unwrap_or(self.config.timeout);
+            let connect = self.connect(egress, pair, url);
+            self.throttle(&target).await?;
+            match probe::certificates(&target, timeout, &connect).await {
+                Ok(certificates) => {
+                    debug!("{} certificates: {:?}", target, certificates);
+                    result.intercepted = !pins.is_empty()
+                        && !certificates
+                            .iter()
+                            .any(|certificate| pins.contains(&certificate));
+                    result.certificates = certificates;
+                }
+                Err(e) => debug!("Failed to capture the certificates of {}: {:#}", target, e),
+            }
+        }
+        if self.config.timings && direct && http {
+            let timeout = pair.timeout.unwrap_or(self.config.timeout);
+            let connect = self.connect(egress, pair, url);
+            let headers = headers(&self.config)?;
+            self.throttle(&target).await?;
+            let timings = probe::timings(&target, timeout, &connect, &headers).await;
+            debug!("{} timings: {:?}", target, timings);
+            result.timings = Some(timings);
+        }
+        Ok(result)
+    }
+
+    #[instrument(level = "debug", name = "pair", skip_all, fields(beacon = %pair.name, family = ?egress.family, uplink = ?egress.uplink))]
+    async fn check_pair(
+        &self,
+        egress: &Egress,
+        clients: &(BeaconClient, BeaconClient),
+        pair: &BeaconPair,
+    ) -> Result<PairResult> {
+        let (mut valid, mut invalid) = tokio::try_join!(
+            self.check_beacon(egress, &clients.0, pair, &pair.valid_url),
+            self.check_beacon(egress, &clients.1, pair, &pair.invalid_url)
+        )?;
+
+        let (mut verdict, reason) = if valid.intercepted {
+            (
+                Verdict::Inconclusive,
+                "valid beacon certificate not pinned, TLS interception suspected",
+            )
+        } else {
+            Verdict::of_pair(valid.success, invalid.success)
+        };
+        let mut reason = reason.to_string();
+
+        // The beacons reached through different networks do not tell about the same upstream
+        let unexpected = [&valid, &invalid].into_iter().find_map(|beacon| {
+            beacon.asn.filter(|asn| {
+                !self.config.expect_asn.is_empty() && !self.config.expect_asn.contains(asn)
+            })
+        });
+        match (valid.asn, invalid.asn, unexpected) {
+            (_, _, Some(asn)) => {
+                verdict = Verdict::Inconclusive;
+                reason = format!(
+                    "requests seen from AS{} instead of {}, egressing via an unexpected network",
+                    asn,
+                    self.config
+                        .expect_asn
+                        .iter()
+                        .map(|asn| format!("AS{}", asn))
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                );
+            }
+            (Some(valid_asn), Some(invalid_asn), None) if valid_asn != invalid_asn => {
+                verdict = Verdict::Inconclusive;
+                reason = format!(
+                    "valid beacon saw the requests from AS{} and invalid beacon from AS{}",
+                    valid_asn, invalid_asn
+                );
+            }
+            _ => {}
+        }
+
+        // The isbgpsafeyet beacons tell which of them answered, e.g. not a cached response
+        let mut warnings = Vec::new();
+        for (validity, beacon) in [("valid", &valid), ("invalid", &invalid)] {
+            if let Some(status) = beacon
+                .status
+                .as_deref()
+                .filter(|status| *status != validity)
+            {
+                warnings.push(format!("{} beacon reported status `{}`", validity, status));
+            }
+            if beacon.blackholed {
+                warnings.push(format!(
+                    "{} beacon reported the requests as blackholed",
+                    validity
+                ));
+            }
+        }
+        if let (true, Some(warning)) = (
+            self.config.strict && verdict != Verdict::Inconclusive,
+            warnings.first(),
+        ) {
+            verdict = Verdict::Inconclusive;
+            reason = warning.clone();
+        }
+
+        // The paths are traced directly, once over the regular requests
+        let direct = pair.proxy.is_none() && self.config.proxy.is_none();
+        let http3 = matches!(clients.0, BeaconClient::Http3 { .. });
+        if let (Some(mode), true) = (
+            self.config.traceroute,
+            verdict != Verdict::Enforced && direct && !http3,
+        ) {
+            let valid_connect = self.connect(egress, pair, &pair.valid_url);
+            let invalid_connect = self.connect(egress, pair, &pair.invalid_url);
+            let timeout = self.config.timeout;
+            let (valid_path, invalid_path) = tokio::join!(
+                traceroute::trace(&pair.valid_url, mode, timeout, &valid_connect),
+                traceroute::trace(&pair.invalid_url, mode, timeout, &invalid_connect)
+            );
+            valid.traceroute = Some(valid_path);
+            invalid.traceroute = Some(invalid_path);
+        }
+
+        let mut looking_glasses = Vec::new();
+        if !self.config.looking_glasses.is_empty() && !http3 {
+            let valid_connect = self.connect(egress, pair, &pair.valid_url);
+            let invalid_connect = self.connect(egress, pair, &pair.invalid_url);
+            let client = build_client(
+                &self.config,
+                egress.interface.as_deref(),
+                egress.family,
+                self.config.proxy.as_ref(),
+                Vec::new(),
+            )?;
+            let lookups = self.config.looking_glasses.iter().map(|looking_glass| {
+                looking_glass.check(
+                    &client,
+                    &pair.valid_url,
+                    &valid_connect,
+                    &pair.invalid_url,
+                    &invalid_connect,
+                )
+            });
+            looking_glasses = join_all(lookups).await;
+        }
+
+        // An unreachable invalid beacon only means filtering if its prefix is announced
+        let mut routes = None;
+        if let (Some(ris), true) = (&self.config.ris, verdict == Verdict::Enforced && !http3) {
+            let connect = self.connect(egress, pair, &pair.invalid_url);
+            let client = build_client(
+                &self.config,
+                egress.interface.as_deref(),
+                egress.family,
+                self.config.proxy.as_ref(),
+                Vec::new(),
+            )?;
+            let result = ris::check(&client, ris, &pair.invalid_url, &connect).await;
+            if result.error.is_none() && !result.announced() {
+                verdict = Verdict::Inconclusive;
+                reason = "invalid beacon prefix not announced according to RIS".to_string();
+            }
+            routes = Some(result);
+        }
+
+        // Nor if the invalid beacon is up, as seen from a network not enforcing ROV
+        let mut control = None;
+        if let (Some(vantage), true) = (
+            &self.config.control,
+            verdict == Verdict::Enforced && pair.kind != BeaconKind::Dns,
+        ) {
+            let relay = (vantage.kind == ControlKind::Relay).then_some(&vantage.url);
+            let client = build_client(
+                &self.config,
+                egress.interface.as_deref(),
+                egress.family,
+                relay.or(self.config.proxy.as_ref()),
+                Vec::new(),
+            )?;
+            let result = vantage
+                .check(&client, &pair.invalid_url, pair.matcher.as_ref())
+                .await;
+            if result.reachable == Some(false) {
+                verdict = Verdict::Inconclusive;
+                reason = "invalid beacon unreachable from the control vantage too".to_string();
+            }
+            control = Some(result);
+        }
+
+        Ok(PairResult {
+            name: pair.name.clone(),
+            family: egress.family,
+            uplink: egress.uplink.clone(),
+            http3,
+            invalidity: pair.invalidity,
+            verdict,
+            reason,
+            warnings,
+            valid,
+            invalid,
+            ris: routes,
+            control,
+            looking_glasses,
+        })
+    }
+
+    /// Hosts of the beacons, as limited by the rate limit.
+    fn hosts(&self) -> Vec<String> {
+        let mut hosts = self
+            .config
+            .pairs
+            .iter()
+            .flat_map(|pair| [&pair.valid_url, &pair.invalid_url])
+            .filter_map(|url| url.host_str().map(String::from))
+            .collect::<Vec<_>>();
+        hosts.sort();
+        hosts.dedup();
+        hosts
+    }
+
+    /// Wait until the rate limit allows a request to the beacon host, if any.
+    async fn throttle(&self, url: &Url) -> Result<()> {
+        match (&self.config.rate_limit, url.host_str()) {
+            (Some(limit), Some(host)) => limit.acquire(host).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Labels of the results: the configured ones, along with the hostname unless overridden.
+    fn labels(&self) -> BTreeMap<String, String> {
+        let mut labels = self.config.labels.clone();
+        if let Ok(hostname) = hostname::get() {
+            labels
+                .entry("hostname".to_string())
+                .or_insert_with(|| hostname.to_string_lossy().into_owned());
+        }
+        labels
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn check(&self) -> Result<RovResult> {
+        if let Some(limit) = &self.config.rate_limit {
+            limit.start(&self.hosts()).await?;
+        }
+        match &self.config.netns {
+            Some(name) => {
+                let checker = self.clone();
+                let check = async move { checker.check_pairs().await }.in_current_span();
+                netns::run(name, check).await
+            }
+            None => self.check_pairs().await,
+        }
+    }
+
+    /// Per-hop statistics of the paths to the valid and invalid beacons of each pair,
+    /// from each uplink and address family, over `rounds` traceroutes.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn paths(&self, mode: TracerouteMode, rounds: u32) -> Result<Vec<PairPaths>> {
+        match &self.config.netns {
+            Some(name) => {
+                let checker = self.clone();
+                let paths = async move { checker.analyze_paths(mode, rounds).await };
+                netns::run(name, paths.in_current_span()).await
+            }
+            None => self.analyze_paths(mode, rounds).await,
+        }
+    }
+
+    async fn analyze_paths(&self, mode: TracerouteMode, rounds: u32) -> Result<Vec<PairPaths>> {
+        if self.config.proxy.is_some() || self.config.pairs.iter().any(|pair| pair.proxy.is_some())
+        {
+            bail!("the paths to the beacons cannot be analyzed through a proxy");
+        }
+        let timeout = self.config.timeout;
+        let analyses = self.egresses.iter().flat_map(|egress| {
+            self.config.pairs.iter().map(move |pair| async move {
+                let valid_connect = self.connect(egress, pair, &pair.valid_url);
+                let invalid_connect = self.connect(egress, pair, &pair.invalid_url);
+                let (valid, invalid) = tokio::join!(
+                    traceroute::analyze(&pair.valid_url, mode, rounds, timeout, &valid_connect),
+                    traceroute::analyze(&pair.invalid_url, mode, rounds, timeout, &invalid_connect)
+                );
+                PairPaths {
+                    name: pair.name.clone(),
+                    family: egress.family,
+                    uplink: egress.uplink.clone(),
+                    valid,
+                    invalid,
+                }
+            })
+        });
+        Ok(join_all(analyses).await)
+    }
+
+    /// Verify the RPKI state of the routes to the beacons of the checked pairs, as their ROAs
+    /// may have changed since the beacons were set up.
+    async fn verify_rpki(
+        &self,
+        rpki: &Rpki,
+        egresses: &[Egress],
+        clients: &[(&Egress, &BeaconPair, (BeaconClient, BeaconClient))],
+        pairs: &mut [PairResult],
+    ) -> Result<()> {
+        let egress = &egresses[0];
+        let client = build_client(
+            &self.config,
+            egress.interface.as_deref(),
+            egress.family,
+            self.config.proxy.as_ref(),
+            Vec::new(),
+        )?;
+        let validator = Validator::load(&client, rpki.source, rpki.url.as_ref())
+            .await
+            .map_err(|e| e.to_string());
+        let ris = self.config.ris.clone().unwrap_or_default();
+        let verifications = clients.iter().map(|(egress, pair, _)| {
+            let valid_connect = self.connect(egress, pair, &pair.valid_url);
+            let invalid_connect = self.connect(egress, pair, &pair.invalid_url);
+            let (client, validator, ris) = (&client, &validator, &ris);
+            async move {
+                tokio::join!(
+                    rpki::verify(client, validator, ris, &pair.valid_url, &valid_connect),
+                    rpki::verify(client, validator, ris, &pair.invalid_url, &invalid_connect)
+                )
+            }
+        });
+        let verifications = join_all(verifications).await;
+
+        for (pair, (valid, invalid)) in pairs.iter_mut().zip(verifications) {
+            let expected = match pair.invalidity {
+                Invalidity::Unknown => Validity::NotFound,
+                _ => Validity::Invalid,
+            };
+            let unexpected = [
+                ("valid", valid.unexpected(Validity::Valid)),
+                ("invalid", invalid.unexpected(expected)),
+            ];
+            if let Some((validity, Some(validation))) = unexpected
+                .iter()
+                .find(|(_, validation)| validation.is_some())
+            {
+                pair.verdict = Verdict::Inconclusive;
+                pair.reason = format!(
+                    "{} beacon route {} AS{} is RPKI {}, its ROAs changed",
+                    validity, validation.prefix, validation.asn, validation.validity
+                );
+            }
+            pair.valid.rpki = Some(valid);
+            pair.invalid.rpki = Some(invalid);
+        }
+        Ok(())
+    }
+
+    /// Look up the prefixes covering the beacons, their routes being validated against the RPKI
+    /// data of the verification if any, or of RIPEstat.
+    async fn lookup_prefixes(
+        &self,
+        lookup: &PrefixLookup,
+        egresses: &[Egress],
+        clients: &[(&Egress, &BeaconPair, (BeaconClient, BeaconClient))],
+        pairs: &mut [PairResult],
+    ) -> Result<()> {
+        let egress = &egresses[0];
+        let client = build_client(
+            &self.config,
+            egress.interface.as_deref(),
+            egress.family,
+            self.config.proxy.as_ref(),
+            Vec::new(),
+        )?;
+        let rpki = self.config.rpki.clone().unwrap_or_default();
+        let validator = Validator::load(&client, rpki.source, rpki.url.as_ref())
+            .await
+            .inspect_err(|e| debug!("Failed to load the RPKI data: {}", e))
+            .ok();
+        let lookups = clients.iter().map(|(egress, pair, _)| {
+            let valid_connect = self.connect(egress, pair, &pair.valid_url);
+            let invalid_connect = self.connect(egress, pair, &pair.invalid_url);
+            let (client, validator) = (&client, validator.as_ref());
+            async move {
+                tokio::join!(
+                    lookup.check(client, validator, &pair.valid_url, &valid_connect),
+                    lookup.check(client, validator, &pair.invalid_url, &invalid_connect)
+                )
+            }
+        });
+        let lookups = join_all(lookups).await;
+
+        for (pair, (valid, invalid)) in pairs.iter_mut().zip(lookups) {
+            pair.valid.prefix_info = Some(valid);
+            pair.invalid.prefix_info = Some(invalid);
+        }
+        Ok(())
+    }
+
+    async fn check_pairs(&self) -> Result<RovResult> {
+        let started_at = Utc::now();
+
+        // New clients for each check, built once and shared by the trials and retries of its
+        // requests, their connections not outliving it
+        let fresh;
+        let egresses = match self.config.connection_reuse {
+            ConnectionReuse::Check => {
+                fresh = egresses(&self.config)?;
+                &fresh
+            }
+            ConnectionReuse::Never | ConnectionReuse::Always => &self.egresses,
+        };
+        let clients = egresses
+            .iter()
+            .flat_map(|egress| {
+                self.config
+                    .pairs
+                    .iter()
+                    .zip(&egress.clients)
+                    .map(move |(pair, clients)| {
+                        let clients = match (
+                            self.direct_client(egress, pair, &pair.valid_url),
+                            self.direct_client(egress, pair, &pair.invalid_url),
+                        ) {
+                            (Some(valid), Some(invalid)) => (valid, invalid),
+                            _ => clients.clone(),
+                        };
+                        (egress, pair, clients)
+                    })
+            })
+            .collect::<Vec<_>>();
+        let checks = clients
+            .iter()
+            .map(|(egress, pair, clients)| self.check_pair(egress, clients, pair));
+        let http3_clients = egresses
+            .iter()
+            .filter(|_| self.config.http3)
+            .flat_map(|egress| {
+                // The DNS beacons are not requested over HTTP
+                let pairs = self.config.pairs.iter();
+                let pairs = pairs.filter(|pair| pair.kind != BeaconKind::Dns);
+                pairs.map(move |pair| {
+                    let clients = (
+                        self.http3_client(egress, pair, &pair.valid_url)?,
+                        self.http3_client(egress, pair, &pair.invalid_url)?,
+                    );
+                    Ok((egress, pair, clients))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let http3_checks = http3_clients
+            .iter()
+            .map(|(egress, pair, clients)| self.check_pair(egress, clients, pair));
+        let preflight = async {
+            let Some(preflight) = &self.config.preflight else {
+                return Ok(None);
+            };
+            let egress = &egresses[0];
+            let client = build_client(
+                &self.config,
+                egress.interface.as_deref(),
+                egress.family,
+                self.config.proxy.as_ref(),
+                Vec::new(),
+            )?;
+            Ok::<_, anyhow::Error>(Some(preflight::check(&client, preflight).await))
+        };
+        let (preflight, mut pairs, http3_pairs) =
+            tokio::try_join!(preflight, try_join_all(checks), try_join_all(http3_checks))?;
+        if let Some(rpki) = &self.config.rpki {
+            self.verify_rpki(rpki, egresses, &clients, &mut pairs)
+                .await?;
+        }
+        if let Some(lookup) = &self.config.prefix_info {
+            self.lookup_prefixes(lookup, egresses, &clients, &mut pairs)
+                .await?;
+        }
+        let http3 = self.config.http3.then(|| {
+            let (verdict, reason, families) = self.verdict(&http3_pairs.iter().collect::<Vec<_>>());
+            Http3Verdict {
+                verdict,
+                reason,
+                families,
+            }
+        });
+
+        let (verdict, reason, families, uplinks) = if self.config.uplinks.len() > 1 {
+            let uplinks = self
+                .config
+                .uplinks
+                .iter()
+                .map(|uplink| {
+                    let pairs = pairs
+                        .iter()
+                        .filter(|pair| pair.uplink.as_ref() == Some(uplink))
+                        .collect::<Vec<_>>();
+                    let (verdict, reason, families) = self.verdict(&pairs);
+                    UplinkVerdict {
+                        uplink: uplink.clone(),
+                        verdict,
+                        reason,
+                        families,
+                    }
+                })
+                .collect::<Vec<_>>();
+            let verdicts = uplinks
+                .iter()
+                .map(|uplink| {
+                    (
+                        uplink.uplink.clone(),
+                        uplink.verdict,
+                        uplink.reason.as_str(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let (verdict, reason) = combine_labeled(&verdicts);
+            // Verdict of each address family across the uplinks
+            let families = families(&self.config)
+                .into_iter()
+                .flatten()
+                .map(|family| {
+                    let verdicts = uplinks
+                        .iter()
+                        .flat_map(|uplink| {
+                            uplink
+                                .families
+                                .iter()
+                                .filter(|verdict| verdict.family == family)
+                                .map(|verdict| {
+                                    (
+                                        uplink.uplink.clone(),
+                                        verdict.verdict,
+                                        verdict.reason.as_str(),
+                                    )
+                                })
+                        })
+                        .collect::<Vec<_>>();
+                    let (verdict, reason) = combine_labeled(&verdicts);
+                    FamilyVerdict {
+                        family,
+                        verdict,
+                        reason,
+                    }
+                })
+                .collect::<Vec<_>>();
+            (verdict, reason, families, uplinks)
+        } else {
+            let (verdict, reason, families) = self.verdict(&pairs.iter().collect::<Vec<_>>());
+            (verdict, reason, families, Vec::new())
+        };
+
+        let invalidities = self.invalidities(&pairs);
+        let (verdict, reason) = if invalidities.is_empty() {
+            (verdict, reason)
+        } else {
+            combine_invalidities(verdict, reason, &invalidities)
+        };
+
+        // A captive portal or a filtering proxy may fake the beacon responses
+        let (verdict, reason) = match &preflight {
+            Some(preflight) if !preflight.success => (
+                Verdict::Inconclusive,
+                format!(
+                    "preflight to {} failed: {}",
+                    preflight.url,
+                    preflight.error.as_deref().unwrap_or_default()
+                ),
+            ),
+            _ => (verdict, reason),
+        };
+
+        let mut result = RovResult {
+            verdict,
+            reason,
+            families,
+            uplinks,
+            invalidities,
+            vrf: self.config.vrf.clone(),
+            http3,
+            preflight,
+            asn_info: None,
+            radar: None,
+            confidence: None,
+            pairs: pairs.into_iter().chain(http3_pairs).collect(),
+            started_at,
+            finished_at: Utc::now(),
+            labels: self.labels(),
+            signature: None,
+        };
+        result.confidence = confidence::confidence(&result);
+        let lookups = self.config.asn_info.is_some() || self.config.radar.is_some();
+        if let (true, Some(asn)) = (lookups, result.asn()) {
+            let egress = &egresses[0];
+            let client = build_client(
+                &self.config,
+                egress.interface.as_deref(),
+                egress.family,
+                self.config.proxy.as_ref(),
+                Vec::new(),
+            )?;
+            let asn_info = async {
+                match &self.config.asn_info {
+                    Some(_) => Some(self.asn_cache.lookup(&client, asn).await),
+                    None => None,
+                }
+            };
+            let verdict = result.verdict;
+            let radar = async {
+                match &self.config.radar {
+                    Some(radar) => Some(radar.check(&client, asn, verdict).await),
+                    None => None,
+                }
+            };
+            (result.asn_info, result.radar) = tokio::join!(asn_info, radar);
+            result.finished_at = Utc::now();
+        }
+        if let Some(key) = &self.config.signing_key {
+            signing::sign(key, &mut result)?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Result of a check of the `cloudflare` pair over IPv4, its valid beacon reached in 120ms
+    /// and its invalid beacon timing out after 3s.
+    pub(crate) fn result() -> RovResult {
+        let beacon = |url: &str, success: bool, finished_at: &str| {
+            serde_json::json!({
+                "url": url,
+                "success": success,
+                "attempts": 1,
+                "trials": 1,
+                "successes": success as u32,
+                "asn": success.then_some(64496),
+                "name": null,
+                "error": (!success).then_some("operation timed out"),
+                "timings": null,
+                "started_at": "2026-01-01T00:00:00Z",
+                "finished_at": finished_at,
+            })
+        };
+        serde_json::from_value(serde_json::json!({
+            "verdict": "ENFORCED",
+            "reason": "valid beacon reachable, invalid beacon unreachable",
+            "families": [],
+            "pairs": [{
+                "name": "cloudflare",
+                "family": "ipv4",
+                "verdict": "ENFORCED",
+                "reason": "valid beacon reachable, invalid beacon unreachable",
+                "valid": beacon("https://valid.rpki.isbgpsafeyet.com/", true, "2026-01-01T00:00:00.120Z"),
+                "invalid": beacon("https://invalid.rpki.isbgpsafeyet.com/", false, "2026-01-01T00:00:03Z"),
+            }],
+            "started_at": "2026-01-01T00:00:00Z",
+            "finished_at": "2026-01-01T00:00:03Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn judged_pairs() {
+        use Invalidity::*;
+        let (pairs, excluded) = judged(&[Origin, Unknown, MaxLength], |invalidity| invalidity);
+        assert_eq!(pairs, [Origin, MaxLength]);
+        assert_eq!(excluded, [Unknown]);
+        // Only RPKI-unknown beacons are judged rather than none
+        let (pairs, excluded) = judged(&[Unknown, Unknown], |invalidity| invalidity);
+        assert_eq!(pairs, [Unknown, Unknown]);
+        assert!(excluded.is_empty());
+    }
+
+    /// Number of connections accepted by a keep-alive beacon over two checks of 3 trials.
+    async fn connections(reuse: ConnectionReuse) -> usize {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buffer = [0; 4096];
+                    while let Ok(n @ 1..) = stream.read(&mut buffer).await {
+                        if buffer[..n].windows(4).any(|w| w == b"\r\n\r\n") {
+                            let response = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+                            if stream.write_all(response.as_bytes()).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut pair = preset::get("cloudflare").unwrap();
+        pair.kind = BeaconKind::Http;
+        pair.valid_url = Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+        pair.invalid_url = Url::parse("http://127.0.0.1:1/").unwrap();
+        let checker = RovChecker::new(Config {
+            pairs: vec![pair],
+            trials: 3,
+            connection_reuse: reuse,
+            ..Default::default()
+        })
+        .unwrap();
+        for _ in 0..2 {
+            let result = checker.check().await.unwrap();
+            assert_eq!(result.verdict, Verdict::Enforced);
+        }
+        accepted.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn connection_reuse() {
+        assert_eq!(connections(ConnectionReuse::Never).await, 6);
+        // One connection for the trials of each check
+        assert_eq!(connections(ConnectionReuse::Check).await, 2);
+        assert_eq!(connections(ConnectionReuse::Always).await, 1);
+    }
+}
@@ -0,0 +1,718 @@
+use anyhow::{bail, Context, Result};
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic::{Request, Status, Streaming};
+use tracing::{debug, info, warn};
+
+use crate::checker::RovResult;
+use crate::daemon::Shutdown;
+use crate::grpc::{self, CheckReport, CollectorServer, Probe, ReportAck};
+use crate::signing;
+use crate::verdict::Verdict;
+
+/// Collector of the results of the agents, over gRPC and HTTP.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Collector {
+    /// Address of the gRPC service the agents stream their results to
+    #[serde(default = "default_grpc_address")]
+    pub grpc_address: SocketAddr,
+    /// Address of the HTTP API, also receiving results
+    #[serde(default = "default_address")]
+    pub address: SocketAddr,
+    /// SQLite database the results are stored in
+    #[serde(default = "default_database")]
+    pub database: PathBuf,
+    /// Tokens the agents authenticate with, any agent being accepted if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<String>,
+    /// PEM files of the certificate and key of the gRPC service, serving it over TLS
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// PEM file of the CA certificates the agents are required client certificates from
+    pub client_ca: Option<PathBuf>,
+    /// Number of consecutive results for a change of the verdict of a probe to be accepted
+    #[serde(default = "default_flap_after")]
+    pub flap_after: u32,
+    /// Public keys the results must be signed with, base64-encoded or PEM files, unsigned
+    /// results being accepted if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub public_keys: Vec<String>,
+}
+
+fn default_grpc_address() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 50051))
+}
+
+fn default_address() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 8080))
+}
+
+fn default_database() -> PathBuf {
+    PathBuf::from("rovcheck-collector.db")
+}
+
+fn default_flap_after() -> u32 {
+    2
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Collector {
+            grpc_address: default_grpc_address(),
+            address: default_address(),
+            database: default_database(),
+            tokens: Vec::new(),
+            tls_cert: None,
+            tls_key: None,
+            client_ca: None,
+            flap_after: default_flap_after(),
+            public_keys: Vec::new(),
+        }
+    }
+}
+
+/// Latest state of a probe.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProbeStatus {
+    pub id: String,
+    pub hostname: String,
+    pub version: String,
+    pub asn: Option<u32>,
+    /// Verdict of the probe, only changed once seen in enough consecutive results
+    pub verdict: Verdict,
+    /// Verdict of the last result of the probe
+    pub last_verdict: Verdict,
+    pub reports: u64,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// ROV status of an ASN, across the probes in it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AsnStatus {
+    pub asn: u32,
+    /// Combined verdict of the probes
+    pub verdict: Verdict,
+    /// Number of probes per verdict
+    pub verdicts: BTreeMap<String, usize>,
+    pub probes: Vec<String>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Granularity of the status of an ASN over time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bucket {
+    #[default]
+    Hour,
+    Day,
+}
+
+impl FromStr for Bucket {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(Bucket::Hour),
+            "day" => Ok(Bucket::Day),
+            _ => bail!("unknown bucket `{}`", s),
+        }
+    }
+}
+
+impl Bucket {
+    fn seconds(&self) -> i64 {
+        match self {
+            Bucket::Hour => 3600,
+            Bucket::Day => 86400,
+        }
+    }
+}
+
+/// ROV status of an ASN over a bucket of time, from the last verdict of each probe in it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AsnPoint {
+    pub start: DateTime<Utc>,
+    pub verdict: Verdict,
+    pub verdicts: BTreeMap<String, usize>,
+}
+
+/// SQLite store of the results of the agents, along with the state of each probe.
+pub struct Store {
+    conn: Connection,
+    flap_after: u32,
+}
+
+fn timestamp(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap_or_default()
+}
+
+fn verdict(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Verdict> {
+    row.get::<_, String>(index)?
+        .parse()
+        .map_err(|e: anyhow::Error| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+        })
+}
+
+/// Counts of the verdicts, and their combination.
+fn tally(verdicts: impl IntoIterator<Item = Verdict>) -> (Verdict, BTreeMap<String, usize>) {
+    let verdicts = verdicts.into_iter().collect::<Vec<_>>();
+    let mut counts = BTreeMap::new();
+    for verdict in &verdicts {
+        *counts.entry(verdict.to_string()).or_default() += 1;
+    }
+    (Verdict::combine(verdicts), counts)
+}
+
+impl Store {
+    pub fn open(path: &Path, flap_after: u32) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open collector database {}", path.display()))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS reports (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                probe TEXT NOT NULL,
+                received_at INTEGER NOT NULL,
+                finished_at INTEGER NOT NULL,
+                verdict TEXT NOT NULL,
+                probe_verdict TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                asn INTEGER,
+                confidence REAL,
+                result TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS reports_asn ON reports (asn, finished_at);
+            CREATE TABLE IF NOT EXISTS probes (
+                id TEXT PRIMARY KEY,
+                hostname TEXT NOT NULL,
+                version TEXT NOT NULL,
+                asn INTEGER,
+                verdict TEXT NOT NULL,
+                last_verdict TEXT NOT NULL,
+                pending INTEGER NOT NULL,
+                reports INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL
+            );",
+        )?;
+        Ok(Store {
+            conn,
+            flap_after: flap_after.max(1),
+        })
+    }
+
+    /// Store a report, returning the verdict of its probe once its flaps are damped.
+    pub fn insert(&self, report: &CheckReport) -> Result<Verdict> {
+        let probe = report.probe.clone().unwrap_or_default();
+        if probe.id.is_empty() {
+            bail!("report without probe identity");
+        }
+        let received: Verdict = report.verdict.parse()?;
+        let state = self
+            .conn
+            .query_row(
+                "SELECT verdict, last_verdict, pending FROM probes WHERE id = ?1",
+                params![probe.id],
+                |row| Ok((verdict(row, 0)?, verdict(row, 1)?, row.get::<_, u32>(2)?)),
+            )
+            .optional()?;
+        // As the watcher of the daemons, a change is only accepted once seen in enough
+        // consecutive results
+        let (verdict, pending) = match state {
+            None => (received, 0),
+            Some((verdict, _, _)) if verdict == received => (verdict, 0),
+            Some((verdict, last, pending)) => {
+                let count = if last == received { pending + 1 } else { 1 };
+                if count >= self.flap_after {
+                    (received, 0)
+                } else {
+                    debug!(
+                        probe = probe.id,
+                        "Verdict {} seen in {}/{} consecutive results",
+                        received,
+                        count,
+                        self.flap_after
+                    );
+                    (verdict, count)
+                }
+            }
+        };
+
+        let now = Utc::now().timestamp();
+        self.conn.execute(
+            "INSERT INTO reports
+             (probe, received_at, finished_at, verdict, probe_verdict, reason, asn, confidence, result)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                probe.id,
+                now,
+                report.timestamp,
+                received.to_string(),
+                verdict.to_string(),
+                report.reason,
+                report.asn,
+                report.confidence,
+                report.result,
+            ],
+        )?;
+        self.conn.execute(
+            "INSERT INTO probes
+             (id, hostname, version, asn, verdict, last_verdict, pending, reports, last_seen)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1, ?8)
+             ON CONFLICT (id) DO UPDATE SET
+                hostname = excluded.hostname, version = excluded.version, asn = excluded.asn,
+                verdict = excluded.verdict, last_verdict = excluded.last_verdict,
+                pending = excluded.pending, reports = reports + 1, last_seen = excluded.last_seen",
+            params![
+                probe.id,
+                probe.hostname,
+                probe.version,
+                report.asn,
+                verdict.to_string(),
+                received.to_string(),
+                pending,
+                report.timestamp,
+            ],
+        )?;
+        Ok(verdict)
+    }
+
+    pub fn probes(&self) -> Result<Vec<ProbeStatus>> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, hostname, version, asn, verdict, last_verdict, reports, last_seen
+             FROM probes ORDER BY id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(ProbeStatus {
+                id: row.get(0)?,
+                hostname: row.get(1)?,
+                version: row.get(2)?,
+                asn: row.get(3)?,
+                verdict: verdict(row, 4)?,
+                last_verdict: verdict(row, 5)?,
+                reports: row.get(6)?,
+                last_seen: timestamp(row.get(7)?),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// ROV status of each ASN, from the current verdict of its probes.
+    pub fn asns(&self) -> Result<Vec<AsnStatus>> {
+        let mut asns = BTreeMap::<u32, Vec<ProbeStatus>>::new();
+        for probe in self.probes()? {
+            if let Some(asn) = probe.asn {
+                asns.entry(asn).or_default().push(probe);
+            }
+        }
+        Ok(asns
+            .into_iter()
+            .map(|(asn, probes)| {
+                let (verdict, verdicts) = tally(probes.iter().map(|probe| probe.verdict));
+                AsnStatus {
+                    asn,
+                    verdict,
+                    verdicts,
+                    last_seen: probes
+                        .iter()
+                        .map(|probe| probe.last_seen)
+                        .max()
+                        .unwrap_or_default(),
+                    probes: probes.into_iter().map(|probe| probe.id).collect(),
+                }
+            })
+            .collect())
+    }
+
+    /// ROV status of an ASN over time, oldest first.
+    pub fn asn_history(
+        &self,
+        asn: u32,
+        since: Option<DateTime<Utc>>,
+        bucket: Bucket,
+    ) -> Result<Vec<AsnPoint>> {
+        let mut statement = self.conn.prepare(
+            "SELECT probe, finished_at, probe_verdict FROM reports
+             WHERE asn = ?1 AND (?2 IS NULL OR finished_at >= ?2)
+             ORDER BY finished_at",
+        )?;
+        let rows =
+            statement.query_map(params![asn, since.map(|since| since.timestamp())], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    verdict(row, 2)?,
+                ))
+            })?;
+        // Last verdict of each probe in each bucket
+        let mut buckets = BTreeMap::<i64, BTreeMap<String, Verdict>>::new();
+        for row in rows {
+            let (probe, finished_at, verdict) = row?;
+            let start = finished_at - finished_at.rem_euclid(bucket.seconds());
+            buckets.entry(start).or_default().insert(probe, verdict);
+        }
+        Ok(buckets
+            .into_iter()
+            .map(|(start, probes)| {
+                let (verdict, verdicts) = tally(probes.into_values());
+                AsnPoint {
+                    start: timestamp(start),
+                    verdict,
+                    verdicts,
+                }
+            })
+            .collect())
+    }
+}
+
+type SharedStore = Arc<Mutex<Store>>;
+
+/// Whether the bearer token of a request is accepted, any being accepted without tokens.
+fn authorized(tokens: &[String], authorization: Option<&str>) -> bool {
+    tokens.is_empty()
+        || authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| tokens.iter().any(|accepted| accepted == token))
+}
+
+/// Check the signature of the result of a report, if keys are trusted.
+fn authenticate(trusted: &[VerifyingKey], report: &CheckReport) -> Result<()> {
+    if !trusted.is_empty() {
+        let result = serde_json::from_str(&report.result).context("invalid result")?;
+        signing::verify(&result, trusted)?;
+    }
+    Ok(())
+}
+
+fn store(store: &SharedStore, report: &CheckReport) -> Result<()> {
+    let verdict = store.lock().unwrap().insert(report)?;
+    let probe = report.probe.as_ref().map(|probe| probe.id.as_str());
+    info!(
+        probe,
+        asn = report.asn,
+        "{}: {} ({})",
+        probe.unwrap_or_default(),
+        report.verdict,
+        verdict
+    );
+    Ok(())
+}
+
+struct Service {
+    store: SharedStore,
+    tokens: Arc<Vec<String>>,
+    trusted: Arc<Vec<VerifyingKey>>,
+}
+
+#[tonic::async_trait]
+impl grpc::Collector for Service {
+    async fn report(
+        &self,
+        request: Request<Streaming<CheckReport>>,
+    ) -> Result<tonic::Response<ReportAck>, Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        if !authorized(&self.tokens, authorization) {
+            return Err(Status::unauthenticated("invalid token"));
+        }
+        let mut reports = request.into_inner();
+        let mut received = 0;
+        while let Some(report) = reports.message().await? {
+            authenticate(&self.trusted, &report)
+                .map_err(|e| Status::unauthenticated(format!("{:#}", e)))?;
+            store(&self.store, &report).map_err(|e| Status::invalid_argument(e.to_string()))?;
+            received += 1;
+        }
+        Ok(tonic::Response::new(ReportAck { received }))
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    store: SharedStore,
+    tokens: Arc<Vec<String>>,
+    trusted: Arc<Vec<VerifyingKey>>,
+}
+
+/// Result posted to the HTTP API.
+#[derive(Deserialize)]
+struct PostedReport {
+    probe: Probe,
+    result: RovResult,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    bucket: Bucket,
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))
+    }
+}
+
+async fn post_report(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(posted): Json<PostedReport>,
+) -> Result<StatusCode, ApiError> {
+    let authorization = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok());
+    if !authorized(&state.tokens, authorization) {
+        return Err(ApiError(
+            StatusCode::UNAUTHORIZED,
+            "invalid token".to_string(),
+        ));
+    }
+    let report = CheckReport::new(posted.probe, &posted.result)?;
+    authenticate(&state.trusted, &report)
+        .map_err(|e| ApiError(StatusCode::UNAUTHORIZED, format!("{:#}", e)))?;
+    store(&state.store, &report).map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn probes(State(state): State<ApiState>) -> Result<Json<Vec<ProbeStatus>>, ApiError> {
+    Ok(Json(state.store.lock().unwrap().probes()?))
+}
+
+async fn asns(State(state): State<ApiState>) -> Result<Json<Vec<AsnStatus>>, ApiError> {
+    Ok(Json(state.store.lock().unwrap().asns()?))
+}
+
+async fn asn_history(
+    State(state): State<ApiState>,
+    UrlPath(asn): UrlPath<u32>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<AsnPoint>>, ApiError> {
+    let store = state.store.lock().unwrap();
+    Ok(Json(store.asn_history(asn, query.since, query.bucket)?))
+}
+
+impl Collector {
+    fn tls(&self) -> Result<Option<ServerTlsConfig>> {
+        let (cert, key) = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) if self.client_ca.is_some() => {
+                bail!("client certificates require the certificate and key of the collector")
+            }
+            (None, None) => return Ok(None),
+            _ => bail!("the certificate and key of the collector are required together"),
+        };
+        let read = |path: &PathBuf| {
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+        };
+        let mut config =
+            ServerTlsConfig::new().identity(Identity::from_pem(read(cert)?, read(key)?));
+        if let Some(ca) = &self.client_ca {
+            config = config.client_ca_root(Certificate::from_pem(read(ca)?));
+        }
+        Ok(Some(config))
+    }
+
+    /// Receive the results of the agents over gRPC and HTTP until SIGTERM or SIGINT, and serve
+    /// their aggregates on the HTTP API.
+    pub async fn run(self) -> Result<()> {
+        let store = Arc::new(Mutex::new(Store::open(&self.database, self.flap_after)?));
+        let tokens = Arc::new(self.tokens.clone());
+        if tokens.is_empty() {
+            warn!("No tokens configured, accepting results from any agent");
+        }
+        let trusted = self
+            .public_keys
+            .iter()
+            .map(|key| signing::parse_public_key(key))
+            .collect::<Result<Vec<_>>>()?;
+        let trusted = Arc::new(trusted);
+
+        let mut server = Server::builder();
+        if let Some(tls) = self.tls()? {
+            server = server.tls_config(tls)?;
+        }
+        let service = CollectorServer::new(Service {
+            store: store.clone(),
+            tokens: tokens.clone(),
+            trusted: trusted.clone(),
+        });
+        let mut shutdown = Shutdown::new()?;
+        let (stop, stopped) = tokio::sync::watch::channel(false);
+        let grpc = server
+            .add_service(service)
+            .serve_with_shutdown(self.grpc_address, {
+                let mut stopped = stopped.clone();
+                async move {
+                    let _ = stopped.changed().await;
+                }
+            });
+        info!("Receiving results over gRPC on {}", self.grpc_address);
+
+        let app = Router::new()
+            .route("/api/v1/reports", post(post_report))
+            .route("/api/v1/probes", get(probes))
+            .route("/api/v1/asns", get(asns))
+            .route("/api/v1/asns/{asn}", get(asn_history))
+            .with_state(ApiState {
+                store,
+                tokens,
+                trusted,
+            });
+        let listener = tokio::net::TcpListener::bind(self.address).await?;
+        info!("Listening on {}", self.address);
+        let api = axum::serve(listener, app).with_graceful_shutdown({
+            let mut stopped = stopped.clone();
+            async move {
+                let _ = stopped.changed().await;
+            }
+        });
+
+        let signal = async move {
+            shutdown.recv().await;
+            info!("Shutting down");
+            let _ = stop.send(true);
+            Ok::<_, anyhow::Error>(())
+        };
+        tokio::try_join!(
+            async { Ok::<_, anyhow::Error>(grpc.await?) },
+            async { Ok(api.await?) },
+            signal
+        )?;
+        info!("Stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(probe: &str, asn: u32, verdict: Verdict, timestamp: i64) -> CheckReport {
+        CheckReport {
+            probe: Some(Probe {
+                id: probe.to_string(),
+                version: "0.1.0".to_string(),
+                hostname: format!("{}.example", probe),
+            }),
+            verdict: verdict.to_string(),
+            reason: String::new(),
+            asn: Some(asn),
+            confidence: None,
+            timestamp,
+            result: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn damped_flaps() {
+        use Verdict::*;
+        let store = Store::open(Path::new(":memory:"), 3).unwrap();
+        let insert = |verdict, timestamp| store.insert(&report("a", 64496, verdict, timestamp));
+        assert_eq!(insert(Enforced, 0).unwrap(), Enforced);
+        // A change is only accepted once seen in 3 consecutive results
+        assert_eq!(insert(NotEnforced, 60).unwrap(), Enforced);
+        assert_eq!(insert(NotEnforced, 120).unwrap(), Enforced);
+        assert_eq!(insert(Inconclusive, 180).unwrap(), Enforced);
+        assert_eq!(insert(NotEnforced, 240).unwrap(), Enforced);
+        assert_eq!(insert(NotEnforced, 300).unwrap(), Enforced);
+        assert_eq!(insert(NotEnforced, 360).unwrap(), NotEnforced);
+        // Then damped back the other way
+        assert_eq!(insert(Enforced, 420).unwrap(), NotEnforced);
+
+        let probes = store.probes().unwrap();
+        assert_eq!(probes.len(), 1);
+        assert_eq!(probes[0].verdict, NotEnforced);
+        assert_eq!(probes[0].last_verdict, Enforced);
+        assert_eq!(probes[0].reports, 8);
+        assert_eq!(probes[0].hostname, "a.example");
+    }
+
+    #[test]
+    fn flaps_across_agents() {
+        use Verdict::*;
+        let store = Store::open(Path::new(":memory:"), 2).unwrap();
+        for (probe, verdict, timestamp) in [
+            ("a", Enforced, 0),
+            ("b", Enforced, 10),
+            ("b", NotEnforced, 3600),
+            ("a", NotEnforced, 3610),
+            ("a", NotEnforced, 7200),
+        ] {
+            store
+                .insert(&report(probe, 64496, verdict, timestamp))
+                .unwrap();
+        }
+        store.insert(&report("c", 64497, Enforced, 7210)).unwrap();
+
+        // The flap of b is not accepted, nor counted by the one of a
+        let probes = store.probes().unwrap();
+        let verdicts = probes
+            .iter()
+            .map(|probe| (probe.id.as_str(), probe.verdict, probe.last_verdict))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            [
+                ("a", NotEnforced, NotEnforced),
+                ("b", Enforced, NotEnforced),
+                ("c", Enforced, Enforced),
+            ]
+        );
+        let asns = store.asns().unwrap();
+        assert_eq!(asns.len(), 2);
+        assert_eq!(asns[0].asn, 64496);
+        assert_eq!(asns[0].verdict, NotEnforced);
+        assert_eq!(asns[0].probes, ["a", "b"]);
+        assert_eq!(asns[0].verdicts["ENFORCED"], 1);
+        assert_eq!(asns[0].verdicts["NOT_ENFORCED"], 1);
+
+        // Over time, from the damped verdict of each probe at the end of each hour
+        let history = store.asn_history(64496, None, Bucket::Hour).unwrap();
+        let history = history
+            .iter()
+            .map(|point| (point.start.timestamp(), point.verdict))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            [(0, Enforced), (3600, Enforced), (7200, NotEnforced)]
+        );
+        let since = DateTime::from_timestamp(3600, 0);
+        assert_eq!(
+            store.asn_history(64496, since, Bucket::Day).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn anonymous_reports() {
+        let store = Store::open(Path::new(":memory:"), 1).unwrap();
+        let mut report = report("", 64496, Verdict::Enforced, 0);
+        assert!(store.insert(&report).is_err());
+        report.probe = None;
+        assert!(store.insert(&report).is_err());
+    }
+}
@@ -0,0 +1,687 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use ipnet::IpNet;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+use tracing::{error, info};
+use url::Url;
+
+use rovcheck::apnic::{self, Comparison};
+use rovcheck::atlas::Probes;
+use rovcheck::history::{Entry, Summary};
+use rovcheck::output::{html, influx};
+use rovcheck::rpki::{RpkiSource, Validation, Validator};
+use rovcheck::rtr;
+use rovcheck::signing;
+use rovcheck::traceroute::PathAnalysis;
+use rovcheck::{
+    preset, AddressFamily, BeaconDefinition, BeaconPair, ConfigFile, PairPaths, RovChecker,
+    RovResult, Verdict,
+};
+
+use crate::cli::{CheckArgs, Cli, Format, RtrCommand};
+use crate::{
+    custom_beacon, exit_code, load_config, open_history, print_structured, worst_code,
+    EXIT_ENFORCED, EXIT_ERROR,
+};
+
+/// Beacon pairs of a batch file, or of stdin with `-`.
+fn read_pairs(path: &Path, sni: Option<&String>) -> Result<Vec<BeaconDefinition>> {
+    let content = match path == Path::new("-") {
+        true => io::read_to_string(io::stdin()).context("failed to read stdin")?,
+        false => std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    let mut pairs = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (valid_url, invalid_url, name) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [valid_url, invalid_url] => (valid_url, invalid_url, None),
+            [valid_url, invalid_url, name] => (valid_url, invalid_url, Some(name)),
+            _ => bail!(
+                "line {}: expected `<valid URL> <invalid URL> [name]`",
+                index + 1
+            ),
+        };
+        let parse = |url: &str| {
+            Url::parse(url).with_context(|| format!("line {}: invalid URL `{}`", index + 1, url))
+        };
+        let (valid_url, invalid_url) = (parse(valid_url)?, parse(invalid_url)?);
+        // Named after the host of the valid beacon by default
+        let name = name
+            .or(valid_url.host_str())
+            .map_or_else(|| format!("line{}", index + 1), String::from);
+        pairs.push(custom_beacon(&name, valid_url, invalid_url, sni.cloned()));
+    }
+    Ok(pairs)
+}
+
+pub async fn batch(
+    cli: &Cli,
+    args: &CheckArgs,
+    file: &ConfigFile,
+    input: &Path,
+    concurrency: u16,
+) -> Result<ExitCode> {
+    let pairs = read_pairs(input, args.sni.as_ref())?;
+    if pairs.is_empty() {
+        bail!("no beacon pairs in {}", input.display());
+    }
+    let mut config = load_config(file)?;
+    config.quorum = None;
+
+    #[derive(Serialize)]
+    struct Output {
+        name: String,
+        valid_url: Url,
+        invalid_url: Url,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<RovResult>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    }
+    #[derive(Serialize)]
+    struct BatchSummary {
+        pairs: usize,
+        verdicts: BTreeMap<String, usize>,
+        errors: usize,
+    }
+    let checks = pairs.into_iter().map(|definition| {
+        let mut config = config.clone();
+        config.pairs = vec![BeaconPair::from(&definition)];
+        async move {
+            let result = match RovChecker::new(config) {
+                Ok(checker) => checker.check().await,
+                Err(e) => Err(e),
+            };
+            let (result, error) = match result {
+                Ok(result) => (Some(result), None),
+                Err(e) => (None, Some(format!("{:#}", e))),
+            };
+            Output {
+                name: definition.name,
+                valid_url: definition.valid_url,
+                invalid_url: definition.invalid_url,
+                result,
+                error,
+            }
+        }
+    });
+    let mut checks = stream::iter(checks).buffer_unordered(usize::from(concurrency));
+
+    let mut summary = BatchSummary {
+        pairs: 0,
+        verdicts: BTreeMap::new(),
+        errors: 0,
+    };
+    let mut worst = EXIT_ENFORCED;
+    while let Some(output) = checks.next().await {
+        summary.pairs += 1;
+        let code = match &output.result {
+            Some(result) => {
+                *summary
+                    .verdicts
+                    .entry(result.verdict.to_string())
+                    .or_default() += 1;
+                exit_code(cli, result)
+            }
+            None => {
+                summary.errors += 1;
+                EXIT_ERROR
+            }
+        };
+        worst = worst_code(cli, worst, code);
+        match cli.format {
+            _ if cli.quiet => {}
+            Format::Json => println!("{}", serde_json::to_string(&output)?),
+            Format::Yaml => print!("---\n{}", serde_norway::to_string(&output)?),
+            _ => match (&output.result, &output.error) {
+                (Some(result), _) if cli.format == Format::Influx => {
+                    println!("{}", influx::lines(result).join("\n"))
+                }
+                (Some(result), _) => info!(
+                    beacon = %output.name,
+                    verdict = %result.verdict,
+                    asn = result.asn(),
+                    "{}: {}: {}",
+                    output.name,
+                    result.verdict,
+                    result.reason
+                ),
+                (None, error) => error!(
+                    beacon = %output.name,
+                    "{}: {}",
+                    output.name,
+                    error.as_deref().unwrap_or_default()
+                ),
+            },
+        }
+    }
+
+    match cli.format {
+        _ if cli.quiet => {}
+        Format::Json => println!("{}", serde_json::json!({ "summary": summary })),
+        Format::Yaml => print!("---\n{}", serde_norway::to_string(&summary)?),
+        _ => {
+            let verdicts = summary
+                .verdicts
+                .iter()
+                .map(|(verdict, count)| format!("{} {}", count, verdict))
+                .chain((summary.errors > 0).then(|| format!("{} failed", summary.errors)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            info!("{} beacon pairs checked: {}", summary.pairs, verdicts);
+        }
+    }
+    Ok(ExitCode::from(worst))
+}
+
+pub fn list_beacons(cli: &Cli, file: &ConfigFile) -> Result<ExitCode> {
+    let mut pairs = preset::names()
+        .into_iter()
+        .map(preset::get)
+        .collect::<Result<Vec<_>>>()?;
+    pairs.extend(file.beacons.iter().map(BeaconPair::from));
+
+    if !print_structured(cli, &pairs)? {
+        for pair in &pairs {
+            println!(
+                "{}\t{:?}\t{}\t{}",
+                pair.name, pair.kind, pair.valid_url, pair.invalid_url
+            );
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Beacon pair defined in the config file, or preset, of a name.
+fn find_pair(file: &ConfigFile, beacon: &str) -> Result<BeaconPair> {
+    match file
+        .beacons
+        .iter()
+        .find(|definition| definition.name == beacon)
+    {
+        Some(definition) => Ok(BeaconPair::from(definition)),
+        None => preset::get(beacon),
+    }
+}
+
+pub async fn path(cli: &Cli, file: &ConfigFile, beacon: &str, rounds: u32) -> Result<ExitCode> {
+    let mut config = load_config(file)?;
+    config.pairs = vec![find_pair(file, beacon)?];
+    config.quorum = None;
+    let mode = file.traceroute_mode.unwrap_or_default();
+    let paths = RovChecker::new(config)?.paths(mode, rounds).await?;
+
+    if !print_structured(cli, &paths)? {
+        for pair in &paths {
+            print_paths(pair);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the statistics of the paths to the beacons of a pair side by side, like mtr.
+fn print_paths(pair: &PairPaths) {
+    let target = |analysis: &PathAnalysis| match (&analysis.target, &analysis.error) {
+        (_, Some(e)) => format!("error: {}", e),
+        (Some(target), None) => target.to_string(),
+        (None, None) => "-".to_string(),
+    };
+    println!(
+        "{}: {} rounds over {}, valid beacon at {}, invalid beacon at {}",
+        pair.label(),
+        pair.valid.rounds.max(pair.invalid.rounds),
+        pair.valid.mode,
+        target(&pair.valid),
+        target(&pair.invalid)
+    );
+    let ms = |ms: Option<f64>| ms.map_or("-".to_string(), |ms| format!("{:.1}", ms));
+    let cell = |analysis: &PathAnalysis, index: usize| match analysis.hops.get(index) {
+        Some(hop) => {
+            let host = match hop.ips.as_slice() {
+                [] => "???".to_string(),
+                [ip] => ip.to_string(),
+                [ip, others @ ..] => format!("{} (+{})", ip, others.len()),
+            };
+            format!(
+                "{:<44} {:>5.1}% {:>7} {:>7} {:>7}",
+                host,
+                hop.loss,
+                ms(hop.avg_ms),
+                ms(hop.best_ms),
+                ms(hop.worst_ms)
+            )
+        }
+        None => format!("{:<44} {:>6} {:>7} {:>7} {:>7}", "", "", "", "", ""),
+    };
+    println!(
+        "{:>3}  {:<44} {:>6} {:>7} {:>7} {:>7}   {:<44} {:>6} {:>7} {:>7} {:>7}",
+        "HOP", "VALID", "LOSS", "AVG", "BEST", "WORST", "INVALID", "LOSS", "AVG", "BEST", "WORST"
+    );
+    for index in 0..pair.valid.hops.len().max(pair.invalid.hops.len()) {
+        let row = format!(
+            "{:>3}  {}   {}",
+            index + 1,
+            cell(&pair.valid, index),
+            cell(&pair.invalid, index)
+        );
+        println!("{}", row.trim_end());
+    }
+}
+
+pub async fn atlas(
+    cli: &Cli,
+    file: &ConfigFile,
+    beacon: &str,
+    probes: &Probes,
+    wait: Duration,
+) -> Result<ExitCode> {
+    let Some(atlas) = &file.atlas else {
+        bail!("a RIPE Atlas API key is required, with --atlas-key or the `atlas` table of the config file");
+    };
+    let pair = find_pair(file, beacon)?;
+    let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let family = file.family.unwrap_or(AddressFamily::Ipv4);
+    let result = atlas.measure(&client, &pair, probes, family, wait).await?;
+
+    if !print_structured(cli, &result)? {
+        for probe in &result.probes {
+            println!(
+                "probe {} ({}): {} ({})",
+                probe.probe,
+                probe.from.as_deref().unwrap_or("-"),
+                probe.verdict,
+                probe.reason
+            );
+        }
+        println!(
+            "{}: {} probes, {} enforced, {} not enforced, {} inconclusive (measurements {} and {})",
+            result.name,
+            result.probes.len(),
+            result.count(Verdict::Enforced),
+            result.count(Verdict::NotEnforced),
+            result.count(Verdict::Inconclusive),
+            result.valid_measurement,
+            result.invalid_measurement
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn compare(
+    cli: &Cli,
+    file: &ConfigFile,
+    asn: Option<u32>,
+    url: &Url,
+) -> Result<ExitCode> {
+    let result = RovChecker::new(load_config(file)?)?.check().await?;
+    let Some(asn) = asn.or(result.asn()) else {
+        bail!("the beacons did not report the ASN, it must be given with --asn");
+    };
+    let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let score = apnic::score(&client, url, asn).await?;
+    let comparison = Comparison::new(asn, result.asn(), result.verdict, result.reason, score);
+
+    if !print_structured(cli, &comparison)? {
+        let seen = comparison
+            .seen_asn
+            .map(|seen| format!(" (the beacons saw AS{})", seen))
+            .unwrap_or_default();
+        println!(
+            "local: {}: {}{}",
+            comparison.verdict, comparison.reason, seen
+        );
+        println!(
+            "APNIC Labs: {}: {:.1}% of {} samples of AS{} filtering RPKI invalid routes",
+            comparison.apnic_verdict,
+            comparison.apnic.filtered,
+            comparison.apnic.samples,
+            comparison.asn
+        );
+        match comparison.agrees {
+            Some(true) => println!("the verdicts agree"),
+            Some(false) => println!("the verdicts disagree"),
+            None => println!("the verdicts cannot be compared"),
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn validate(
+    cli: &Cli,
+    source: RpkiSource,
+    url: Option<&Url>,
+    prefix: IpNet,
+    asn: u32,
+) -> Result<ExitCode> {
+    let client = Client::builder().timeout(Duration::from_secs(60)).build()?;
+    let validator = Validator::load(&client, source, url).await?;
+    let validation = validator.validate(&client, prefix, asn).await?;
+    print_validation(cli, &validation)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Check results of a file: a single result or an array of them, the history output, or JSON
+/// lines.
+fn read_results(path: &Path) -> Result<Vec<serde_json::Value>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let value = match serde_json::from_str::<serde_json::Value>(&content) {
+        Ok(value) => value,
+        Err(_) => {
+            return content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).context("invalid JSON line"))
+                .collect();
+        }
+    };
+    Ok(match value {
+        serde_json::Value::Array(results) => results,
+        serde_json::Value::Object(output) if output.contains_key("entries") => {
+            match &output["entries"] {
+                serde_json::Value::Array(entries) => entries
+                    .iter()
+                    .filter_map(|entry| entry.get("result").cloned())
+                    .collect(),
+                _ => bail!("invalid history output"),
+            }
+        }
+        result => vec![result],
+    })
+}
+
+pub fn verify(cli: &Cli, path: &Path, public_keys: &[String]) -> Result<ExitCode> {
+    let trusted = public_keys
+        .iter()
+        .map(|key| signing::parse_public_key(key))
+        .collect::<Result<Vec<_>>>()?;
+
+    #[derive(Serialize)]
+    struct Verification {
+        finished_at: Option<String>,
+        verdict: Option<String>,
+        public_key: Option<String>,
+        error: Option<String>,
+    }
+    let verifications = read_results(path)?
+        .iter()
+        .map(|result| {
+            let field = |name: &str| {
+                result
+                    .get(name)
+                    .and_then(|value| value.as_str())
+                    .map(String::from)
+            };
+            let (public_key, error) = match signing::verify(result, &trusted) {
+                Ok(key) => (Some(key), None),
+                Err(e) => (None, Some(format!("{:#}", e))),
+            };
+            Verification {
+                finished_at: field("finished_at"),
+                verdict: field("verdict"),
+                public_key,
+                error,
+            }
+        })
+        .collect::<Vec<_>>();
+    if verifications.is_empty() {
+        bail!("no check results in {}", path.display());
+    }
+
+    let valid = verifications
+        .iter()
+        .all(|verification| verification.error.is_none());
+    match cli.format {
+        _ if cli.quiet => {}
+        Format::Json => println!("{}", serde_json::to_string(&verifications)?),
+        Format::Yaml => print!("{}", serde_norway::to_string(&verifications)?),
+        _ => {
+            for verification in &verifications {
+                let at = verification.finished_at.as_deref().unwrap_or_default();
+                let verdict = verification.verdict.as_deref().unwrap_or_default();
+                match (&verification.public_key, &verification.error) {
+                    (Some(key), _) => info!("{} {}: signed by {}", at, verdict, key),
+                    (None, error) => error!(
+                        "{} {}: {}",
+                        at,
+                        verdict,
+                        error.as_deref().unwrap_or_default()
+                    ),
+                }
+            }
+            let signed = verifications
+                .iter()
+                .filter(|verification| verification.error.is_none())
+                .count();
+            info!("{}/{} results validly signed", signed, verifications.len());
+        }
+    }
+    match valid {
+        true => Ok(ExitCode::SUCCESS),
+        false => Ok(ExitCode::FAILURE),
+    }
+}
+
+pub async fn rtr(
+    cli: &Cli,
+    server: &str,
+    timeout: Duration,
+    command: &RtrCommand,
+) -> Result<ExitCode> {
+    let snapshot = rtr::fetch(server, timeout).await?;
+    match command {
+        RtrCommand::Status => {
+            if !print_structured(cli, &snapshot)? {
+                let seconds =
+                    |interval: Option<u32>| interval.map_or("-".to_string(), |s| format!("{}s", s));
+                println!("server: {}", snapshot.server);
+                println!("version: {}", snapshot.version);
+                println!("session: {}", snapshot.session_id);
+                println!("serial: {}", snapshot.serial);
+                println!(
+                    "refresh: {}, retry: {}, expire: {}",
+                    seconds(snapshot.refresh),
+                    seconds(snapshot.retry),
+                    seconds(snapshot.expire)
+                );
+                println!(
+                    "payloads: {} IPv4 prefixes, {} IPv6 prefixes, {} router keys",
+                    snapshot.ipv4_prefixes, snapshot.ipv6_prefixes, snapshot.router_keys
+                );
+                println!("transfer: {:.0}ms", snapshot.duration_ms);
+            }
+        }
+        RtrCommand::Validate { prefix, asn } => {
+            info!(
+                session_id = snapshot.session_id,
+                serial = snapshot.serial,
+                "Fetched {} payloads from {}",
+                snapshot.roas.len(),
+                snapshot.server
+            );
+            let validator = Validator::Roas {
+                source: RpkiSource::Rtr,
+                roas: snapshot.roas,
+            };
+            let validation = validator.validate(&Client::new(), *prefix, *asn).await?;
+            print_validation(cli, &validation)?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_validation(cli: &Cli, validation: &Validation) -> Result<()> {
+    if !print_structured(cli, &validation)? {
+        println!(
+            "{} AS{}: {}",
+            validation.prefix, validation.asn, validation.validity
+        );
+        for roa in &validation.roas {
+            let state = if roa.matches(&validation.prefix, validation.asn) {
+                "matching"
+            } else {
+                "covering"
+            };
+            println!("  {} ROA {}", state, roa);
+        }
+    }
+    Ok(())
+}
+
+pub fn show_config(cli: &Cli, file: ConfigFile, show_secrets: bool) -> Result<ExitCode> {
+    let mut file = file.with_defaults();
+    if !show_secrets {
+        file = file.redacted();
+    }
+    if !print_structured(cli, &file)? {
+        print!("{}", toml::to_string(&file)?)
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn report(
+    file: &ConfigFile,
+    path: &Path,
+    since: Option<DateTime<Utc>>,
+) -> Result<ExitCode> {
+    let entries = match open_history(file)? {
+        Some(history) => history.query(since, None)?,
+        None => {
+            let result = RovChecker::new(load_config(file)?)?.check().await?;
+            vec![Entry {
+                id: 0,
+                asn: result.asn(),
+                result,
+            }]
+        }
+    };
+    std::fs::write(path, html::report(&entries))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    info!(
+        "Wrote the report of {} checks to {}",
+        entries.len(),
+        path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn show_history(
+    cli: &Cli,
+    file: &ConfigFile,
+    since: Option<DateTime<Utc>>,
+    verdict: Option<Verdict>,
+) -> Result<ExitCode> {
+    let Some(history) = open_history(file)? else {
+        bail!("no history database configured");
+    };
+    let entries = history.query(since, verdict)?;
+    let summary = Summary::new(&entries);
+
+    #[derive(Serialize)]
+    struct Output<'a> {
+        entries: &'a [Entry],
+        summary: &'a Summary,
+    }
+    let output = Output {
+        entries: &entries,
+        summary: &summary,
+    };
+    if !print_structured(cli, &output)? {
+        for entry in &entries {
+            let asn = entry
+                .asn
+                .map(|asn| format!("AS{}", asn))
+                .unwrap_or_default();
+            println!(
+                "{}\t{}\t{}\t{}",
+                entry.result.started_at.to_rfc3339(),
+                entry.result.verdict,
+                asn,
+                entry.result.reason
+            );
+        }
+        let verdicts = summary
+            .verdicts
+            .iter()
+            .map(|(verdict, count)| format!("{} {}", count, verdict))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "{} checks ({}), {} verdict changes",
+            summary.checks, verdicts, summary.changes
+        );
+        if let Some(last_change) = summary.last_change {
+            println!("Last verdict change: {}", last_change.to_rfc3339());
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(name: &str, content: &str) -> Result<Vec<BeaconDefinition>> {
+        let path =
+            std::env::temp_dir().join(format!("rovcheck-test-{}-{}.txt", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let pairs = read_pairs(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        pairs
+    }
+
+    #[test]
+    fn read_pairs_lines() {
+        let pairs = pairs(
+            "pairs",
+            "# beacons\n\
+             \n\
+             https://valid.example/ https://invalid.example/\n\
+             \u{20}  # indented comment\n\
+             http://192.0.2.1/ http://198.51.100.1/ lab\t\n",
+        )
+        .unwrap();
+        let pairs = pairs
+            .iter()
+            .map(|pair| {
+                (
+                    pair.name.as_str(),
+                    pair.valid_url.as_str(),
+                    pair.invalid_url.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            [
+                (
+                    "valid.example",
+                    "https://valid.example/",
+                    "https://invalid.example/"
+                ),
+                ("lab", "http://192.0.2.1/", "http://198.51.100.1/"),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_pairs_malformed() {
+        let error = pairs("missing", "https://valid.example/\n").unwrap_err();
+        assert!(error.to_string().starts_with("line 1: expected"));
+        let error = pairs("extra", "\na b c d\n").unwrap_err();
+        assert!(error.to_string().starts_with("line 2: expected"));
+        let error = pairs("url", "# pairs\nhttps://valid.example/ invalid\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid URL `invalid`");
+    }
+}
@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::beacon::{BeaconResult, Invalidity};
+use crate::checker::{PairResult, RovResult};
+use crate::verdict::Verdict;
+
+/// Weights of the factors lowering the confidence in a verdict.
+const SINGLE_PAIR: f64 = 0.8;
+const SINGLE_TRIAL: f64 = 0.85;
+const RETRIES: f64 = 0.9;
+const RPKI_UNVERIFIED: f64 = 0.9;
+const LIVENESS_UNCHECKED: f64 = 0.85;
+
+/// Confidence in a conclusive verdict, from 0 to 1, telling a verdict from many agreeing beacons
+/// over several trials apart from a single sample.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Confidence {
+    pub score: f64,
+    /// What lowered the score, e.g. `single trial`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub factors: Vec<String>,
+}
+
+impl Confidence {
+    /// Level of the score: `high` from 0.8, `medium` from 0.5, `low` below.
+    pub fn level(&self) -> &'static str {
+        match self.score {
+            score if score >= 0.8 => "high",
+            score if score >= 0.5 => "medium",
+            _ => "low",
+        }
+    }
+
+    fn lower(&mut self, weight: f64, factor: String) {
+        self.score *= weight;
+        self.factors.push(factor);
+    }
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} ({})", self.score, self.level())
+    }
+}
+
+/// Share of the trials of a beacon agreeing with its reachability.
+fn consistency(beacon: &BeaconResult) -> f64 {
+    let trials = beacon.trials.max(1);
+    let agreeing = if beacon.success {
+        beacon.successes
+    } else {
+        trials.saturating_sub(beacon.successes)
+    };
+    agreeing as f64 / trials as f64
+}
+
+fn verified(pair: &PairResult) -> bool {
+    [&pair.valid, &pair.invalid]
+        .iter()
+        .all(|beacon| matches!(&beacon.rpki, Some(rpki) if rpki.error.is_none()))
+}
+
+/// Whether the invalid beacon of a pair is known up, unreachable as it is when ROV is enforced.
+fn alive(pair: &PairResult) -> bool {
+    let control = pair
+        .control
+        .as_ref()
+        .is_some_and(|control| control.reachable == Some(true));
+    let announced = pair.ris.as_ref().is_some_and(|ris| ris.announced());
+    control || announced
+}
+
+/// Confidence in the verdict of a check, none if it is INCONCLUSIVE: lowered by the beacon pairs
+/// disagreeing with it, a single pair or trial, inconsistent trials, retries, and the RPKI state
+/// and liveness of the beacons left unchecked.
+pub fn confidence(result: &RovResult) -> Option<Confidence> {
+    if result.verdict == Verdict::Inconclusive {
+        return None;
+    }
+    // As for the verdict, the unknown beacons only count if there are no others
+    let pairs = result
+        .pairs
+        .iter()
+        .filter(|pair| !pair.http3)
+        .collect::<Vec<_>>();
+    let pairs = match pairs
+        .iter()
+        .any(|pair| pair.invalidity != Invalidity::Unknown)
+    {
+        true => pairs
+            .into_iter()
+            .filter(|pair| pair.invalidity != Invalidity::Unknown)
+            .collect(),
+        false => pairs,
+    };
+    let agreeing = pairs
+        .iter()
+        .filter(|pair| pair.verdict == result.verdict)
+        .copied()
+        .collect::<Vec<_>>();
+    if agreeing.is_empty() {
+        return None;
+    }
+
+    let mut confidence = Confidence {
+        score: 1.0,
+        factors: Vec::new(),
+    };
+    if agreeing.len() < pairs.len() {
+        confidence.lower(
+            agreeing.len() as f64 / pairs.len() as f64,
+            format!("{}/{} beacon pairs agree", agreeing.len(), pairs.len()),
+        );
+    }
+    if pairs.len() == 1 {
+        confidence.lower(SINGLE_PAIR, "single beacon pair".to_string());
+    }
+
+    let beacons = agreeing
+        .iter()
+        .flat_map(|pair| {
+            [
+                ("valid", *pair, &pair.valid),
+                ("invalid", *pair, &pair.invalid),
+            ]
+        })
+        .collect::<Vec<_>>();
+    if beacons.iter().all(|(_, _, beacon)| beacon.trials <= 1) {
+        confidence.lower(SINGLE_TRIAL, "single trial".to_string());
+    }
+    if let Some((validity, pair, beacon)) = beacons
+        .iter()
+        .filter(|(_, _, beacon)| consistency(beacon) < 1.0)
+        .min_by(|(_, _, a), (_, _, b)| consistency(a).total_cmp(&consistency(b)))
+    {
+        confidence.lower(
+            consistency(beacon),
+            format!(
+                "{} {} beacon reached on {}/{} trials",
+                pair.label(),
+                validity,
+                beacon.successes,
+                beacon.trials
+            ),
+        );
+    }
+    let retried = beacons
+        .iter()
+        .filter(|(_, _, beacon)| beacon.attempts > beacon.trials.max(1))
+        .count();
+    if retried > 0 {
+        confidence.lower(RETRIES, format!("beacons retried ({})", retried));
+    }
+    if !agreeing.iter().all(|pair| verified(pair)) {
+        confidence.lower(
+            RPKI_UNVERIFIED,
+            "beacon RPKI state not verified".to_string(),
+        );
+    }
+    if result.verdict == Verdict::Enforced && !agreeing.iter().all(|pair| alive(pair)) {
+        confidence.lower(
+            LIVENESS_UNCHECKED,
+            "invalid beacon liveness not checked".to_string(),
+        );
+    }
+    confidence.score = (confidence.score * 100.0).round() / 100.0;
+    Some(confidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+    use crate::control::{ControlKind, ControlResult};
+    use crate::rpki::BeaconRpki;
+
+    /// Pair of the result checked over 3 trials, its RPKI state verified and its invalid beacon
+    /// seen up from a control vantage.
+    fn checked(name: &str, verdict: Verdict) -> PairResult {
+        let mut pair = result().pairs.remove(0);
+        pair.name = name.to_string();
+        pair.verdict = verdict;
+        pair.invalid.success = verdict == Verdict::NotEnforced;
+        for beacon in [&mut pair.valid, &mut pair.invalid] {
+            beacon.trials = 3;
+            beacon.attempts = 3;
+            beacon.successes = if beacon.success { 3 } else { 0 };
+            beacon.rpki = Some(BeaconRpki {
+                address: None,
+                validations: Vec::new(),
+                error: None,
+            });
+        }
+        pair.control = Some(ControlResult {
+            kind: ControlKind::Relay,
+            reachable: Some(true),
+            error: None,
+        });
+        pair
+    }
+
+    #[test]
+    fn clean() {
+        let mut result = result();
+        result.pairs = vec![
+            checked("cloudflare", Verdict::Enforced),
+            checked("ripe", Verdict::Enforced),
+        ];
+        let confidence = confidence(&result).unwrap();
+        assert_eq!(confidence.score, 1.0);
+        assert!(confidence.factors.is_empty());
+        assert_eq!(confidence.to_string(), "1.00 (high)");
+    }
+
+    #[test]
+    fn degraded_trials() {
+        let mut result = result();
+        let valid = &mut result.pairs[0].valid;
+        (valid.trials, valid.successes, valid.attempts) = (3, 2, 4);
+        let confidence = confidence(&result).unwrap();
+        // 0.8 * 2/3 * 0.9 * 0.9 * 0.85
+        assert_eq!(confidence.score, 0.37);
+        assert_eq!(
+            confidence.factors,
+            [
+                "single beacon pair",
+                "cloudflare (ipv4) valid beacon reached on 2/3 trials",
+                "beacons retried (1)",
+                "beacon RPKI state not verified",
+                "invalid beacon liveness not checked",
+            ]
+        );
+        assert_eq!(confidence.level(), "low");
+    }
+
+    #[test]
+    fn quorum_disagreement() {
+        let mut result = result();
+        result.pairs = vec![
+            checked("cloudflare", Verdict::Enforced),
+            checked("ripe", Verdict::Enforced),
+            checked("nlnog", Verdict::NotEnforced),
+        ];
+        let confidence = confidence(&result).unwrap();
+        assert_eq!(confidence.score, 0.67);
+        assert_eq!(confidence.factors, ["2/3 beacon pairs agree"]);
+        assert_eq!(confidence.level(), "medium");
+    }
+
+    #[test]
+    fn inconclusive() {
+        let mut result = result();
+        result.verdict = Verdict::Inconclusive;
+        assert_eq!(confidence(&result), None);
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::matcher::Matcher;
+
+/// How the control vantage fetches the invalid beacons.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlKind {
+    /// HTTP or SOCKS proxy the invalid beacons are requested through
+    #[default]
+    Relay,
+    /// API fetching the invalid beacon given as the `url` query parameter, answering
+    /// `{"reachable": true|false}`
+    Api,
+}
+
+impl FromStr for ControlKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relay" => Ok(ControlKind::Relay),
+            "api" => Ok(ControlKind::Api),
+            _ => bail!("unknown control kind `{}`", s),
+        }
+    }
+}
+
+impl std::fmt::Display for ControlKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlKind::Relay => write!(f, "relay"),
+            ControlKind::Api => write!(f, "api"),
+        }
+    }
+}
+
+/// Control vantage in a network not enforcing ROV, telling an invalid beacon dropped by the
+/// checked network apart from a beacon which is down.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Control {
+    #[serde(default)]
+    pub kind: ControlKind,
+    /// URL of the relay proxy or of the API
+    pub url: Url,
+    /// Bearer token of the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Control {
+    pub fn new(kind: ControlKind, url: Url) -> Self {
+        Control {
+            kind,
+            url,
+            token: None,
+        }
+    }
+}
+
+/// Reachability of the invalid beacon from the control vantage.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ControlResult {
+    pub kind: ControlKind,
+    /// Whether the control vantage reached the invalid beacon, unknown if the API failed
+    pub reachable: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiResponse {
+    reachable: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl Control {
+    /// Request of the beacon through the relay, failing if the beacon does not answer or
+    /// its response does not match.
+    async fn relay(&self, client: &Client, url: &Url, matcher: Option<&Matcher>) -> Result<()> {
+        let response = client.get(url.clone()).send().await?;
+        match matcher {
+            Some(matcher) => {
+                let status = response.status().as_u16();
+                matcher.check(status, &response.bytes().await?)?;
+            }
+            None => {
+                response.error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn api(&self, client: &Client, url: &Url) -> Result<ApiResponse> {
+        let mut api = self.url.clone();
+        api.query_pairs_mut().append_pair("url", url.as_str());
+        let mut request = client.get(api);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?.error_for_status()?;
+        response
+            .json()
+            .await
+            .context("invalid control API response")
+    }
+
+    /// Fetch the invalid beacon from the control vantage, the client going through the relay if
+    /// any. A beacon which could not be requested through the relay is unreachable, while a
+    /// failed API call leaves it unknown.
+    #[instrument(level = "debug", name = "control", skip_all, fields(url = %url))]
+    pub async fn check(
+        &self,
+        client: &Client,
+        url: &Url,
+        matcher: Option<&Matcher>,
+    ) -> ControlResult {
+        let mut result = ControlResult {
+            kind: self.kind,
+            reachable: None,
+            error: None,
+        };
+        match self.kind {
+            ControlKind::Relay => match self.relay(client, url, matcher).await {
+                Ok(()) => result.reachable = Some(true),
+                Err(e) => {
+                    result.reachable = Some(false);
+                    result.error = Some(format!("{:#}", e));
+                }
+            },
+            ControlKind::Api => match self.api(client, url).await {
+                Ok(response) => {
+                    result.reachable = Some(response.reachable);
+                    result.error = response.error;
+                }
+                Err(e) => result.error = Some(e.to_string()),
+            },
+        }
+        debug!(
+            reachable = result.reachable,
+            error = result.error,
+            "Control fetch finished"
+        );
+        result
+    }
+}
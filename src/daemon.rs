@@ -0,0 +1,338 @@
+use anyhow::Result;
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::Notify;
+use tokio::time::Interval;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+use crate::checker::{RovChecker, RovResult};
+use crate::export::{export, flush, Exporter};
+use crate::history::History;
+use crate::notify::{notify, Event, Notifier, Ping};
+use crate::systemd;
+use crate::verdict::Verdict;
+use crate::watcher::{Transition, Watcher};
+
+/// Runs checks periodically, storing their results and notifying verdict transitions.
+pub struct Daemon {
+    watcher: Watcher,
+    interval: Duration,
+    history: Option<History>,
+    notifiers: Vec<Notifier>,
+    ping: Option<Ping>,
+    exporters: Vec<Exporter>,
+    client: Client,
+    /// Number of consecutive checks which verdict was not ENFORCED
+    failures: u32,
+    reload: Option<Reload>,
+    trigger: Option<Arc<Notify>>,
+    profile: Option<String>,
+}
+
+/// Builds a new daemon from the reloaded configuration, of which the checker,
+/// interval, notifiers, ping and exporters replace the running ones.
+pub type Reload = Box<dyn Fn() -> Result<Daemon> + Send>;
+
+enum Wake {
+    Check,
+    Trigger,
+    Reload,
+    Shutdown,
+}
+
+impl Daemon {
+    pub fn new(checker: RovChecker, interval: Duration) -> Self {
+        Daemon {
+            watcher: Watcher::new(checker),
+            interval,
+            history: None,
+            notifiers: Vec::new(),
+            ping: None,
+            exporters: Vec::new(),
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            failures: 0,
+            reload: None,
+            trigger: None,
+            profile: None,
+        }
+    }
+
+    /// Name of the profile of the config file the daemon runs, if any.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn with_history(mut self, history: Option<History>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn with_notifiers(mut self, notifiers: Vec<Notifier>) -> Self {
+        self.notifiers = notifiers;
+        self
+    }
+
+    pub fn with_ping(mut self, ping: Option<Ping>) -> Self {
+        self.ping = ping;
+        self
+    }
+
+    pub fn with_exporters(mut self, exporters: Vec<Exporter>) -> Self {
+        self.exporters = exporters;
+        self
+    }
+
+    /// Only report verdict changes once they are seen in `alert_after` consecutive checks,
+    /// or `recover_after` when back to ENFORCED.
+    pub fn with_damping(mut self, alert_after: u32, recover_after: u32) -> Self {
+        self.watcher = self.watcher.with_damping(alert_after, recover_after);
+        self
+    }
+
+    /// Reload the configuration on SIGHUP.
+    pub fn with_reload(mut self, reload: Reload) -> Self {
+        self.reload = Some(reload);
+        self
+    }
+
+    /// Check right away when `trigger` is notified, the next scheduled check being an interval
+    /// later.
+    pub fn with_trigger(mut self, trigger: Arc<Notify>) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Run a profile of the config file, its name identifying the logs of the daemon.
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Replace the configuration by the reloaded one, keeping the verdict state and the history.
+    async fn reload(&mut self) {
+        let Some(reload) = &self.reload else {
+            return;
+        };
+        systemd::reloading();
+        match reload() {
+            Ok(daemon) => {
+                flush(&self.client, &self.exporters).await;
+                self.watcher.replace(daemon.watcher);
+                self.interval = daemon.interval;
+                self.notifiers = daemon.notifiers;
+                self.ping = daemon.ping;
+                self.exporters = daemon.exporters;
+                info!("Reloaded the configuration");
+            }
+            Err(e) => error!("Failed to reload the configuration: {:#}", e),
+        }
+        systemd::ready();
+    }
+
+    /// Run the checks until SIGTERM or SIGINT, calling `on_result` after each of them with the
+    /// verdict transition it reports, once damped.
+    /// On shutdown, the in-flight check completes (or is cancelled by a second signal)
+    /// and the exporters are flushed.
+    pub async fn run<F>(self, on_result: F) -> Result<()>
+    where
+        F: FnMut(&RovResult, Option<&Transition>) -> Result<()>,
+    {
+        let span = match &self.profile {
+            Some(profile) => info_span!("profile", name = %profile),
+            None => Span::none(),
+        };
+        self.checks(on_result).instrument(span).await
+    }
+
+    async fn checks<F>(mut self, mut on_result: F) -> Result<()>
+    where
+        F: FnMut(&RovResult, Option<&Transition>) -> Result<()>,
+    {
+        let mut ticker = tokio::time::interval(self.interval);
+        let watchdog = systemd::watchdog();
+        let mut hangup = match self.reload {
+            Some(_) => Some(signal(SignalKind::hangup())?),
+            None => None,
+        };
+        let mut shutdown = Shutdown::new()?;
+        systemd::ready();
+        loop {
+            let trigger = self.trigger.clone();
+            match wait(
+                &mut ticker,
+                watchdog,
+                hangup.as_mut(),
+                trigger.as_deref(),
+                &mut shutdown,
+            )
+            .await
+            {
+                Wake::Check => {}
+                Wake::Trigger => {
+                    info!("Checking on demand");
+                    ticker.reset();
+                }
+                Wake::Reload => {
+                    self.reload().await;
+                    // Check right away with the new configuration
+                    ticker = tokio::time::interval(self.interval);
+                    continue;
+                }
+                Wake::Shutdown => break,
+            }
+
+            let (tick, stopping) = {
+                let tick = self.watcher.tick();
+                tokio::pin!(tick);
+                tokio::select! {
+                    tick = &mut tick => (tick, false),
+                    _ = shutdown.recv() => {
+                        info!("Shutting down after the current check, signal again to cancel it");
+                        tokio::select! {
+                            tick = &mut tick => (tick, true),
+                            _ = shutdown.recv() => {
+                                info!("Cancelled the current check");
+                                break;
+                            }
+                        }
+                    }
+                }
+            };
+            match tick {
+                Ok((result, transition)) => {
+                    self.handle(result, transition, &mut on_result).await;
+                }
+                Err(e) => {
+                    error!("{:#}", e);
+                    systemd::status(&format!("Check failed: {:#}", e));
+                    ping(&self.client, self.ping.as_ref(), false).await;
+                }
+            }
+            if stopping {
+                break;
+            }
+        }
+
+        systemd::stopping();
+        flush(&self.client, &self.exporters).await;
+        info!("Stopped");
+        Ok(())
+    }
+
+    async fn handle<F>(
+        &mut self,
+        result: RovResult,
+        transition: Option<Transition>,
+        on_result: &mut F,
+    ) where
+        F: FnMut(&RovResult, Option<&Transition>) -> Result<()>,
+    {
+        if let Some(transition) = &transition {
+            warn!(
+                "Verdict changed from {} to {}",
+                transition.from, transition.to
+            );
+        }
+        self.failures = match result.verdict {
+            Verdict::Enforced => 0,
+            _ => self.failures + 1,
+        };
+        let event = Event {
+            transition,
+            failures: self.failures,
+            result: result.clone(),
+        };
+        notify(&self.client, &self.notifiers, &event).await;
+        let success = result.verdict == Verdict::Enforced;
+        ping(&self.client, self.ping.as_ref(), success).await;
+        export(&self.client, &self.exporters, &result).await;
+
+        if let Err(e) = on_result(&result, event.transition.as_ref()) {
+            error!("{:#}", e);
+        }
+        systemd::status(&format!("{}: {}", result.verdict, result.reason));
+
+        if let Some(history) = &self.history {
+            if let Err(e) = history.insert(&result) {
+                error!("Failed to store result: {:#}", e);
+            }
+        }
+    }
+}
+
+/// SIGTERM and SIGINT listener.
+pub struct Shutdown {
+    terminate: Signal,
+    interrupt: Signal,
+}
+
+impl Shutdown {
+    pub fn new() -> Result<Self> {
+        Ok(Shutdown {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    /// Wait for the next signal.
+    pub async fn recv(&mut self) {
+        tokio::select! {
+            _ = self.terminate.recv() => {}
+            _ = self.interrupt.recv() => {}
+        }
+    }
+}
+
+/// Keep the systemd watchdog alive, if it is enabled. Never returns.
+async fn keepalive(watchdog: Option<Duration>) {
+    let Some(watchdog) = watchdog else {
+        return std::future::pending().await;
+    };
+    let mut keepalive = tokio::time::interval(watchdog);
+    loop {
+        keepalive.tick().await;
+        systemd::keepalive();
+    }
+}
+
+/// Wait for the next check or for a signal, keeping the systemd watchdog alive meanwhile.
+async fn wait(
+    ticker: &mut Interval,
+    watchdog: Option<Duration>,
+    hangup: Option<&mut Signal>,
+    trigger: Option<&Notify>,
+    shutdown: &mut Shutdown,
+) -> Wake {
+    let hangup = async {
+        match hangup {
+            Some(hangup) => hangup.recv().await,
+            None => std::future::pending().await,
+        }
+    };
+    let trigger = async {
+        match trigger {
+            Some(trigger) => trigger.notified().await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = ticker.tick() => Wake::Check,
+        _ = trigger => Wake::Trigger,
+        _ = hangup => Wake::Reload,
+        _ = shutdown.recv() => Wake::Shutdown,
+        _ = keepalive(watchdog) => unreachable!(),
+    }
+}
+
+async fn ping(client: &Client, ping: Option<&Ping>, success: bool) {
+    if let Some(ping) = ping {
+        if let Err(e) = ping.send(client, success).await {
+            error!("Failed to ping {}: {:#}", ping.url, e);
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use anyhow::{bail, Context, Result};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Client;
+use rustls_platform_verifier::ConfigVerifierExt;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_rustls::rustls::{pki_types::ServerName, ClientConfig};
+use tokio_rustls::TlsConnector;
+use url::{Host, Url};
+
+use crate::probe::Connect;
+use crate::resolver::AddressFamily;
+
+/// Largest DNS message over UDP without EDNS.
+const UDP_SIZE: usize = 512;
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// DNS server the beacon hostnames are resolved with, instead of the system resolver.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum NameServer {
+    /// Plain DNS over UDP, falling back to TCP for truncated responses
+    Udp(SocketAddr),
+    /// DNS over TLS (RFC 7858)
+    Tls { host: Host<String>, port: u16 },
+    /// DNS over HTTPS (RFC 8484)
+    Https(Url),
+}
+
+impl NameServer {
+    /// A DNS over TLS server: `dns.quad9.net`, `9.9.9.9`, `[2620:fe::fe]:853`...
+    pub fn tls(s: &str) -> Result<Self, String> {
+        let url = Url::parse(&format!("tls://{}", s))
+            .ok()
+            .filter(|url| url.path().is_empty() && url.username().is_empty())
+            .ok_or_else(|| format!("invalid DNS over TLS server `{}`, expected HOST[:PORT]", s))?;
+        let host = url
+            .host()
+            .ok_or_else(|| format!("missing host in DNS over TLS server `{}`", s))?;
+        Ok(NameServer::Tls {
+            host: host.to_owned(),
+            port: url.port().unwrap_or(853),
+        })
+    }
+}
+
+impl FromStr for NameServer {
+    type Err = String;
+
+    /// An IP address with an optional port (`9.9.9.9`, `[2620:fe::fe]:53`),
+    /// a DNS over TLS server (`tls://dns.quad9.net`) or a DNS over HTTPS URL (`https://dns.quad9.net/dns-query`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(server) = s.strip_prefix("tls://") {
+            return NameServer::tls(server);
+        }
+        if s.starts_with("https://") {
+            let url =
+                Url::parse(s).map_err(|e| format!("invalid DNS over HTTPS URL `{}`: {}", s, e))?;
+            return Ok(NameServer::Https(url));
+        }
+        let addr = match s.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, 53),
+            Err(_) => s
+                .parse::<SocketAddr>()
+                .map_err(|_| format!("invalid DNS server `{}`, expected an IP address", s))?,
+        };
+        Ok(NameServer::Udp(addr))
+    }
+}
+
+impl TryFrom<String> for NameServer {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<NameServer> for String {
+    fn from(server: NameServer) -> Self {
+        server.to_string()
+    }
+}
+
+impl std::fmt::Display for NameServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameServer::Udp(addr) => write!(f, "{}", addr),
+            NameServer::Tls { host, port } => write!(f, "tls://{}:{}", host, port),
+            NameServer::Https(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Type of the address records of a family.
+fn record_type(family: AddressFamily) -> u16 {
+    match family {
+        AddressFamily::Ipv4 => 1,
+        AddressFamily::Ipv6 => 28,
+    }
+}
+
+/// Recursive query of the records of a type for a name.
+fn query(id: u16, name: &str, kind: u16) -> Result<Vec<u8>> {
+    let mut message = Vec::with_capacity(UDP_SIZE);
+    message.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, a single question
+    message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("invalid DNS name {}", name);
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&kind.to_be_bytes());
+    // IN class
+    message.extend_from_slice(&1u16.to_be_bytes());
+    Ok(message)
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .message
+            .get(self.position..self.position + length)
+            .context("truncated DNS response")?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skip a name, ending with a compression pointer or the root label.
+    fn skip_name(&mut self) -> Result<()> {
+        loop {
+            match self.take(1)?[0] {
+                0 => return Ok(()),
+                length if length & 0xc0 == 0xc0 => {
+                    self.take(1)?;
+                    return Ok(());
+                }
+                length => {
+                    self.take(length as usize)?;
+                }
+            }
+        }
+    }
+}
+
+fn rcode(code: u16) -> String {
+    match code {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        code => format!("RCODE {}", code),
+    }
+}
+
+/// Addresses of a response to a query, `None` if the response was truncated.
+fn parse(id: u16, kind: u16, message: &[u8]) -> Result<Option<Vec<IpAddr>>> {
+    let mut reader = Reader {
+        message,
+        position: 0,
+    };
+    if reader.u16()? != id {
+        bail!("DNS response ID does not match the query");
+    }
+    let flags = reader.u16()?;
+    if flags & 0x8000 == 0 {
+        bail!("DNS message is not a response");
+    }
+    if flags & 0x0200 != 0 {
+        return Ok(None);
+    }
+    if flags & 0x000f != 0 {
+        bail!("{}", rcode(flags & 0x000f));
+    }
+    let questions = reader.u16()?;
+    let answers = reader.u16()?;
+    // Authority and additional records counts
+    reader.take(4)?;
+    for _ in 0..questions {
+        reader.skip_name()?;
+        reader.take(4)?;
+    }
+
+    let mut addrs = Vec::new();
+    for _ in 0..answers {
+        reader.skip_name()?;
+        let record = reader.u16()?;
+        // Class and TTL
+        reader.take(6)?;
+        let length = reader.u16()? as usize;
+        let data = reader.take(length)?;
+        // The CNAME records of the chain are skipped
+        if record != kind {
+            continue;
+        }
+        if let (1, Ok(octets)) = (record, <[u8; 4]>::try_from(data)) {
+            addrs.push(IpAddr::V4(Ipv4Addr::from(octets)));
+        } else if let (28, Ok(octets)) = (record, <[u8; 16]>::try_from(data)) {
+            addrs.push(IpAddr::V6(Ipv6Addr::from(octets)));
+        } else {
+            bail!("malformed DNS address record of {} bytes", data.len());
+        }
+    }
+    Ok(Some(addrs))
+}
+
+async fn exchange_udp(addr: SocketAddr, message: &[u8]) -> Result<Vec<u8>> {
+    let local = match addr {
+        SocketAddr::V4(_) => AddressFamily::Ipv4.unspecified(),
+        SocketAddr::V6(_) => AddressFamily::Ipv6.unspecified(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(local, 0)).await?;
+    // Only accept the responses of the server
+    socket.connect(addr).await?;
+    socket.send(message).await?;
+    let mut buf = vec![0; UDP_SIZE];
+    let length = socket.recv(&mut buf).await?;
+    buf.truncate(length);
+    Ok(buf)
+}
+
+/// Exchange over a stream, the messages being prefixed with their length.
+async fn exchange_stream<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    message: &[u8],
+) -> Result<Vec<u8>> {
+    let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    stream.write_all(&frame).await?;
+    let length = stream.read_u16().await? as usize;
+    let mut buf = vec![0; length];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+async fn exchange_tls(host: &Host<String>, port: u16, message: &[u8]) -> Result<Vec<u8>> {
+    let (stream, server_name) = match host {
+        Host::Domain(domain) => (
+            TcpStream::connect((domain.as_str(), port)).await?,
+            ServerName::try_from(domain.clone())?,
+        ),
+        Host::Ipv4(ip) => (
+            TcpStream::connect((*ip, port)).await?,
+            ServerName::from(IpAddr::V4(*ip)),
+        ),
+        Host::Ipv6(ip) => (
+            TcpStream::connect((*ip, port)).await?,
+            ServerName::from(IpAddr::V6(*ip)),
+        ),
+    };
+    let config = ClientConfig::with_platform_verifier()?;
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await?;
+    let response = exchange_stream(&mut stream, message).await?;
+    let _ = stream.shutdown().await;
+    Ok(response)
+}
+
+/// Client of the DNS over HTTPS servers, itself using the system resolver.
+fn https_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new)
+}
+
+async fn exchange_https(url: &Url, message: &[u8]) -> Result<Vec<u8>> {
+    let response = https_client()
+        .post(url.clone())
+        .header(CONTENT_TYPE, DNS_MESSAGE)
+        .header(ACCEPT, DNS_MESSAGE)
+        .body(message.to_vec())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Query sent to a DNS beacon, given as `dns://SERVER[:PORT]/NAME[?type=TYPE]` like RFC 4501.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BeaconQuery {
+    pub port: u16,
+    pub name: String,
+    pub kind: u16,
+}
+
+impl BeaconQuery {
+    pub fn parse(url: &Url) -> Result<Self> {
+        if url.scheme() != "dns" || url.host().is_none() {
+            bail!(
+                "invalid DNS beacon {}, expected dns://SERVER[:PORT]/NAME",
+                url
+            );
+        }
+        let name = url.path().trim_start_matches('/');
+        if name.is_empty() {
+            bail!("missing name to query in DNS beacon {}", url);
+        }
+        let kind = match url.query_pairs().find(|(key, _)| key == "type") {
+            Some((_, kind)) => match kind.to_ascii_uppercase().as_str() {
+                "A" => 1,
+                "NS" => 2,
+                "CNAME" => 5,
+                "SOA" => 6,
+                "TXT" => 16,
+                "AAAA" => 28,
+                _ => bail!("unsupported query type {} in DNS beacon {}", kind, url),
+            },
+            None => 1,
+        };
+        Ok(BeaconQuery {
+            port: url.port().unwrap_or(53),
+            name: name.to_string(),
+            kind,
+        })
+    }
+}
+
+/// Send the query of a DNS beacon over UDP, returning the response code:
+/// any response makes the beacon reachable, whatever its code.
+#[tracing::instrument(level = "debug", name = "dns", skip_all, fields(url = %url))]
+pub async fn query_beacon(url: &Url, timeout: Duration, connect: &Connect) -> Result<String> {
+    let beacon = BeaconQuery::parse(url)?;
+    let exchange = async {
+        let addr = SocketAddr::new(connect.address(url).await?, beacon.port);
+        let socket = UdpSocket::from_std(connect.udp(addr).await?)?;
+        socket.connect(addr).await?;
+        let id = rand::random();
+        socket.send(&query(id, &beacon.name, beacon.kind)?).await?;
+        let mut buf = vec![0; UDP_SIZE];
+        loop {
+            let length = socket.recv(&mut buf).await?;
+            let mut reader = Reader {
+                message: &buf[..length],
+                position: 0,
+            };
+            // Stray responses are ignored
+            if reader.u16()? == id {
+                return Ok(rcode(reader.u16()? & 0x000f));
+            }
+        }
+    };
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out"))?
+}
+
+impl NameServer {
+    /// Addresses of a family for a name.
+    async fn lookup_family(&self, name: &str, family: AddressFamily) -> Result<Vec<IpAddr>> {
+        // DNS over HTTPS queries use a zero ID to be cache friendly
+        let id = match self {
+            NameServer::Https(_) => 0,
+            _ => rand::random(),
+        };
+        let kind = record_type(family);
+        let message = query(id, name, kind)?;
+        let response = match self {
+            NameServer::Udp(addr) => {
+                if let Some(addrs) = parse(id, kind, &exchange_udp(*addr, &message).await?)? {
+                    return Ok(addrs);
+                }
+                exchange_stream(&mut TcpStream::connect(addr).await?, &message).await?
+            }
+            NameServer::Tls { host, port } => exchange_tls(host, *port, &message).await?,
+            NameServer::Https(url) => exchange_https(url, &message).await?,
+        };
+        parse(id, kind, &response)?.context("truncated DNS response")
+    }
+
+    /// Addresses of a name, of the given family or else of both, IPv6 first.
+    pub async fn lookup(
+        &self,
+        name: &str,
+        family: Option<AddressFamily>,
+        timeout: Duration,
+    ) -> Result<Vec<IpAddr>> {
+        let lookup = async {
+            match family {
+                Some(family) => self.lookup_family(name, family).await,
+                None => {
+                    let (ipv6, ipv4) = tokio::join!(
+                        self.lookup_family(name, AddressFamily::Ipv6),
+                        self.lookup_family(name, AddressFamily::Ipv4)
+                    );
+                    match (ipv6, ipv4) {
+                        (Err(e), Err(_)) => Err(e),
+                        (ipv6, ipv4) => Ok(ipv6
+                            .unwrap_or_default()
+                            .into_iter()
+                            .chain(ipv4.unwrap_or_default())
+                            .collect()),
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(timeout, lookup)
+            .await
+            .map_err(|_| anyhow::anyhow!("timed out"))
+            .and_then(|result| result)
+            .with_context(|| format!("failed to resolve {} with {}", name, self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response to a query of `example.com`, with its answers.
+    fn response(id: u16, flags: u16, answers: &[&[u8]]) -> Vec<u8> {
+        let mut message = query(id, "example.com", 1).unwrap();
+        message[2..4].copy_from_slice(&flags.to_be_bytes());
+        message[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+        for answer in answers {
+            message.extend_from_slice(answer);
+        }
+        message
+    }
+
+    /// Record of a type for the name of the question, compressed as a pointer to it.
+    fn record(kind: u16, data: &[u8]) -> Vec<u8> {
+        let mut record = vec![0xc0, 12];
+        record.extend_from_slice(&kind.to_be_bytes());
+        record.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+        record.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        record.extend_from_slice(data);
+        record
+    }
+
+    #[test]
+    fn encode_query() {
+        let message = query(0x1234, "example.com.", 28).unwrap();
+        assert_eq!(&message[..12], &[0x12, 0x34, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&message[12..], b"\x07example\x03com\x00\x00\x1c\x00\x01");
+        assert!(query(1, "example..com", 1).is_err());
+        assert!(query(1, &format!("{}.com", "a".repeat(64)), 1).is_err());
+    }
+
+    #[test]
+    fn parse_compressed_answers() {
+        // A CNAME chain with an uncompressed target, then the address record
+        let cname = record(5, b"\x03www\x07example\x03net\x00");
+        let mut address = b"\x03www\xc0\x0c".to_vec();
+        address.extend_from_slice(&record(1, &[192, 0, 2, 1])[2..]);
+        let message = response(7, 0x8180, &[&cname, &address]);
+        assert_eq!(
+            parse(7, 1, &message).unwrap(),
+            Some(vec!["192.0.2.1".parse().unwrap()])
+        );
+
+        let address = record(28, &Ipv6Addr::LOCALHOST.octets());
+        let message = response(7, 0x8180, &[&address]);
+        assert_eq!(
+            parse(7, 28, &message).unwrap(),
+            Some(vec![IpAddr::V6(Ipv6Addr::LOCALHOST)])
+        );
+    }
+
+    #[test]
+    fn parse_truncated() {
+        // Truncation flag, to be retried over TCP
+        let message = response(7, 0x8380, &[]);
+        assert_eq!(parse(7, 1, &message).unwrap(), None);
+
+        let message = response(7, 0x8180, &[&record(1, &[192, 0, 2, 1])]);
+        for length in 0..message.len() {
+            assert!(parse(7, 1, &message[..length]).is_err(), "{} bytes", length);
+        }
+    }
+
+    #[test]
+    fn parse_malformed() {
+        let address = record(1, &[192, 0, 2, 1]);
+        assert!(parse(8, 1, &response(7, 0x8180, &[&address])).is_err());
+        // A query rather than a response
+        assert!(parse(7, 1, &response(7, 0x0100, &[&address])).is_err());
+        // More answers than records
+        let mut message = response(7, 0x8180, &[&address]);
+        message[7] = 2;
+        assert!(parse(7, 1, &message).is_err());
+        // Address of the wrong length
+        let message = response(7, 0x8180, &[&record(1, &[192, 0, 2])]);
+        assert!(parse(7, 1, &message).is_err());
+        let message = response(7, 0x8180, &[&record(1, &Ipv6Addr::LOCALHOST.octets())]);
+        assert!(parse(7, 1, &message).is_err());
+        // Label running past the end of the message
+        let message = response(7, 0x8180, &[b"\x3fexample"]);
+        assert!(parse(7, 1, &message).is_err());
+
+        let error = parse(7, 1, &response(7, 0x8183, &[])).unwrap_err();
+        assert_eq!(error.to_string(), "NXDOMAIN");
+    }
+
+    #[test]
+    fn parse_name_servers() {
+        assert_eq!(
+            "9.9.9.9".parse(),
+            Ok(NameServer::Udp("9.9.9.9:53".parse().unwrap()))
+        );
+        assert_eq!(
+            "[2620:fe::fe]:5353".parse(),
+            Ok(NameServer::Udp("[2620:fe::fe]:5353".parse().unwrap()))
+        );
+        assert_eq!(
+            "tls://dns.quad9.net".parse(),
+            Ok(NameServer::Tls {
+                host: Host::Domain("dns.quad9.net".to_string()),
+                port: 853
+            })
+        );
+        assert!(matches!(
+            "https://dns.quad9.net/dns-query".parse(),
+            Ok(NameServer::Https(_))
+        ));
+        assert!("dns.quad9.net".parse::<NameServer>().is_err());
+        assert!("tls://dns.quad9.net/path".parse::<NameServer>().is_err());
+    }
+
+    #[test]
+    fn parse_beacon_query() {
+        let url = Url::parse("dns://192.0.2.1:5353/invalid.example?type=aaaa").unwrap();
+        assert_eq!(
+            BeaconQuery::parse(&url).unwrap(),
+            BeaconQuery {
+                port: 5353,
+                name: "invalid.example".to_string(),
+                kind: 28
+            }
+        );
+        assert!(BeaconQuery::parse(&Url::parse("dns://192.0.2.1/").unwrap()).is_err());
+        assert!(
+            BeaconQuery::parse(&Url::parse("dns://192.0.2.1/a.example?type=MX").unwrap()).is_err()
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{de, Deserialize, Deserializer, Serializer};
+use std::time::Duration;
+
+/// Parse a duration such as `500ms`, `2s` or `5m`, or a bare number of `unit`s.
+fn parse(s: &str, unit: Duration) -> Result<Duration, String> {
+    if let Ok(count) = s.parse::<u32>() {
+        return Ok(unit * count);
+    }
+    humantime::parse_duration(s).map_err(|e| format!("invalid duration `{}`: {}", s, e))
+}
+
+/// Parse a positive duration, a bare number being a number of seconds.
+pub fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let duration = parse(s, Duration::from_secs(1))?;
+    if duration.is_zero() {
+        return Err(format!("invalid duration `{}`: must be positive", s));
+    }
+    Ok(duration)
+}
+
+/// Parse a duration, a bare number being a number of milliseconds.
+pub fn parse_millis(s: &str) -> Result<Duration, String> {
+    parse(s, Duration::from_millis(1))
+}
+
+fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.collect_str(&humantime::format_duration(*duration)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    parse: fn(&str) -> Result<Duration, String>,
+) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(u32),
+        String(String),
+    }
+
+    let s = match Option::<Value>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Value::Number(number)) => number.to_string(),
+        Some(Value::String(s)) => s,
+    };
+    parse(&s).map(Some).map_err(de::Error::custom)
+}
+
+/// Optional positive duration, as a string such as `2s` or a number of seconds.
+pub mod seconds {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize(duration, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        super::deserialize(deserializer, parse_seconds)
+    }
+}
+
+/// Optional duration, as a string such as `500ms` or a number of milliseconds.
+pub mod millis {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize(duration, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        super::deserialize(deserializer, parse_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Durations {
+        #[serde(default, with = "seconds")]
+        interval: Option<Duration>,
+        #[serde(default, with = "millis")]
+        timeout: Option<Duration>,
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_seconds("2"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_seconds("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_seconds("1m 30s"), Ok(Duration::from_secs(90)));
+        assert!(parse_seconds("0").is_err());
+        assert!(parse_seconds("0s").is_err());
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("2 fortnights").is_err());
+        assert_eq!(parse_millis("250"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_millis("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_millis("2s"), Ok(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn deserialize_durations() {
+        let durations: Durations = toml::from_str("interval = 60\ntimeout = 500").unwrap();
+        assert_eq!(durations.interval, Some(Duration::from_secs(60)));
+        assert_eq!(durations.timeout, Some(Duration::from_millis(500)));
+        let durations: Durations = toml::from_str("interval = \"5m\"").unwrap();
+        assert_eq!(durations.interval, Some(Duration::from_secs(300)));
+        assert_eq!(durations.timeout, None);
+        assert!(toml::from_str::<Durations>("interval = \"0s\"").is_err());
+        assert!(toml::from_str::<Durations>("interval = true").is_err());
+    }
+
+    #[test]
+    fn serialize_durations() {
+        let durations = Durations {
+            interval: Some(Duration::from_secs(90)),
+            timeout: None,
+        };
+        let toml = toml::to_string(&durations).unwrap();
+        assert_eq!(toml, "interval = \"1m 30s\"\n");
+        let durations: Durations = toml::from_str(&toml).unwrap();
+        assert_eq!(durations.interval, Some(Duration::from_secs(90)));
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use reqwest::Client;
+use tracing::error;
+
+use crate::checker::RovResult;
+
+pub mod clickhouse;
+pub mod file;
+pub mod influx;
+pub mod kafka;
+pub mod mqtt;
+pub mod nats;
+pub mod otlp;
+pub mod pushgateway;
+pub mod statsd;
+pub mod zabbix;
+
+pub use clickhouse::Clickhouse;
+pub use file::JsonFile;
+pub use influx::Influx;
+pub use kafka::Kafka;
+pub use mqtt::Mqtt;
+pub use nats::Nats;
+pub use otlp::Otlp;
+pub use pushgateway::Pushgateway;
+pub use statsd::{Statsd, StatsdFormat};
+pub use zabbix::Zabbix;
+
+/// A destination of the check results, pushed after each check.
+#[derive(Clone, Debug)]
+pub enum Exporter {
+    Zabbix(Zabbix),
+    Statsd(Statsd),
+    Influx(Influx),
+    Otlp(Otlp),
+    Pushgateway(Pushgateway),
+    Kafka(Kafka),
+    Clickhouse(Clickhouse),
+    Nats(Nats),
+    Mqtt(Mqtt),
+    JsonFile(JsonFile),
+}
+
+impl Exporter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Exporter::Zabbix(_) => "zabbix",
+            Exporter::Statsd(_) => "statsd",
+            Exporter::Influx(_) => "influx",
+            Exporter::Otlp(_) => "otlp",
+            Exporter::Pushgateway(_) => "pushgateway",
+            Exporter::Kafka(_) => "kafka",
+            Exporter::Clickhouse(_) => "clickhouse",
+            Exporter::Nats(_) => "nats",
+            Exporter::Mqtt(_) => "mqtt",
+            Exporter::JsonFile(_) => "file",
+        }
+    }
+
+    pub async fn export(&self, client: &Client, result: &RovResult) -> Result<()> {
+        match self {
+            Exporter::Zabbix(zabbix) => zabbix.send(result).await,
+            Exporter::Statsd(statsd) => statsd.send(result).await,
+            Exporter::Influx(influx) => influx.send(client, result).await,
+            Exporter::Otlp(otlp) => otlp.record(result),
+            Exporter::Pushgateway(pushgateway) => pushgateway.send(client, result).await,
+            Exporter::Kafka(kafka) => kafka.send(result).await,
+            Exporter::Clickhouse(clickhouse) => clickhouse.send(client, result).await,
+            Exporter::Nats(nats) => nats.send(result).await,
+            Exporter::Mqtt(mqtt) => mqtt.send(result).await,
+            Exporter::JsonFile(file) => file.send(result),
+        }
+    }
+
+    /// Send the results batched by the exporter.
+    pub async fn flush(&self, client: &Client) -> Result<()> {
+        match self {
+            Exporter::Clickhouse(clickhouse) => clickhouse.flush(client).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Push a check result to all the exporters, logging their failures.
+pub async fn export(client: &Client, exporters: &[Exporter], result: &RovResult) {
+    for exporter in exporters {
+        if let Err(e) = exporter.export(client, result).await {
+            error!("Failed to export to {}: {:#}", exporter.name(), e);
+        }
+    }
+}
+
+/// Flush the results batched by the exporters, logging their failures.
+pub async fn flush(client: &Client, exporters: &[Exporter]) {
+    for exporter in exporters {
+        if let Err(e) = exporter.flush(client).await {
+            error!("Failed to export to {}: {:#}", exporter.name(), e);
+        }
+    }
+}
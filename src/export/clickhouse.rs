@@ -0,0 +1,102 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+/// ClickHouse server, receiving the check results through its HTTP interface.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Clickhouse {
+    /// URL of the HTTP interface, optionally with the credentials and `database` parameter
+    pub url: Url,
+    #[serde(default = "default_table")]
+    pub table: String,
+    /// Number of results inserted at once in daemon mode
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Results waiting to be inserted
+    #[serde(skip)]
+    batch: Arc<Mutex<Vec<Row>>>,
+}
+
+fn default_table() -> String {
+    "rovcheck".to_string()
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Row {
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    verdict: Verdict,
+    reason: String,
+    asn: Option<u32>,
+    /// Full result, as JSON
+    result: String,
+}
+
+impl Clickhouse {
+    pub fn new(url: Url, table: String) -> Self {
+        Clickhouse {
+            url,
+            table,
+            batch_size: default_batch_size(),
+            batch: Arc::default(),
+        }
+    }
+
+    pub async fn send(&self, client: &Client, result: &RovResult) -> Result<()> {
+        let full = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.push(Row {
+                started_at: result.started_at,
+                finished_at: result.finished_at,
+                verdict: result.verdict,
+                reason: result.reason.clone(),
+                asn: result.asn(),
+                result: serde_json::to_string(result)?,
+            });
+            batch.len() >= self.batch_size
+        };
+        if full {
+            self.flush(client).await?;
+        }
+        Ok(())
+    }
+
+    /// Insert the pending results.
+    pub async fn flush(&self, client: &Client) -> Result<()> {
+        let rows = std::mem::take(&mut *self.batch.lock().unwrap());
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut body = String::new();
+        for row in &rows {
+            body.push_str(&serde_json::to_string(row)?);
+            body.push('\n');
+        }
+        let mut url = self.url.clone();
+        url.query_pairs_mut()
+            .append_pair(
+                "query",
+                &format!("INSERT INTO {} FORMAT JSONEachRow", self.table),
+            )
+            .append_pair("date_time_input_format", "best_effort");
+
+        let response = client.post(url).body(body).send().await?;
+        if let Err(e) = response.error_for_status_ref() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(anyhow::Error::from(e).context(message.trim().to_string()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::checker::RovResult;
+
+/// File to which the check results are appended as JSON Lines.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonFile {
+    pub path: PathBuf,
+    /// Rotate the file once it reaches this size, in bytes
+    pub max_size: Option<u64>,
+    /// Number of rotated files to keep (`<path>.1` being the most recent)
+    #[serde(default = "default_keep")]
+    pub keep: u32,
+}
+
+fn default_keep() -> u32 {
+    5
+}
+
+fn rotated(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+impl JsonFile {
+    pub fn new(path: PathBuf, max_size: Option<u64>) -> Self {
+        JsonFile {
+            path,
+            max_size,
+            keep: default_keep(),
+        }
+    }
+
+    fn rotate(&self) -> Result<()> {
+        if self.keep == 0 {
+            return Ok(fs::remove_file(&self.path)?);
+        }
+        for index in (1..self.keep).rev() {
+            let from = rotated(&self.path, index);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, index + 1))?;
+            }
+        }
+        Ok(fs::rename(&self.path, rotated(&self.path, 1))?)
+    }
+
+    pub fn send(&self, result: &RovResult) -> Result<()> {
+        if let (Some(max_size), Ok(metadata)) = (self.max_size, fs::metadata(&self.path)) {
+            if metadata.len() >= max_size {
+                self.rotate()
+                    .with_context(|| format!("failed to rotate {}", self.path.display()))?;
+            }
+        }
+
+        let mut line = serde_json::to_vec(result)?;
+        line.push(b'\n');
+        // A single write on a file opened in append mode, so that lines are never interleaved
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .with_context(|| format!("failed to write to {}", self.path.display()))
+    }
+}
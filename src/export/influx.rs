@@ -0,0 +1,36 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::checker::RovResult;
+use crate::output::influx;
+
+/// InfluxDB v2 server, receiving the results through its HTTP write API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Influx {
+    /// Base URL of the InfluxDB server
+    pub url: Url,
+    pub org: String,
+    pub bucket: String,
+    /// API token with write permission on the bucket
+    pub token: Option<String>,
+}
+
+impl Influx {
+    pub async fn send(&self, client: &Client, result: &RovResult) -> Result<()> {
+        let mut url = self.url.join("api/v2/write")?;
+        url.query_pairs_mut()
+            .append_pair("org", &self.org)
+            .append_pair("bucket", &self.bucket)
+            .append_pair("precision", "ns");
+
+        let mut request = client.post(url).body(influx::lines(result).join("\n"));
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
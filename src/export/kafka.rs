@@ -0,0 +1,104 @@
+use anyhow::Result;
+use chrono::Utc;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::{ClientBuilder, Credentials, SaslConfig};
+use rskafka::record::Record;
+use rskafka::BackoffConfig;
+use rustls_platform_verifier::ConfigVerifierExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio_rustls::rustls::ClientConfig;
+
+use crate::checker::RovResult;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SaslMechanism {
+    #[default]
+    Plain,
+    ScramSha256,
+    ScramSha512,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaSasl {
+    #[serde(default)]
+    pub mechanism: SaslMechanism,
+    pub username: String,
+    pub password: String,
+}
+
+/// Kafka cluster, receiving the check results as JSON messages.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Kafka {
+    /// Bootstrap brokers, as `host:port`
+    pub brokers: Vec<String>,
+    pub topic: String,
+    #[serde(default)]
+    pub partition: i32,
+    /// Connect to the brokers over TLS
+    #[serde(default)]
+    pub tls: bool,
+    pub sasl: Option<KafkaSasl>,
+    /// Connection to the partition leader, kept across checks
+    #[serde(skip)]
+    client: Arc<OnceCell<PartitionClient>>,
+}
+
+impl Kafka {
+    pub fn new(brokers: Vec<String>, topic: String) -> Self {
+        Kafka {
+            brokers,
+            topic,
+            partition: 0,
+            tls: false,
+            sasl: None,
+            client: Arc::default(),
+        }
+    }
+
+    async fn connect(&self) -> Result<PartitionClient> {
+        // Give up on unreachable brokers instead of retrying forever, not to block the checks
+        let backoff = BackoffConfig {
+            deadline: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let mut builder = ClientBuilder::new(self.brokers.clone())
+            .client_id(env!("CARGO_PKG_NAME"))
+            .backoff_config(backoff);
+        if self.tls {
+            builder = builder.tls_config(Arc::new(ClientConfig::with_platform_verifier()?));
+        }
+        if let Some(sasl) = &self.sasl {
+            let credentials = Credentials::new(sasl.username.clone(), sasl.password.clone());
+            builder = builder.sasl_config(match sasl.mechanism {
+                SaslMechanism::Plain => SaslConfig::Plain(credentials),
+                SaslMechanism::ScramSha256 => SaslConfig::ScramSha256(credentials),
+                SaslMechanism::ScramSha512 => SaslConfig::ScramSha512(credentials),
+            });
+        }
+        let client = builder.build().await?;
+        Ok(client
+            .partition_client(&self.topic, self.partition, UnknownTopicHandling::Error)
+            .await?)
+    }
+
+    pub async fn send(&self, result: &RovResult) -> Result<()> {
+        let client = self.client.get_or_try_init(|| self.connect()).await?;
+        let record = Record {
+            key: None,
+            value: Some(serde_json::to_vec(result)?),
+            headers: BTreeMap::new(),
+            timestamp: Utc::now(),
+        };
+        client
+            .produce(vec![record], Compression::NoCompression)
+            .await?;
+        Ok(())
+    }
+}
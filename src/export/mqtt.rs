@@ -0,0 +1,122 @@
+use anyhow::{bail, Context, Result};
+use rumqttc::{
+    AsyncClient, Event, MqttOptions, Outgoing, Packet, QoS, TlsConfiguration, Transport,
+};
+use rustls_platform_verifier::ConfigVerifierExt;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::rustls::ClientConfig;
+use url::Url;
+
+use crate::checker::RovResult;
+
+/// MQTT broker, receiving the verdict and the JSON check results.
+///
+/// The verdict is published (retained) on `<topic>/verdict`, and the result on `<topic>/result`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mqtt {
+    /// Broker URL, `mqtt://host[:port]` or `mqtts://host[:port]` for TLS
+    pub broker: Url,
+    #[serde(default = "default_topic")]
+    pub topic: String,
+    /// Quality of service of the messages (0, 1 or 2)
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+fn default_topic() -> String {
+    "rovcheck".to_string()
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+impl Mqtt {
+    pub fn new(broker: Url, topic: String, qos: u8) -> Self {
+        Mqtt {
+            broker,
+            topic,
+            qos,
+            username: None,
+            password: None,
+        }
+    }
+
+    fn options(&self) -> Result<MqttOptions> {
+        let host = self
+            .broker
+            .host_str()
+            .context("MQTT broker URL without host")?;
+        let tls = match self.broker.scheme() {
+            "mqtt" | "tcp" => false,
+            "mqtts" | "ssl" => true,
+            scheme => bail!("unsupported MQTT broker scheme `{}`", scheme),
+        };
+        let port = self.broker.port().unwrap_or(if tls { 8883 } else { 1883 });
+
+        let id = format!("{}-{}", env!("CARGO_PKG_NAME"), nanoid::nanoid!(8));
+        let mut options = MqttOptions::new(id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if tls {
+            let config = ClientConfig::with_platform_verifier()?;
+            options.set_transport(Transport::Tls(TlsConfiguration::Rustls(Arc::new(config))));
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            options.set_credentials(username, password);
+        }
+        Ok(options)
+    }
+
+    pub async fn send(&self, result: &RovResult) -> Result<()> {
+        let qos = match self.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            qos => bail!("invalid MQTT QoS {}", qos),
+        };
+        let (client, mut eventloop) = AsyncClient::new(self.options()?, 10);
+
+        let messages = [
+            (
+                format!("{}/verdict", self.topic),
+                true,
+                result.verdict.to_string().into_bytes(),
+            ),
+            (
+                format!("{}/result", self.topic),
+                false,
+                serde_json::to_vec(result)?,
+            ),
+        ];
+        let count = messages.len();
+        for (topic, retain, payload) in messages {
+            client.publish(topic, qos, retain, payload).await?;
+        }
+
+        // Drive the connection until the messages are sent (and acknowledged with QoS > 0)
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let mut done = 0;
+            while done < count {
+                match eventloop.poll().await? {
+                    Event::Outgoing(Outgoing::Publish(_)) if qos == QoS::AtMostOnce => done += 1,
+                    Event::Incoming(Packet::PubAck(_)) if qos == QoS::AtLeastOnce => done += 1,
+                    Event::Incoming(Packet::PubComp(_)) if qos == QoS::ExactlyOnce => done += 1,
+                    _ => {}
+                }
+            }
+            client.disconnect().await?;
+            while !matches!(
+                eventloop.poll().await?,
+                Event::Outgoing(Outgoing::Disconnect)
+            ) {}
+            anyhow::Ok(())
+        })
+        .await
+        .context("MQTT publish timed out")?
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use async_nats::{Client, ConnectOptions};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+use crate::checker::RovResult;
+
+/// NATS server, receiving the check results as JSON messages published on a subject.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Nats {
+    /// Server URL, e.g. `nats://localhost:4222`
+    pub url: String,
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    /// Publish to a JetStream stream, and wait for its acknowledgement
+    #[serde(default)]
+    pub jetstream: bool,
+    /// Path to a credentials file
+    pub credentials: Option<PathBuf>,
+    /// Connection to the server, kept across checks
+    #[serde(skip)]
+    client: Arc<OnceCell<Client>>,
+}
+
+fn default_subject() -> String {
+    "rovcheck.results".to_string()
+}
+
+impl Nats {
+    pub fn new(url: String, subject: String, jetstream: bool) -> Self {
+        Nats {
+            url,
+            subject,
+            jetstream,
+            credentials: None,
+            client: Arc::default(),
+        }
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let options = match &self.credentials {
+            Some(path) => ConnectOptions::with_credentials_file(path).await?,
+            None => ConnectOptions::new(),
+        };
+        Ok(options
+            .name(env!("CARGO_PKG_NAME"))
+            .connect(&self.url)
+            .await?)
+    }
+
+    pub async fn send(&self, result: &RovResult) -> Result<()> {
+        let client = self.client.get_or_try_init(|| self.connect()).await?;
+        let payload = serde_json::to_vec(result)?;
+        if self.jetstream {
+            async_nats::jetstream::new(client.clone())
+                .publish(self.subject.clone(), payload.into())
+                .await?
+                .await?;
+        } else {
+            client.publish(self.subject.clone(), payload.into()).await?;
+            client.flush().await?;
+        }
+        Ok(())
+    }
+}
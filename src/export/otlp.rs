@@ -0,0 +1,80 @@
+use anyhow::Result;
+use opentelemetry::metrics::{Counter, Gauge, Histogram};
+use opentelemetry::{global, KeyValue};
+
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+/// OpenTelemetry metrics of the check results, exported by the global meter provider.
+#[derive(Clone, Debug)]
+pub struct Otlp {
+    enforced: Gauge<u64>,
+    checks: Counter<u64>,
+    reachable: Gauge<u64>,
+    duration: Histogram<f64>,
+}
+
+impl Default for Otlp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Otlp {
+    pub fn new() -> Self {
+        let meter = global::meter(env!("CARGO_PKG_NAME"));
+        Otlp {
+            enforced: meter
+                .u64_gauge("rovcheck.rov_enforced")
+                .with_description("Whether ROV is enforced (1) or not (0)")
+                .build(),
+            checks: meter
+                .u64_counter("rovcheck.checks")
+                .with_description("Number of checks run, by verdict")
+                .build(),
+            reachable: meter
+                .u64_gauge("rovcheck.beacon.reachable")
+                .with_description("Whether the beacon was reachable (1) or not (0)")
+                .build(),
+            duration: meter
+                .f64_histogram("rovcheck.request.duration")
+                .with_description("Duration of the beacon requests")
+                .with_unit("s")
+                .build(),
+        }
+    }
+
+    pub fn record(&self, result: &RovResult) -> Result<()> {
+        let labels = result
+            .labels
+            .iter()
+            .map(|(name, value)| KeyValue::new(name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        let enforced = (result.verdict == Verdict::Enforced) as u64;
+        self.enforced.record(enforced, &labels);
+        let mut attributes = labels.clone();
+        attributes.push(KeyValue::new("verdict", result.verdict.to_string()));
+        self.checks.add(1, &attributes);
+
+        for pair in &result.pairs {
+            for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                let mut attributes = labels.clone();
+                attributes.push(KeyValue::new("beacon", pair.name.clone()));
+                attributes.push(KeyValue::new("validity", validity));
+                if let Some(family) = pair.family {
+                    attributes.push(KeyValue::new("family", family.to_string()));
+                }
+                if let Some(uplink) = &pair.uplink {
+                    attributes.push(KeyValue::new("uplink", uplink.clone()));
+                }
+                if pair.http3 {
+                    attributes.push(KeyValue::new("protocol", "http3"));
+                }
+                self.reachable.record(beacon.success as u64, &attributes);
+                self.duration
+                    .record(beacon.duration().as_secs_f64(), &attributes);
+            }
+        }
+        Ok(())
+    }
+}
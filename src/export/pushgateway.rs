@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Context, Result};
+use metrics_exporter_prometheus::PrometheusRecorder;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+use url::Url;
+
+use crate::checker::RovResult;
+use crate::prometheus;
+
+/// Prometheus Pushgateway, receiving the metrics of the check results.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pushgateway {
+    /// Base URL of the Pushgateway
+    pub url: Url,
+    #[serde(default = "default_job")]
+    pub job: String,
+    /// Labels of the grouping key, besides the job (e.g. `instance`)
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Recorder of the pushed metrics, kept across checks so that counters accumulate
+    #[serde(skip)]
+    recorder: Arc<OnceLock<PrometheusRecorder>>,
+}
+
+fn default_job() -> String {
+    "rovcheck".to_string()
+}
+
+impl Pushgateway {
+    pub fn new(url: Url, job: String, labels: BTreeMap<String, String>) -> Self {
+        Pushgateway {
+            url,
+            job,
+            labels,
+            recorder: Arc::default(),
+        }
+    }
+
+    pub async fn send(&self, client: &Client, result: &RovResult) -> Result<()> {
+        if self.recorder.get().is_none() {
+            // Only fails if another check set the recorder first, which is then used
+            let _ = self.recorder.set(prometheus::recorder()?);
+        }
+        let recorder = self.recorder.get().context("missing Prometheus recorder")?;
+        metrics::with_local_recorder(recorder, || prometheus::record(result));
+        let body = recorder.handle().render();
+
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("invalid Pushgateway URL {}", self.url))?
+            .pop_if_empty()
+            .extend(["metrics", "job", &self.job]);
+        for (name, value) in &self.labels {
+            url.path_segments_mut()
+                .map_err(|_| anyhow!("invalid Pushgateway URL {}", self.url))?
+                .extend([name, value]);
+        }
+
+        client
+            .put(url)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tokio::net::{lookup_host, UdpSocket};
+
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsdFormat {
+    #[default]
+    Statsd,
+    /// Graphite plaintext protocol
+    Graphite,
+}
+
+impl FromStr for StatsdFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "statsd" => Ok(StatsdFormat::Statsd),
+            "graphite" => Ok(StatsdFormat::Graphite),
+            _ => bail!("unknown StatsD format `{}`", s),
+        }
+    }
+}
+
+/// StatsD or Graphite server, receiving the metrics over UDP.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Statsd {
+    /// Server address, as `host:port`
+    pub address: String,
+    #[serde(default)]
+    pub format: StatsdFormat,
+    /// Prefix of the metric names
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+}
+
+fn default_prefix() -> String {
+    "rovcheck".to_string()
+}
+
+/// Component of a metric path, without the separators of the protocols.
+fn component(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+enum Kind {
+    Counter,
+    Gauge,
+    Timer,
+}
+
+impl Statsd {
+    pub fn new(address: String, format: StatsdFormat) -> Self {
+        Statsd {
+            address,
+            format,
+            prefix: default_prefix(),
+        }
+    }
+
+    fn metrics(&self, result: &RovResult) -> Vec<(String, f64, Kind)> {
+        let enforced = (result.verdict == Verdict::Enforced) as u8;
+        let mut metrics = vec![
+            ("checks".to_string(), 1.0, Kind::Counter),
+            ("enforced".to_string(), enforced.into(), Kind::Gauge),
+        ];
+        for pair in &result.pairs {
+            for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                let name = component(&pair.name);
+                let mut path = match pair.family {
+                    Some(family) => format!("beacon.{}.{}.{}", name, validity, family),
+                    None => format!("beacon.{}.{}", name, validity),
+                };
+                if let Some(uplink) = &pair.uplink {
+                    path.push_str(&format!(".{}", component(uplink)));
+                }
+                if pair.http3 {
+                    path.push_str(".http3");
+                }
+                metrics.push((
+                    format!("{}.reachable", path),
+                    (beacon.success as u8).into(),
+                    Kind::Gauge,
+                ));
+                metrics.push((
+                    format!("{}.duration", path),
+                    beacon.duration().as_secs_f64() * 1000.0,
+                    Kind::Timer,
+                ));
+            }
+        }
+        metrics
+    }
+
+    fn lines(&self, result: &RovResult) -> Vec<String> {
+        let timestamp = result.finished_at.timestamp();
+        self.metrics(result)
+            .into_iter()
+            .map(|(name, value, kind)| match self.format {
+                StatsdFormat::Statsd => {
+                    let kind = match kind {
+                        Kind::Counter => "c",
+                        Kind::Gauge => "g",
+                        Kind::Timer => "ms",
+                    };
+                    format!("{}.{}:{}|{}", self.prefix, name, value, kind)
+                }
+                StatsdFormat::Graphite => {
+                    format!("{}.{} {} {}", self.prefix, name, value, timestamp)
+                }
+            })
+            .collect()
+    }
+
+    pub async fn send(&self, result: &RovResult) -> Result<()> {
+        let address = lookup_host(&self.address)
+            .await?
+            .next()
+            .with_context(|| format!("failed to resolve {}", self.address))?;
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => "0.0.0.0:0".parse()?,
+            SocketAddr::V6(_) => "[::]:0".parse()?,
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(address).await?;
+        // One datagram per metric, to stay below the MTU
+        for line in self.lines(result) {
+            socket.send(format!("{}\n", line).as_bytes()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+
+    #[test]
+    fn statsd_lines() {
+        let statsd = Statsd::new("localhost:8125".to_string(), StatsdFormat::Statsd);
+        assert_eq!(
+            statsd.lines(&result()),
+            [
+                "rovcheck.checks:1|c",
+                "rovcheck.enforced:1|g",
+                "rovcheck.beacon.cloudflare.valid.ipv4.reachable:1|g",
+                "rovcheck.beacon.cloudflare.valid.ipv4.duration:120|ms",
+                "rovcheck.beacon.cloudflare.invalid.ipv4.reachable:0|g",
+                "rovcheck.beacon.cloudflare.invalid.ipv4.duration:3000|ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn graphite_lines() {
+        let mut statsd = Statsd::new("localhost:2003".to_string(), StatsdFormat::Graphite);
+        statsd.prefix = "rov".to_string();
+        let mut result = result();
+        result.verdict = Verdict::NotEnforced;
+        let pair = &mut result.pairs[0];
+        (pair.name, pair.uplink, pair.http3) =
+            ("my beacon:1".to_string(), Some("eth0.10".to_string()), true);
+        let lines = statsd.lines(&result);
+        assert_eq!(lines[1], "rov.enforced 0 1767225603");
+        assert_eq!(
+            lines[2],
+            "rov.beacon.my_beacon_1.valid.ipv4.eth0_10.http3.reachable 1 1767225603"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+const HEADER: &[u8] = b"ZBXD\x01";
+const DEFAULT_PORT: u16 = 10051;
+
+/// Zabbix server or proxy, receiving the results as trapper items through the sender protocol.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Zabbix {
+    /// Zabbix server or proxy address, as `host[:port]`
+    pub server: String,
+    /// Name of the monitored host in Zabbix
+    pub host: String,
+}
+
+#[derive(Serialize)]
+struct Item<'a> {
+    host: &'a str,
+    key: String,
+    value: String,
+}
+
+/// Packet of the sender protocol: the header, the length of the data as 64 bits little-endian,
+/// then the data.
+fn packet(data: &[u8]) -> Vec<u8> {
+    let mut packet = HEADER.to_vec();
+    packet.extend((data.len() as u64).to_le_bytes());
+    packet.extend(data);
+    packet
+}
+
+#[derive(Deserialize)]
+struct Response {
+    response: String,
+    info: Option<String>,
+}
+
+impl Zabbix {
+    pub fn new(server: String, host: String) -> Self {
+        Zabbix { server, host }
+    }
+
+    fn items(&self, result: &RovResult) -> Vec<Item<'_>> {
+        let item = |key: String, value: String| Item {
+            host: &self.host,
+            key,
+            value,
+        };
+        let enforced = (result.verdict == Verdict::Enforced) as u8;
+        let mut items = vec![
+            item("rovcheck.verdict".to_string(), result.verdict.to_string()),
+            item("rovcheck.enforced".to_string(), enforced.to_string()),
+        ];
+        for pair in &result.pairs {
+            for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                let mut params = match pair.family {
+                    Some(family) => format!("{},{},{}", pair.name, validity, family),
+                    None => format!("{},{}", pair.name, validity),
+                };
+                if let Some(uplink) = &pair.uplink {
+                    params.push_str(&format!(",{}", uplink));
+                }
+                if pair.http3 {
+                    params.push_str(",http3");
+                }
+                items.push(item(
+                    format!("rovcheck.reachable[{}]", params),
+                    (beacon.success as u8).to_string(),
+                ));
+                items.push(item(
+                    format!("rovcheck.duration[{}]", params),
+                    format!("{:.3}", beacon.duration().as_secs_f64()),
+                ));
+            }
+        }
+        items
+    }
+
+    pub async fn send(&self, result: &RovResult) -> Result<()> {
+        let body = json!({
+            "request": "sender data",
+            "data": self.items(result),
+        })
+        .to_string();
+
+        let address = match self.server.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => self.server.clone(),
+            _ => format!("{}:{}", self.server, DEFAULT_PORT),
+        };
+        let response = tokio::time::timeout(Duration::from_secs(10), async {
+            let mut stream = TcpStream::connect(&address).await?;
+            stream.write_all(&packet(body.as_bytes())).await?;
+
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).await?;
+            anyhow::Ok(response)
+        })
+        .await
+        .context("Zabbix request timed out")?
+        .with_context(|| format!("failed to send items to Zabbix {}", address))?;
+
+        if response.len() < HEADER.len() + 8 || !response.starts_with(HEADER) {
+            bail!("invalid response from Zabbix {}", address);
+        }
+        let response: Response = serde_json::from_slice(&response[HEADER.len() + 8..])?;
+        debug!(
+            "Zabbix response: {}",
+            response.info.as_deref().unwrap_or_default()
+        );
+        if response.response != "success" {
+            bail!(
+                "Zabbix {} rejected the items: {}",
+                address,
+                response.info.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn packets() {
+        let data = vec![b'x'; 0x0102];
+        let framed = packet(&data);
+        assert_eq!(&framed[..5], b"ZBXD\x01");
+        assert_eq!(&framed[5..13], [0x02, 0x01, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&framed[13..], data);
+        assert_eq!(packet(b""), b"ZBXD\x01\0\0\0\0\0\0\0\0");
+    }
+
+    /// Server reading a single packet, answering with a response.
+    async fn server(response: &'static str) -> (String, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; 13];
+            stream.read_exact(&mut header).await.unwrap();
+            let length = u64::from_le_bytes(header[5..].try_into().unwrap());
+            let mut data = vec![0u8; length as usize];
+            stream.read_exact(&mut data).await.unwrap();
+            stream
+                .write_all(&packet(response.as_bytes()))
+                .await
+                .unwrap();
+            [&header[..], &data].concat()
+        });
+        (address, received)
+    }
+
+    #[tokio::test]
+    async fn send() {
+        let (address, received) =
+            server(r#"{"response": "success", "info": "processed: 6; failed: 0"}"#).await;
+        let zabbix = Zabbix::new(address, "probe1".to_string());
+        zabbix.send(&result()).await.unwrap();
+        let received = received.await.unwrap();
+        assert_eq!(&received[..5], HEADER);
+        let data: serde_json::Value = serde_json::from_slice(&received[13..]).unwrap();
+        assert_eq!(data["request"], "sender data");
+        let items = data["data"].as_array().unwrap();
+        let item = |key: &str| {
+            let item = items.iter().find(|item| item["key"] == key).unwrap();
+            assert_eq!(item["host"], "probe1");
+            item["value"].as_str().unwrap().to_string()
+        };
+        assert_eq!(items.len(), 6);
+        assert_eq!(item("rovcheck.verdict"), "ENFORCED");
+        assert_eq!(item("rovcheck.enforced"), "1");
+        assert_eq!(item("rovcheck.reachable[cloudflare,valid,ipv4]"), "1");
+        assert_eq!(item("rovcheck.reachable[cloudflare,invalid,ipv4]"), "0");
+        assert_eq!(item("rovcheck.duration[cloudflare,valid,ipv4]"), "0.120");
+        assert_eq!(item("rovcheck.duration[cloudflare,invalid,ipv4]"), "3.000");
+    }
+
+    #[tokio::test]
+    async fn rejected() {
+        let (address, _) = server(r#"{"response": "failed", "info": "processed: 0"}"#).await;
+        let zabbix = Zabbix::new(address.clone(), "probe1".to_string());
+        let error = zabbix.send(&result()).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Zabbix {} rejected the items: processed: 0", address)
+        );
+    }
+}
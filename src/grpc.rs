@@ -0,0 +1,194 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::Body;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::codegen::{http, Body as HttpBody, BoxFuture, Service, StdError};
+use tonic::metadata::GRPC_CONTENT_TYPE;
+use tonic::server::{ClientStreamingService, Grpc, NamedService};
+use tonic::transport::Channel;
+use tonic::{Code, IntoStreamingRequest, Request, Response, Status, Streaming};
+use tonic_prost::ProstCodec;
+
+use crate::checker::RovResult;
+
+/// Name of the collector service, defined in `proto/collector.proto`.
+pub const SERVICE_NAME: &str = "rovcheck.v1.Collector";
+/// Path of the `Report` method of the collector service.
+pub const REPORT_PATH: &str = "/rovcheck.v1.Collector/Report";
+
+/// Identity of the agent reporting the results.
+#[derive(Clone, PartialEq, prost::Message, Serialize, Deserialize)]
+pub struct Probe {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Version of rovcheck
+    #[prost(string, tag = "2")]
+    #[serde(default)]
+    pub version: String,
+    #[prost(string, tag = "3")]
+    #[serde(default)]
+    pub hostname: String,
+}
+
+/// Result of a check of an agent.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CheckReport {
+    #[prost(message, optional, tag = "1")]
+    pub probe: Option<Probe>,
+    /// `ENFORCED`, `NOT_ENFORCED` or `INCONCLUSIVE`
+    #[prost(string, tag = "2")]
+    pub verdict: String,
+    #[prost(string, tag = "3")]
+    pub reason: String,
+    /// ASN seen by the beacons
+    #[prost(uint32, optional, tag = "4")]
+    pub asn: Option<u32>,
+    /// Confidence in the verdict, unless INCONCLUSIVE
+    #[prost(double, optional, tag = "5")]
+    pub confidence: Option<f64>,
+    /// End of the check, in seconds since the Unix epoch
+    #[prost(int64, tag = "6")]
+    pub timestamp: i64,
+    /// Full check result, as JSON
+    #[prost(string, tag = "7")]
+    pub result: String,
+}
+
+/// Acknowledgement of the reports of a stream.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReportAck {
+    #[prost(uint64, tag = "1")]
+    pub received: u64,
+}
+
+impl CheckReport {
+    pub fn new(probe: Probe, result: &RovResult) -> Result<Self> {
+        Ok(CheckReport {
+            probe: Some(probe),
+            verdict: result.verdict.to_string(),
+            reason: result.reason.clone(),
+            asn: result.asn(),
+            confidence: result
+                .confidence
+                .as_ref()
+                .map(|confidence| confidence.score),
+            timestamp: result.finished_at.timestamp(),
+            result: serde_json::to_string(result)?,
+        })
+    }
+}
+
+/// Client of the `rovcheck.v1.Collector` service.
+#[derive(Clone, Debug)]
+pub struct CollectorClient {
+    inner: tonic::client::Grpc<Channel>,
+}
+
+impl CollectorClient {
+    pub fn new(channel: Channel) -> Self {
+        CollectorClient {
+            inner: tonic::client::Grpc::new(channel),
+        }
+    }
+
+    /// Stream check reports to the collector, until the stream ends.
+    pub async fn report(
+        &mut self,
+        request: impl IntoStreamingRequest<Message = CheckReport>,
+    ) -> Result<Response<ReportAck>, Status> {
+        self.inner
+            .ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("collector not ready: {}", e)))?;
+        self.inner
+            .client_streaming(
+                request.into_streaming_request(),
+                PathAndQuery::from_static(REPORT_PATH),
+                ProstCodec::default(),
+            )
+            .await
+    }
+}
+
+/// Implementation of the `rovcheck.v1.Collector` service.
+#[tonic::async_trait]
+pub trait Collector: Send + Sync + 'static {
+    /// Receive the check reports streamed by an agent.
+    async fn report(
+        &self,
+        request: Request<Streaming<CheckReport>>,
+    ) -> Result<Response<ReportAck>, Status>;
+}
+
+/// Server of the `rovcheck.v1.Collector` service, routing the requests to its implementation.
+#[derive(Debug)]
+pub struct CollectorServer<T> {
+    inner: Arc<T>,
+}
+
+impl<T> CollectorServer<T> {
+    pub fn new(inner: T) -> Self {
+        CollectorServer {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<T> Clone for CollectorServer<T> {
+    fn clone(&self) -> Self {
+        CollectorServer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+struct ReportService<T>(Arc<T>);
+
+impl<T: Collector> ClientStreamingService<CheckReport> for ReportService<T> {
+    type Response = ReportAck;
+    type Future = BoxFuture<Response<ReportAck>, Status>;
+
+    fn call(&mut self, request: Request<Streaming<CheckReport>>) -> Self::Future {
+        let inner = self.0.clone();
+        Box::pin(async move { inner.report(request).await })
+    }
+}
+
+impl<T, B> Service<http::Request<B>> for CollectorServer<T>
+where
+    T: Collector,
+    B: HttpBody + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if request.uri().path() != REPORT_PATH {
+            return Box::pin(async {
+                let mut response = http::Response::new(Body::default());
+                let headers = response.headers_mut();
+                headers.insert(Status::GRPC_STATUS, (Code::Unimplemented as i32).into());
+                headers.insert(http::header::CONTENT_TYPE, GRPC_CONTENT_TYPE);
+                Ok(response)
+            });
+        }
+        let service = ReportService(self.inner.clone());
+        Box::pin(async move {
+            let mut grpc = Grpc::new(ProstCodec::default());
+            Ok(grpc.client_streaming(service, request).await)
+        })
+    }
+}
+
+impl<T> NamedService for CollectorServer<T> {
+    const NAME: &'static str = SERVICE_NAME;
+}
@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+/// Local SQLite store of the check results.
+pub struct History {
+    conn: Connection,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Entry {
+    pub id: i64,
+    pub asn: Option<u32>,
+    pub result: RovResult,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub checks: usize,
+    pub verdicts: BTreeMap<String, usize>,
+    pub changes: usize,
+    pub first_check: Option<DateTime<Utc>>,
+    pub last_check: Option<DateTime<Utc>>,
+    pub last_change: Option<DateTime<Utc>>,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open history database {}", path.display()))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS checks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL,
+                verdict TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                asn INTEGER,
+                result TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS checks_started_at ON checks (started_at);",
+        )?;
+        Ok(History { conn })
+    }
+
+    pub fn insert(&self, result: &RovResult) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO checks (started_at, finished_at, verdict, reason, asn, result)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                result.started_at.to_rfc3339(),
+                result.finished_at.to_rfc3339(),
+                result.verdict.to_string(),
+                result.reason,
+                result.asn(),
+                serde_json::to_string(result)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the most recent check result, of a profile if given.
+    pub fn last(&self, profile: Option<&str>) -> Result<Option<Entry>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT id, asn, result FROM checks
+                 WHERE ?1 IS NULL OR json_extract(result, '$.labels.profile') = ?1
+                 ORDER BY started_at DESC LIMIT 1",
+                [profile],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, Option<u32>>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        match row {
+            Some((id, asn, result)) => Ok(Some(Entry {
+                id,
+                asn,
+                result: serde_json::from_str(&result)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the check results, oldest first, optionally filtered by start time and verdict.
+    pub fn query(
+        &self,
+        since: Option<DateTime<Utc>>,
+        verdict: Option<Verdict>,
+    ) -> Result<Vec<Entry>> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, asn, result FROM checks
+             WHERE (?1 IS NULL OR started_at >= ?1) AND (?2 IS NULL OR verdict = ?2)
+             ORDER BY started_at",
+        )?;
+        let rows = statement.query_map(
+            params![
+                since.map(|since| since.to_rfc3339()),
+                verdict.map(|verdict| verdict.to_string())
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<u32>>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, asn, result) = row?;
+            entries.push(Entry {
+                id,
+                asn,
+                result: serde_json::from_str(&result)?,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+impl Summary {
+    pub fn new(entries: &[Entry]) -> Self {
+        let mut summary = Summary {
+            checks: entries.len(),
+            first_check: entries.first().map(|entry| entry.result.started_at),
+            last_check: entries.last().map(|entry| entry.result.started_at),
+            ..Default::default()
+        };
+
+        let mut last = None;
+        for entry in entries {
+            let verdict = entry.result.verdict;
+            *summary.verdicts.entry(verdict.to_string()).or_default() += 1;
+            if last.is_some_and(|last| last != verdict) {
+                summary.changes += 1;
+                summary.last_change = Some(entry.result.started_at);
+            }
+            last = Some(verdict);
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+    use chrono::Duration;
+
+    /// Result of a check started some hours after the fixture.
+    fn check(hours: i64, verdict: Verdict) -> RovResult {
+        let mut result = result();
+        result.started_at += Duration::hours(hours);
+        result.finished_at += Duration::hours(hours);
+        result.verdict = verdict;
+        result
+    }
+
+    #[test]
+    fn store_and_query() {
+        let history = History::open(Path::new(":memory:")).unwrap();
+        assert!(history.last(None).unwrap().is_none());
+        for (hours, verdict) in [
+            (0, Verdict::Enforced),
+            (1, Verdict::NotEnforced),
+            (2, Verdict::NotEnforced),
+            (3, Verdict::Enforced),
+        ] {
+            history.insert(&check(hours, verdict)).unwrap();
+        }
+
+        let entries = history.query(None, None).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].asn, Some(64496));
+        assert_eq!(entries[0].result.reason, result().reason);
+        let last = history.last(None).unwrap().unwrap();
+        assert_eq!(
+            last.result.started_at,
+            check(3, Verdict::Enforced).started_at
+        );
+        assert!(history.last(Some("office")).unwrap().is_none());
+
+        let since = Some(check(1, Verdict::Enforced).started_at);
+        let verdicts = |entries: Vec<Entry>| {
+            entries
+                .iter()
+                .map(|entry| entry.result.verdict)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            verdicts(history.query(since, None).unwrap()),
+            [
+                Verdict::NotEnforced,
+                Verdict::NotEnforced,
+                Verdict::Enforced
+            ]
+        );
+        assert_eq!(
+            verdicts(history.query(since, Some(Verdict::Enforced)).unwrap()),
+            [Verdict::Enforced]
+        );
+        assert!(history
+            .query(None, Some(Verdict::Inconclusive))
+            .unwrap()
+            .is_empty());
+
+        let summary = Summary::new(&entries);
+        assert_eq!(summary.checks, 4);
+        assert_eq!(summary.verdicts["ENFORCED"], 2);
+        assert_eq!(summary.verdicts["NOT_ENFORCED"], 2);
+        assert_eq!(summary.changes, 2);
+        assert_eq!(summary.first_check, Some(entries[0].result.started_at));
+        assert_eq!(summary.last_check, Some(entries[3].result.started_at));
+        assert_eq!(summary.last_change, summary.last_check);
+    }
+
+    #[test]
+    fn last_of_profile() {
+        let history = History::open(Path::new(":memory:")).unwrap();
+        let mut office = check(0, Verdict::Enforced);
+        office
+            .labels
+            .insert("profile".to_string(), "office".to_string());
+        history.insert(&office).unwrap();
+        history.insert(&check(1, Verdict::NotEnforced)).unwrap();
+
+        let last = history.last(Some("office")).unwrap().unwrap();
+        assert_eq!(last.result.labels["profile"], "office");
+        assert_eq!(
+            history.last(None).unwrap().unwrap().result.verdict,
+            Verdict::NotEnforced
+        );
+        assert_eq!(Summary::new(&[]).checks, 0);
+    }
+}
@@ -0,0 +1,352 @@
+use anyhow::{anyhow, bail, Context, Result};
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{ClientConfig, Endpoint, EndpointConfig, TokioRuntime, VarInt};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tracing::{debug_span, Instrument};
+use url::{Host, Url};
+
+use crate::beacon::RequestHeader;
+use crate::probe::Connect;
+
+/// Largest response body read from a beacon.
+const MAX_RESPONSE: usize = 1 << 20;
+
+const FRAME_DATA: u64 = 0x00;
+const FRAME_HEADERS: u64 = 0x01;
+const FRAME_SETTINGS: u64 = 0x04;
+const STREAM_CONTROL: u64 = 0x00;
+/// H3_NO_ERROR
+const NO_ERROR: u32 = 0x100;
+
+/// Response of a beacon over HTTP/3.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// QUIC variable-length integer (RFC 9000).
+fn put_varint(buf: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0x3f => buf.push(value as u8),
+        0x40..=0x3fff => buf.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..=0x3fff_ffff => buf.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => buf.extend_from_slice(&(value | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+fn get_varint(buf: &[u8], position: &mut usize) -> Result<u64> {
+    let first = *buf.get(*position).context("truncated HTTP/3 frame")?;
+    let length = 1 << (first >> 6);
+    let bytes = buf
+        .get(*position..*position + length)
+        .context("truncated HTTP/3 frame")?;
+    *position += length;
+    Ok(bytes[1..]
+        .iter()
+        .fold((first & 0x3f) as u64, |value, byte| {
+            value << 8 | *byte as u64
+        }))
+}
+
+/// Prefixed integer of the field lines (RFC 7541 section 5.1), the flags being set in `first`.
+fn put_integer(buf: &mut Vec<u8>, first: u8, prefix: u32, value: usize) {
+    let max = (1usize << prefix) - 1;
+    if value < max {
+        buf.push(first | value as u8);
+        return;
+    }
+    buf.push(first | max as u8);
+    let mut value = value - max;
+    while value >= 0x80 {
+        buf.push(value as u8 & 0x7f | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn get_integer(buf: &[u8], position: &mut usize, prefix: u32) -> Result<usize> {
+    let max = (1usize << prefix) - 1;
+    let byte = |position: &mut usize| {
+        let byte = *buf
+            .get(*position)
+            .context("truncated QPACK field section")?;
+        *position += 1;
+        Ok::<_, anyhow::Error>(byte)
+    };
+    let mut value = byte(position)? as usize & max;
+    if value < max {
+        return Ok(value);
+    }
+    for shift in (0..28).step_by(7) {
+        let byte = byte(position)?;
+        value += ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("QPACK integer too large")
+}
+
+/// String literal of a field line, never Huffman encoded.
+fn put_string(buf: &mut Vec<u8>, first: u8, prefix: u32, value: &str) {
+    put_integer(buf, first, prefix, value.len());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// String literal of a field line, the Huffman flag preceding the length prefix.
+fn get_string<'a>(buf: &'a [u8], position: &mut usize, prefix: u32) -> Result<(bool, &'a [u8])> {
+    let huffman = buf
+        .get(*position)
+        .context("truncated QPACK field section")?
+        & (1 << prefix)
+        != 0;
+    let length = get_integer(buf, position, prefix)?;
+    let value = buf
+        .get(*position..*position + length)
+        .context("truncated QPACK field section")?;
+    *position += length;
+    Ok((huffman, value))
+}
+
+/// Decode a Huffman encoded status code (RFC 7541 appendix B), digits being its only symbols.
+fn huffman_digits(value: &[u8]) -> Result<String> {
+    let mut digits = String::new();
+    let (mut code, mut length) = (0u32, 0);
+    for bit in value
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1))
+    {
+        code = code << 1 | bit as u32;
+        length += 1;
+        let digit = match (length, code) {
+            (5, 0..=2) => code,
+            (6, 0x19..=0x1f) => code - 0x16,
+            (5, 0x0c..=0x0f) => continue,
+            (length, _) if length < 5 => continue,
+            // Only the padding, made of the most significant bits of EOS, may be left
+            _ if code == (1 << length) - 1 => continue,
+            _ => bail!("unexpected Huffman encoded status"),
+        };
+        digits.push(char::from(b'0' + digit as u8));
+        (code, length) = (0, 0);
+    }
+    if length > 7 || code != (1 << length) - 1 {
+        bail!("invalid Huffman padding");
+    }
+    Ok(digits)
+}
+
+/// Status of the entries of the QPACK static table (RFC 9204 appendix A) naming it.
+fn static_status(index: usize) -> Option<&'static str> {
+    Some(match index {
+        24 => "103",
+        25 => "200",
+        26 => "304",
+        27 => "404",
+        28 => "503",
+        63 => "100",
+        64 => "204",
+        65 => "206",
+        66 => "302",
+        67 => "400",
+        68 => "403",
+        69 => "421",
+        70 => "425",
+        71 => "500",
+        _ => return None,
+    })
+}
+
+/// Request field section, only referring to the static table.
+fn encode_request(url: &Url, authority: &str, headers: &[RequestHeader]) -> Vec<u8> {
+    // No dynamic table entries are required
+    let mut buf = vec![0, 0];
+    // :method GET
+    put_integer(&mut buf, 0xc0, 6, 17);
+    // :scheme https
+    put_integer(&mut buf, 0xc0, 6, 23);
+    // :authority and :path with a static name reference
+    put_integer(&mut buf, 0x50, 4, 0);
+    put_string(&mut buf, 0, 7, authority);
+    put_integer(&mut buf, 0x50, 4, 1);
+    put_string(
+        &mut buf,
+        0,
+        7,
+        &url[url::Position::BeforePath..url::Position::AfterQuery],
+    );
+    // The header names are already lowercase
+    for header in headers {
+        put_string(&mut buf, 0x20, 3, header.name.as_str());
+        put_integer(&mut buf, 0, 7, header.value.len());
+        buf.extend_from_slice(header.value.as_bytes());
+    }
+    buf
+}
+
+/// Status of a response field section, the other fields being ignored.
+fn decode_status(section: &[u8]) -> Result<Option<u16>> {
+    let mut position = 0;
+    if get_integer(section, &mut position, 8)? != 0 {
+        bail!("unexpected QPACK dynamic table reference");
+    }
+    get_integer(section, &mut position, 7)?;
+
+    let mut status = None;
+    while position < section.len() {
+        let first = section[position];
+        let (index, value) = if first & 0x80 != 0 {
+            // Indexed field line
+            if first & 0x40 == 0 {
+                bail!("unexpected QPACK dynamic table reference");
+            }
+            let index = get_integer(section, &mut position, 6)?;
+            (Some(index), None)
+        } else if first & 0x40 != 0 {
+            // Literal field line with a name reference
+            if first & 0x10 == 0 {
+                bail!("unexpected QPACK dynamic table reference");
+            }
+            let index = get_integer(section, &mut position, 4)?;
+            (Some(index), Some(get_string(section, &mut position, 7)?))
+        } else if first & 0x20 != 0 {
+            // Literal field line with a literal name
+            let (huffman, name) = get_string(section, &mut position, 3)?;
+            let value = get_string(section, &mut position, 7)?;
+            if !huffman && name == b":status" {
+                (Some(24), Some(value))
+            } else {
+                (None, None)
+            }
+        } else {
+            bail!("unexpected QPACK dynamic table reference");
+        };
+
+        let Some(default) = index.and_then(static_status) else {
+            continue;
+        };
+        let value = match value {
+            None => default.to_string(),
+            Some((true, value)) => huffman_digits(value)?,
+            Some((false, value)) => String::from_utf8_lossy(value).into_owned(),
+        };
+        status = Some(
+            value
+                .parse()
+                .with_context(|| format!("invalid HTTP/3 status {}", value))?,
+        );
+    }
+    Ok(status)
+}
+
+/// Status and body of the frames of a response, skipping the informational responses.
+fn decode_response(buf: &[u8]) -> Result<Response> {
+    let mut position = 0;
+    let mut status = None;
+    let mut body = Vec::new();
+    while position < buf.len() {
+        let kind = get_varint(buf, &mut position)?;
+        let length = get_varint(buf, &mut position)? as usize;
+        let payload = buf
+            .get(position..position + length)
+            .context("truncated HTTP/3 frame")?;
+        position += length;
+        match kind {
+            FRAME_HEADERS if status.is_none_or(|status| (100..200).contains(&status)) => {
+                status = decode_status(payload)?;
+            }
+            FRAME_DATA => body.extend_from_slice(payload),
+            // Trailers and unknown frames
+            _ => {}
+        }
+    }
+    match status {
+        Some(status) if status >= 200 => Ok(Response { status, body }),
+        _ => bail!("HTTP/3 response without status"),
+    }
+}
+
+fn client_config(tls: &rustls::ClientConfig) -> Result<ClientConfig> {
+    let mut config = tls.clone();
+    config.alpn_protocols = vec![b"h3".to_vec()];
+    Ok(ClientConfig::new(Arc::new(QuicClientConfig::try_from(
+        config,
+    )?)))
+}
+
+async fn request(url: &Url, connect: &Connect, headers: &[RequestHeader]) -> Result<Response> {
+    let host = url.host_str().context("URL without host")?;
+    let port = url.port_or_known_default().context("URL without port")?;
+    let (ip, server_name) = match url.host() {
+        Some(Host::Ipv4(ip)) => (IpAddr::V4(ip), ServerName::from(IpAddr::V4(ip))),
+        Some(Host::Ipv6(ip)) => (IpAddr::V6(ip), ServerName::from(IpAddr::V6(ip))),
+        _ => (
+            connect.resolver.lookup(host).await?[0],
+            ServerName::try_from(host.to_string())?,
+        ),
+    };
+    let addr = SocketAddr::new(ip, port);
+
+    let socket = connect.udp(addr).await?;
+    let endpoint = Endpoint::new(
+        EndpointConfig::default(),
+        None,
+        socket,
+        Arc::new(TokioRuntime),
+    )?;
+    let connecting =
+        endpoint.connect_with(client_config(&connect.tls)?, addr, &server_name.to_str())?;
+    let handshake = connecting.instrument(debug_span!("connect", %addr));
+    let connection = match connect.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, handshake)
+            .await
+            .map_err(|_| anyhow!("connect timed out"))??,
+        None => handshake.await?,
+    };
+
+    let exchange = async {
+        // The control stream must stay open along with the connection, and the QPACK
+        // encoder and decoder streams are omitted without any dynamic table
+        let mut control = connection.open_uni().await?;
+        let mut settings = Vec::new();
+        put_varint(&mut settings, STREAM_CONTROL);
+        put_varint(&mut settings, FRAME_SETTINGS);
+        put_varint(&mut settings, 0);
+        control.write_all(&settings).await?;
+
+        let (mut send, mut recv) = connection.open_bi().await?;
+        let authority = &url[url::Position::BeforeHost..url::Position::AfterPort];
+        let section = encode_request(url, authority, headers);
+        let mut frame = Vec::new();
+        put_varint(&mut frame, FRAME_HEADERS);
+        put_varint(&mut frame, section.len() as u64);
+        frame.extend_from_slice(&section);
+        send.write_all(&frame).await?;
+        send.finish()?;
+        let response = recv.read_to_end(MAX_RESPONSE).await?;
+        Ok::<_, anyhow::Error>((control, response))
+    };
+    let response = exchange.instrument(debug_span!("request")).await;
+    connection.close(VarInt::from_u32(NO_ERROR), b"");
+    decode_response(&response?.1)
+}
+
+/// Request a beacon over HTTP/3, on a new QUIC connection.
+pub async fn get(
+    url: &Url,
+    timeout: Duration,
+    connect: &Connect,
+    headers: &[RequestHeader],
+) -> Result<Response> {
+    if url.scheme() != "https" {
+        bail!("HTTP/3 requires an https URL, not {}", url);
+    }
+    tokio::time::timeout(timeout, request(url, connect, headers))
+        .await
+        .map_err(|_| anyhow!("timed out"))?
+}
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use url::Url;
+
+use crate::probe::Connect;
+
+const ECHO_REQUEST_V4: u8 = 8;
+const ECHO_REPLY_V4: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+
+/// ICMP socket of the family of `ip`, unprivileged where the system allows it
+/// (`net.ipv4.ping_group_range` on Linux), raw otherwise. Also tells if the socket is raw.
+fn socket(ip: IpAddr) -> Result<(Socket, bool)> {
+    let (domain, protocol) = match ip {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    if let Ok(socket) = Socket::new(domain, Type::DGRAM, Some(protocol)) {
+        return Ok((socket, false));
+    }
+    let socket = Socket::new(domain, Type::RAW, Some(protocol)).context(
+        "failed to open an ICMP socket: unprivileged ICMP is not allowed and raw sockets require CAP_NET_RAW",
+    )?;
+    Ok((socket, true))
+}
+
+/// Internet checksum of an ICMP message.
+fn checksum(message: &[u8]) -> u16 {
+    let mut sum = message
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word.get(1).copied().unwrap_or(0)]) as u32)
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Echo request of ICMP, or ICMPv6 for an IPv6 address.
+pub fn echo_request(ip: IpAddr, identifier: u16, sequence: u16, payload: &[u8]) -> Vec<u8> {
+    let kind = if ip.is_ipv4() {
+        ECHO_REQUEST_V4
+    } else {
+        ECHO_REQUEST_V6
+    };
+    let mut message = vec![kind, 0, 0, 0];
+    message.extend_from_slice(&identifier.to_be_bytes());
+    message.extend_from_slice(&sequence.to_be_bytes());
+    message.extend_from_slice(payload);
+    // The checksum of ICMPv6 covers a pseudo-header, it is computed by the kernel
+    if ip.is_ipv4() {
+        let checksum = checksum(&message);
+        message[2..4].copy_from_slice(&checksum.to_be_bytes());
+    }
+    message
+}
+
+/// Whether a received packet is the reply to an echo request, the identifier being ignored
+/// as it is rewritten by the kernel for unprivileged sockets.
+fn is_reply(ip: IpAddr, raw: bool, packet: &[u8], sequence: u16, payload: &[u8]) -> bool {
+    // Raw IPv4 sockets receive the IP header along with the ICMP message
+    let message = if ip.is_ipv4() && raw {
+        let length = (packet.first().copied().unwrap_or(0) & 0x0f) as usize * 4;
+        packet.get(length..).unwrap_or_default()
+    } else {
+        packet
+    };
+    let kind = if ip.is_ipv4() {
+        ECHO_REPLY_V4
+    } else {
+        ECHO_REPLY_V6
+    };
+    message.len() >= 8
+        && message[0] == kind
+        && message[6..8] == sequence.to_be_bytes()
+        && &message[8..] == payload
+}
+
+async fn echo(ip: IpAddr, connect: &Connect) -> Result<Duration> {
+    let (socket, raw) = socket(ip)?;
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    if let Some(interface) = &connect.interface {
+        socket
+            .bind_device(Some(interface.as_bytes()))
+            .with_context(|| format!("failed to bind to interface {}", interface))?;
+    }
+    if let Some(source_ip) = connect.source_ip {
+        socket.bind(&SocketAddr::new(source_ip, 0).into())?;
+    }
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(std::net::UdpSocket::from(socket))?;
+
+    let sequence = rand::random::<u16>();
+    let payload = rand::random::<[u8; 16]>();
+    let request = echo_request(ip, rand::random(), sequence, &payload);
+    let start = Instant::now();
+    socket.send_to(&request, SocketAddr::new(ip, 0)).await?;
+    let mut buf = [0u8; 1500];
+    loop {
+        let (length, from) = socket.recv_from(&mut buf).await?;
+        if from.ip() == ip && is_reply(ip, raw, &buf[..length], sequence, &payload) {
+            return Ok(start.elapsed());
+        }
+    }
+}
+
+/// Send an echo request to the host of `url`, returning the address pinged
+/// and the round-trip time of the reply.
+#[tracing::instrument(level = "debug", name = "icmp", skip_all, fields(url = %url))]
+pub async fn ping(url: &Url, timeout: Duration, connect: &Connect) -> Result<(IpAddr, Duration)> {
+    let ping = async {
+        let ip = connect.address(url).await?;
+        Ok((ip, echo(ip, connect).await?))
+    };
+    tokio::time::timeout(timeout, ping)
+        .await
+        .map_err(|_| anyhow!("timed out"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        // Example of RFC 1071
+        assert_eq!(
+            checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]),
+            !0xddf2
+        );
+        // Odd lengths are padded with a zero
+        assert_eq!(checksum(&[0x01]), !0x0100);
+        let request = echo_request("192.0.2.1".parse().unwrap(), 1, 2, b"rovcheck!");
+        assert_eq!(checksum(&request), 0);
+    }
+
+    #[test]
+    fn echo_requests() {
+        let request = echo_request("192.0.2.1".parse().unwrap(), 0x1234, 7, b"abc");
+        assert_eq!(&request[..2], &[ECHO_REQUEST_V4, 0]);
+        assert_eq!(&request[4..], &[0x12, 0x34, 0, 7, b'a', b'b', b'c']);
+        let request = echo_request("2001:db8::1".parse().unwrap(), 0x1234, 7, b"abc");
+        assert_eq!(
+            request,
+            [ECHO_REQUEST_V6, 0, 0, 0, 0x12, 0x34, 0, 7, b'a', b'b', b'c']
+        );
+    }
+
+    #[test]
+    fn replies() {
+        let ipv4 = "192.0.2.1".parse().unwrap();
+        let reply = [ECHO_REPLY_V4, 0, 0, 0, 0xff, 0xff, 0, 7, b'a', b'b', b'c'];
+        assert!(is_reply(ipv4, false, &reply, 7, b"abc"));
+        assert!(!is_reply(ipv4, false, &reply, 8, b"abc"));
+        assert!(!is_reply(ipv4, false, &reply, 7, b"abd"));
+        assert!(!is_reply(ipv4, false, &reply[..7], 7, b""));
+        // Behind the IP header on raw sockets
+        let mut packet = vec![0x45; 20];
+        packet.extend_from_slice(&reply);
+        assert!(is_reply(ipv4, true, &packet, 7, b"abc"));
+        assert!(!is_reply(ipv4, true, &packet[..20], 7, b""));
+        assert!(!is_reply(ipv4, true, &[], 7, b""));
+
+        let ipv6 = "2001:db8::1".parse().unwrap();
+        let mut reply = reply;
+        reply[0] = ECHO_REPLY_V6;
+        assert!(is_reply(ipv6, true, &reply, 7, b"abc"));
+        assert!(!is_reply(ipv4, false, &reply, 7, b"abc"));
+    }
+}
@@ -0,0 +1,51 @@
+pub mod agent;
+pub mod apnic;
+pub mod asn;
+pub mod atlas;
+pub mod beacon;
+pub mod checker;
+pub mod collector;
+pub mod confidence;
+pub mod config;
+pub mod control;
+pub mod daemon;
+pub mod dns;
+pub mod duration;
+pub mod export;
+pub mod grpc;
+pub mod history;
+pub mod http3;
+pub mod icmp;
+pub mod looking_glass;
+pub mod matcher;
+pub mod netns;
+pub mod notify;
+pub mod otel;
+pub mod output;
+pub mod prefix_info;
+pub mod preflight;
+pub mod preset;
+pub mod probe;
+pub mod prometheus;
+pub mod radar;
+pub mod ratelimit;
+pub mod resolver;
+pub mod ris;
+pub mod rpki;
+pub mod rtr;
+pub mod serve;
+pub mod signing;
+pub mod systemd;
+pub mod tls;
+pub mod traceroute;
+pub mod verdict;
+pub mod watcher;
+
+pub use beacon::{BeaconKind, BeaconPair, BeaconResult, Invalidity, RetryPolicy};
+pub use checker::{Config, FamilyVerdict, PairPaths, PairResult, RovChecker, RovResult};
+pub use config::{BeaconDefinition, ConfigFile};
+pub use daemon::Daemon;
+pub use history::History;
+pub use resolver::AddressFamily;
+pub use verdict::{Quorum, Verdict};
+pub use watcher::{Transition, Watcher};
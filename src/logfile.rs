@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+use crate::cli::LogRotation;
+
+/// Log file rotated once it reaches a size, `<path>.1` being the most recent rotated file.
+struct SizeRotating {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl SizeRotating {
+    fn new(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = open(path)?;
+        let size = file.metadata()?.len();
+        Ok(SizeRotating {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.keep).rev() {
+                let from = rotated(&self.path, index);
+                if from.exists() {
+                    fs::rename(&from, rotated(&self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        self.file = open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Write from a background thread, without dropping logs when the writes stall.
+fn non_blocking<W: Write + Send + 'static>(writer: W) -> (NonBlocking, WorkerGuard) {
+    NonBlockingBuilder::default().lossy(false).finish(writer)
+}
+
+/// Non-blocking writer to the log file, rotated by size if `max_size` is set, or else by time,
+/// keeping `keep` rotated files. The logs are flushed when the returned guard is dropped.
+pub fn writer(
+    path: &Path,
+    rotation: LogRotation,
+    max_size: Option<u64>,
+    keep: usize,
+) -> Result<(NonBlocking, WorkerGuard)> {
+    let context = || format!("failed to open log file {}", path.display());
+    if let Some(max_size) = max_size {
+        let file = SizeRotating::new(path, max_size, keep).with_context(context)?;
+        return Ok(non_blocking(file));
+    }
+
+    let rotation = match rotation {
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    };
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // The current log file is counted in the maximum number of files
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .max_log_files(keep + 1);
+    if let Some(name) = path.file_name() {
+        builder = builder.filename_prefix(name.to_string_lossy());
+    }
+    let file = builder.build(directory).with_context(context)?;
+    Ok(non_blocking(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty temporary directory of a test.
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("rovcheck-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn size_rotation() {
+        let directory = directory("logfile-size");
+        let path = directory.join("rovcheck.log");
+        let mut file = SizeRotating::new(&path, 10, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(rotated(&path, 1)).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(rotated(&path, 2)).unwrap(), "second\n");
+        assert!(!rotated(&path, 3).exists());
+
+        // The size of an existing file counts towards the rotation
+        let mut file = SizeRotating::new(&path, 10, 0).unwrap();
+        file.write_all(b"fifth\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fifth\n");
+        assert_eq!(fs::read_to_string(rotated(&path, 1)).unwrap(), "third\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn age_rotation() {
+        let directory = directory("logfile-age");
+        let path = directory.join("rovcheck.log");
+        let (mut writer, guard) = writer(&path, LogRotation::Daily, None, 1).unwrap();
+        writer.write_all(b"check\n").unwrap();
+        drop(guard);
+
+        let name = format!("rovcheck.log.{}", chrono::Utc::now().format("%Y-%m-%d"));
+        assert_eq!(fs::read_to_string(directory.join(name)).unwrap(), "check\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::IpAddr;
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::probe::Connect;
+
+/// Alice-LG instance, the routes being looked up across all its route servers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alice {
+    /// Name of the looking glass in the results [default: the host of the URL]
+    pub name: Option<String>,
+    /// Base URL of the looking glass, serving the `api/v1/` endpoints
+    pub url: Url,
+}
+
+/// bird-lg-go frontend, the routes being looked up on each of its servers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BirdLg {
+    /// Name of the looking glass in the results [default: the host of the URL]
+    pub name: Option<String>,
+    /// Base URL of the frontend, serving the `api/` endpoint
+    pub url: Url,
+    /// Servers to query
+    pub servers: Vec<String>,
+}
+
+/// A looking glass telling whether its routers have a route to the beacons.
+#[derive(Clone, Debug)]
+pub enum LookingGlass {
+    Alice(Alice),
+    BirdLg(BirdLg),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Routes {
+    /// Address of the beacon looked up
+    pub address: Option<IpAddr>,
+    /// Number of routes to the beacon accepted by the routers
+    pub routes: usize,
+    /// Number of routes to the beacon filtered by the route servers, e.g. RPKI invalid
+    #[serde(default)]
+    pub filtered: usize,
+    pub error: Option<String>,
+}
+
+impl Routes {
+    /// Whether the looking glass has a route to the beacon.
+    pub fn seen(&self) -> bool {
+        self.routes > 0
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LookingGlassResult {
+    pub name: String,
+    /// `alice` or `bird-lg`
+    pub kind: String,
+    pub valid: Routes,
+    pub invalid: Routes,
+}
+
+#[derive(Deserialize)]
+struct AliceLookup {
+    #[serde(default)]
+    imported: AliceRoutes,
+    #[serde(default)]
+    filtered: AliceRoutes,
+}
+
+#[derive(Default, Deserialize)]
+struct AliceRoutes {
+    #[serde(default)]
+    routes: Vec<serde_json::Value>,
+    pagination: Option<Pagination>,
+}
+
+impl AliceRoutes {
+    fn count(&self) -> usize {
+        self.pagination
+            .as_ref()
+            .map_or(self.routes.len(), |pagination| pagination.total_results)
+    }
+}
+
+#[derive(Deserialize)]
+struct Pagination {
+    total_results: usize,
+}
+
+#[derive(Deserialize)]
+struct BirdLgResponse {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    result: Vec<BirdLgResult>,
+}
+
+#[derive(Deserialize)]
+struct BirdLgResult {
+    #[serde(default)]
+    data: String,
+}
+
+/// Number of routes in the output of `show route`: the lines with the protocol of a route,
+/// the alternative paths being listed on their own lines under the prefix.
+fn bird_routes(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.contains(" [") && (line.contains("unicast") || line.contains(" via ")))
+        .count()
+}
+
+impl LookingGlass {
+    pub fn name(&self) -> String {
+        let (name, url) = match self {
+            LookingGlass::Alice(alice) => (&alice.name, &alice.url),
+            LookingGlass::BirdLg(bird_lg) => (&bird_lg.name, &bird_lg.url),
+        };
+        name.clone()
+            .unwrap_or_else(|| url.host_str().unwrap_or_default().to_string())
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LookingGlass::Alice(_) => "alice",
+            LookingGlass::BirdLg(_) => "bird-lg",
+        }
+    }
+
+    async fn lookup(&self, client: &Client, ip: IpAddr, routes: &mut Routes) -> Result<()> {
+        match self {
+            LookingGlass::Alice(alice) => {
+                let mut url = alice.url.join("api/v1/lookup/prefix")?;
+                url.query_pairs_mut().append_pair("q", &ip.to_string());
+                let response = client.get(url).send().await?.error_for_status()?;
+                let lookup: AliceLookup = response.json().await?;
+                routes.routes = lookup.imported.count();
+                routes.filtered = lookup.filtered.count();
+            }
+            LookingGlass::BirdLg(bird_lg) => {
+                let body = json!({
+                    "servers": bird_lg.servers,
+                    "type": "bird",
+                    "args": format!("show route for {} all", ip),
+                });
+                let url = bird_lg.url.join("api/")?;
+                let response = client.post(url).json(&body).send().await?;
+                let response: BirdLgResponse = response.error_for_status()?.json().await?;
+                if !response.error.is_empty() {
+                    bail!("{}", response.error);
+                }
+                routes.routes = response
+                    .result
+                    .iter()
+                    .map(|result| bird_routes(&result.data))
+                    .sum();
+            }
+        }
+        Ok(())
+    }
+
+    async fn routes(&self, client: &Client, url: &Url, connect: &Connect) -> Routes {
+        let mut routes = Routes {
+            address: None,
+            routes: 0,
+            filtered: 0,
+            error: None,
+        };
+        let lookup = async {
+            let ip = connect.address(url).await?;
+            routes.address = Some(ip);
+            self.lookup(client, ip, &mut routes).await
+        };
+        if let Err(e) = lookup.await {
+            routes.error = Some(e.to_string());
+        }
+        routes
+    }
+
+    /// Look up the routes to the valid and invalid beacons.
+    #[instrument(level = "debug", name = "looking_glass", skip_all, fields(name = %self.name()))]
+    pub async fn check(
+        &self,
+        client: &Client,
+        valid_url: &Url,
+        valid_connect: &Connect,
+        invalid_url: &Url,
+        invalid_connect: &Connect,
+    ) -> LookingGlassResult {
+        let (valid, invalid) = tokio::join!(
+            self.routes(client, valid_url, valid_connect),
+            self.routes(client, invalid_url, invalid_connect)
+        );
+        debug!(
+            valid = valid.routes,
+            invalid = invalid.routes,
+            "Looking glass lookup finished"
+        );
+        LookingGlassResult {
+            name: self.name(),
+            kind: self.kind().to_string(),
+            valid,
+            invalid,
+        }
+    }
+}
@@ -1,107 +1,1267 @@
-use anyhow::Result;
-use clap::Parser as CliParser;
-use clap_verbosity_flag::{InfoLevel, Verbosity};
-use nanoid::nanoid;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::CommandFactory;
+use futures::future::try_join_all;
 use reqwest::Client;
-use serde::Deserialize;
+use serde::Serialize;
+use std::io;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use tracing::{debug, info};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriterExt};
+use tracing_subscriber::prelude::*;
 use url::Url;
 
-#[derive(CliParser, Debug)]
-#[command(version, about, long_about = None)]
-struct Cli {
-    /// The URL to use for valid requests
-    #[arg(long, default_value = "https://valid.rpki.isbgpsafeyet.com")]
-    valid_url: String,
+use rovcheck::agent::Agent;
+use rovcheck::asn::AsnLookup;
+use rovcheck::atlas::{self, Atlas, Probes};
+use rovcheck::control::Control;
+use rovcheck::daemon::Reload;
+use rovcheck::dns::NameServer;
+use rovcheck::export::{
+    export, flush, Clickhouse, Exporter, Influx, JsonFile, Kafka, Mqtt, Nats, Otlp, Pushgateway,
+    Statsd, Zabbix,
+};
+use rovcheck::notify::{notify, Event, Ping, Webhook};
+use rovcheck::otel::Otel;
+use rovcheck::output::{checkmk, csv, influx, nagios, State};
+use rovcheck::prefix_info::PrefixLookup;
+use rovcheck::preflight::Preflight;
+use rovcheck::radar::Radar;
+use rovcheck::ratelimit::RateLimit;
+use rovcheck::ris::Ris;
+use rovcheck::rpki::Rpki;
+use rovcheck::signing;
+use rovcheck::watcher::Transition;
+use rovcheck::{
+    AddressFamily, BeaconDefinition, BeaconKind, Config, ConfigFile, Daemon, History, Invalidity,
+    RetryPolicy, RovChecker, RovResult, Verdict,
+};
 
-    /// The URL to use for invalid requests
-    #[arg(long, default_value = "https://invalid.rpki.isbgpsafeyet.com")]
-    invalid_url: String,
+mod cli;
+mod commands;
+mod logfile;
+mod syslog;
+mod tui;
 
-    /// Alphabet to use for generating the ID
-    #[arg(long, default_value = "1234567890abcdef")]
-    alphabet: String,
+use cli::{BeaconsCommand, CheckArgs, Cli, Command, ConfigCommand, Format, LogFormat};
+use syslog::Syslog;
+use tui::LogWriter;
 
-    /// Requests timeout
-    #[arg(long, short, default_value = "3")]
-    timeout: usize,
-
-    /// Verbosity level
-    #[clap(flatten)]
-    verbose: Verbosity<InfoLevel>,
+/// Telemetry to flush before exiting.
+struct Telemetry {
+    otel: Option<Otel>,
+    _log_file: Option<WorkerGuard>,
 }
 
-fn set_tracing(cli: &Cli) -> Result<()> {
-    let subscriber = tracing_subscriber::fmt()
-        .compact()
+fn set_tracing(cli: &Cli) -> Result<Telemetry> {
+    let (log_file, guard) = match &cli.log_file {
+        Some(path) => {
+            let (writer, guard) =
+                logfile::writer(path, cli.log_rotation, cli.log_max_size, cli.log_keep)?;
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+    let syslog = cli.log_syslog.as_ref().map(Syslog::new).transpose()?;
+    // The dashboard owns the terminal, so it shows the logs itself.
+    let tui = matches!(&cli.command, Command::Check(args) if args.tui);
+    let writer = match (tui, log_file) {
+        (true, Some(log_file)) => BoxMakeWriter::new(LogWriter.and(log_file)),
+        (true, None) => BoxMakeWriter::new(LogWriter),
+        (false, Some(log_file)) => BoxMakeWriter::new(log_file),
+        (false, None) => BoxMakeWriter::new(std::io::stderr),
+    };
+    let level = cli.log_level();
+    let mut fmt = tracing_subscriber::fmt::layer()
         .with_file(true)
         .with_line_number(true)
-        .with_max_level(cli.verbose)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
-    Ok(())
+        .with_writer(writer);
+    if tui || cli.log_file.is_some() {
+        fmt = fmt.with_ansi(false);
+    }
+    let fmt = match cli.log_format {
+        LogFormat::Compact => fmt.compact().boxed(),
+        LogFormat::Pretty => fmt.pretty().boxed(),
+        LogFormat::Json => fmt.json().boxed(),
+    }
+    .with_filter(level);
+    // Syslog replaces stderr, but not the log file or the dashboard
+    let fmt = (syslog.is_none() || tui || cli.log_file.is_some()).then_some(fmt);
+    let syslog = syslog.map(|syslog| {
+        // Syslog messages already carry the timestamp and the severity
+        let layer = tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .without_time()
+            .with_level(false)
+            .with_writer(syslog);
+        match cli.log_format {
+            LogFormat::Compact => layer.compact().boxed(),
+            LogFormat::Pretty => layer.pretty().boxed(),
+            LogFormat::Json => layer.json().boxed(),
+        }
+        .with_filter(level)
+    });
+    let otel = if cli.otlp { Some(Otel::init()?) } else { None };
+    tracing_subscriber::registry()
+        .with(fmt)
+        .with(syslog)
+        .with(otel.as_ref().map(Otel::layer))
+        .try_init()?;
+    Ok(Telemetry {
+        otel,
+        _log_file: guard,
+    })
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct IsBgpSafeYet {
-    status: String,
-    asn: u32,
-    name: String,
-    blackholed: bool,
+const EXIT_ENFORCED: u8 = 0;
+const EXIT_NOT_ENFORCED: u8 = 1;
+const EXIT_INCONCLUSIVE: u8 = 2;
+const EXIT_ERROR: u8 = 3;
+
+/// Build the checker config from the merged configuration.
+fn load_config(file: &ConfigFile) -> Result<Config> {
+    let mut config = Config::default();
+    let pairs = file.pairs()?;
+    if !pairs.is_empty() {
+        config.pairs = pairs;
+    }
+    if let Some(alphabet) = &file.alphabet {
+        config.alphabet = alphabet.clone();
+    }
+    if let Some(timeout) = file.timeout {
+        config.timeout = timeout;
+    }
+    config.connect_timeout = file.connect_timeout;
+    config.proxy = file.proxy.clone();
+    config.source_ip = file.source_ip;
+    config.uplinks = file.uplinks.clone();
+    config.vrf = file.vrf.clone();
+    config.dns_server = file.dns_server.clone();
+    config.resolve = file.resolve.clone();
+    config.netns = file.netns.clone();
+    config.family = file.family;
+    config.dual_stack = file.dual_stack.unwrap_or(false);
+    config.timings = file.timings.unwrap_or(false);
+    config.all_ips = file.all_ips.unwrap_or(false);
+    config.http3 = file.http3.unwrap_or(false);
+    config.mode = file.mode.unwrap_or_default();
+    config.connection_reuse = file.connection_reuse.unwrap_or_default();
+    config.http_version = file.http_version;
+    if let Some(user_agent) = &file.user_agent {
+        config.user_agent = user_agent.clone();
+    }
+    config.headers = file.headers.clone();
+    config.tls_min_version = file.tls_min_version;
+    config.cacert = file.cacert.clone();
+    config.insecure = file.insecure.unwrap_or(false);
+    config.certificates = file.certificates.unwrap_or(false);
+    config.pins = file.pins.clone();
+    config.expect_asn = file.expect_asn.clone();
+    config.strict = file.strict.unwrap_or(false);
+    config.preflight = file.preflight.clone();
+    config.rpki = file.rpki.clone();
+    config.ris = file.ris.clone();
+    config.control = file.control.clone();
+    config.asn_info = file.asn_info.clone();
+    config.radar = file.radar.clone();
+    config.prefix_info = file.prefix_info.clone();
+    config.signing_key = file
+        .signing_key
+        .as_deref()
+        .map(signing::load_key)
+        .transpose()?;
+    config.labels = file.labels.clone();
+    config.rate_limit = file.rate_limit.clone().filter(RateLimit::is_enabled);
+    config.looking_glasses = file.looking_glasses();
+    if file.traceroute_on_failure.unwrap_or(false) {
+        config.traceroute = Some(file.traceroute_mode.unwrap_or_default());
+    }
+    if let Some(trials) = file.trials {
+        config.trials = trials;
+    }
+    config.trial_threshold = file.trial_threshold;
+    config.quorum = file.quorum;
+    if let Some(threshold) = config.trial_threshold {
+        if !(0.0..=1.0).contains(&threshold) {
+            bail!("trial threshold must be between 0 and 1");
+        }
+    }
+    config.retry = RetryPolicy {
+        retries: file.retries.unwrap_or(0),
+        backoff: file.retry_backoff.unwrap_or(Duration::from_millis(500)),
+    };
+    Ok(config)
 }
 
-async fn get_url(client: &Client, url: Url) -> Result<IsBgpSafeYet, Box<dyn std::error::Error>> {
-    let response = client.get(url).send().await?;
-    let isbgpsafeyet = response.json::<IsBgpSafeYet>().await?;
-    Ok(isbgpsafeyet)
+/// Load the config file, or its profile given with `--profile`, overridden by the environment
+/// variables and the global CLI flags.
+fn load_file(cli: &Cli) -> Result<ConfigFile> {
+    match cli.profile.as_slice() {
+        [] => load_profile(cli, None),
+        [profile] => load_profile(cli, Some(profile)),
+        _ => bail!("only a single profile can be given to this command"),
+    }
 }
 
-async fn check_success(client: &Client, url: Url) -> bool {
-    match get_url(&client, url).await {
-        Ok(response) => {
-            debug!("Response: {:?}", response);
-            true
+/// Load the config file overridden by a profile, of which the results are labelled with its
+/// name, then by the environment variables and the global CLI flags.
+fn load_profile(cli: &Cli, profile: Option<&str>) -> Result<ConfigFile> {
+    let (path, ignore) = (cli.config.as_deref(), &Cli::env_vars());
+    let mut file = match profile {
+        Some(profile) => {
+            let mut file = ConfigFile::load_profile(path, ignore, profile)?;
+            file.labels
+                .entry("profile".to_string())
+                .or_insert_with(|| profile.to_string());
+            file
         }
+        None => ConfigFile::load(path, ignore)?,
+    };
+    if let Some(path) = &cli.history_db {
+        file.history_db = Some(path.clone());
+    }
+    Ok(file)
+}
 
-        Err(e) => {
-            debug!("Error: {}", e);
-            false
+/// Merged configurations of the profiles to check: the ones given with `--profile`, all of
+/// them by default, or the config file alone without profiles.
+fn load_profiles(cli: &Cli, args: &CheckArgs) -> Result<Vec<(Option<String>, ConfigFile)>> {
+    let file = load_profile(cli, None)?;
+    if file.profiles.is_empty() {
+        if !cli.profile.is_empty() {
+            bail!("no profiles configured");
         }
+        return Ok(vec![(None, merge(args, file)?)]);
     }
+    let names = match cli.profile.is_empty() {
+        true => file.profiles.keys().cloned().collect(),
+        false => cli.profile.clone(),
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            let file = merge(args, load_profile(cli, Some(&name))?)?;
+            Ok((Some(name), file))
+        })
+        .collect()
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-    set_tracing(&cli)?;
+/// Override the configuration with the check CLI flags.
+fn merge(args: &CheckArgs, mut file: ConfigFile) -> Result<ConfigFile> {
+    let mut run = args.preset.clone();
+    if let (Some(valid_url), Some(invalid_url)) = (&args.valid_url, &args.invalid_url) {
+        let (valid_url, invalid_url) = (Url::parse(valid_url)?, Url::parse(invalid_url)?);
+        file.beacons.retain(|beacon| beacon.name != "custom");
+        file.beacons.push(custom_beacon(
+            "custom",
+            valid_url,
+            invalid_url,
+            args.sni.clone(),
+        ));
+        run.push("custom".to_string());
+    }
+    if !run.is_empty() {
+        file.run = run;
+    }
 
-    let alphabet = cli.alphabet.chars().collect::<Vec<char>>();
-    let mut id = String::new();
-    if alphabet.len() != 0 {
-        id = nanoid!(10, &alphabet);
+    file.alphabet = args.alphabet.clone().or(file.alphabet);
+    file.timeout = args.timeout.or(file.timeout);
+    file.connect_timeout = args.connect_timeout.or(file.connect_timeout);
+    file.proxy = args.proxy.clone().or(file.proxy);
+    file.source_ip = args.source_ip.or(file.source_ip);
+    if !args.interface.is_empty() {
+        file.uplinks = args.interface.clone();
+    }
+    file.vrf = args.vrf.clone().or(file.vrf);
+    file.dns_server = args
+        .dns_server
+        .clone()
+        .or(args.doh_url.clone().map(NameServer::Https))
+        .or(args.dot_server.clone())
+        .or(file.dns_server);
+    file.resolve.extend(args.resolve.iter().cloned());
+    file.netns = args.netns.clone().or(file.netns);
+    file.retries = args.retries.or(file.retries);
+    file.retry_backoff = args.retry_backoff.or(file.retry_backoff);
+    if args.ipv4 {
+        file.family = Some(AddressFamily::Ipv4);
+    } else if args.ipv6 {
+        file.family = Some(AddressFamily::Ipv6);
+    }
+    file.dual_stack = args.dual_stack.or(file.dual_stack);
+    file.timings = args.timings.or(file.timings);
+    file.all_ips = args.all_ips.or(file.all_ips);
+    file.http3 = args.http3.or(file.http3);
+    file.mode = args.mode.or(file.mode);
+    file.connection_reuse = args.connection_reuse.or(file.connection_reuse);
+    file.http_version = args.http_version.or(file.http_version);
+    file.user_agent = args.user_agent.clone().or(file.user_agent);
+    file.headers.extend(args.header.iter().cloned());
+    file.tls_min_version = args.tls_min_version.or(file.tls_min_version);
+    file.cacert = args.cacert.clone().or(file.cacert);
+    file.insecure = args.insecure.or(file.insecure);
+    file.certificates = args.certificates.or(file.certificates);
+    file.pins.extend(args.pin.iter().cloned());
+    file.expect_asn.extend(&args.expect_asn);
+    file.strict = args.strict.or(file.strict);
+    file.traceroute_on_failure = args.traceroute_on_failure.or(file.traceroute_on_failure);
+    file.traceroute_mode = args.traceroute_mode.or(file.traceroute_mode);
+    // The sections of the file are turned off by the options set to false
+    if let Some(url) = &args.preflight_url {
+        file.preflight = Some(Preflight::new(url.clone()));
+    } else if args.preflight == Some(false) {
+        file.preflight = None;
+    } else if args.preflight == Some(true) && file.preflight.is_none() {
+        file.preflight = Some(Preflight::default());
+    }
+    if args.verify_rpki == Some(false) {
+        file.rpki = None;
+    } else if args.verify_rpki.is_some() || args.rpki_source.is_some() || args.rpki_url.is_some() {
+        let rpki = file.rpki.get_or_insert_with(Rpki::default);
+        rpki.source = args.rpki_source.unwrap_or(rpki.source);
+        rpki.url = args.rpki_url.clone().or(rpki.url.take());
+    }
+    if args.ris == Some(false) {
+        file.ris = None;
+    } else if args.ris.is_some() || !args.upstream_asn.is_empty() {
+        let ris = file.ris.get_or_insert_with(Ris::default);
+        ris.upstreams.extend(&args.upstream_asn);
+    }
+    if let Some(url) = &args.control {
+        file.control = Some(Control::new(
+            args.control_kind.unwrap_or_default(),
+            url.clone(),
+        ));
+    }
+    if args.asn_info == Some(false) {
+        file.asn_info = None;
+    } else if args.asn_info == Some(true) && file.asn_info.is_none() {
+        file.asn_info = Some(AsnLookup::default());
+    }
+    file.signing_key = args.signing_key.clone().or(file.signing_key);
+    file.labels.extend(args.label.iter().cloned());
+    if let Some(source) = args.prefix_info {
+        let lookup = file.prefix_info.get_or_insert_with(PrefixLookup::default);
+        if lookup.source != source {
+            lookup.url = None;
+        }
+        lookup.source = source;
+    }
+    if let Some(token) = &args.radar_token {
+        match &mut file.radar {
+            Some(radar) => radar.token = token.clone(),
+            None => file.radar = Some(Radar::new(token.clone())),
+        }
     }
+    file.trials = args.trials.or(file.trials);
+    file.trial_threshold = args.trial_threshold.or(file.trial_threshold);
+    file.quorum = args.quorum.or(file.quorum);
+    file.interval = args.interval.or(file.interval);
+    if args.rate_limit.is_some() || args.min_interval.is_some() {
+        let limit = file.rate_limit.get_or_insert_with(RateLimit::default);
+        limit.per_minute = args.rate_limit.or(limit.per_minute);
+        limit.min_interval = args.min_interval.or(limit.min_interval);
+    }
+    file.alert_after = args.alert_after.or(file.alert_after);
+    file.recover_after = args.recover_after.or(file.recover_after);
+    file.ping_url = args.ping_url.clone().or(file.ping_url);
 
-    let valid_url = Url::parse(&cli.valid_url)?;
-    let valid_url = valid_url.join(&id)?;
+    file.webhooks
+        .extend(args.webhook.iter().cloned().map(Webhook::new));
+    if let (Some(server), Some(host)) = (&args.zabbix_server, &args.zabbix_host) {
+        file.zabbix = Some(Zabbix::new(server.clone(), host.clone()));
+    }
+    if let Some(url) = &args.pushgateway_url {
+        let labels = args.pushgateway_label.iter().cloned().collect();
+        let pushgateway = Pushgateway::new(url.clone(), args.pushgateway_job.clone(), labels);
+        file.pushgateway = Some(pushgateway);
+    }
+    if let (false, Some(topic)) = (args.kafka_brokers.is_empty(), &args.kafka_topic) {
+        let mut kafka = Kafka::new(args.kafka_brokers.clone(), topic.clone());
+        kafka.tls = args.kafka_tls;
+        file.kafka = Some(kafka);
+    }
+    if let Some(url) = &args.clickhouse_url {
+        file.clickhouse = Some(Clickhouse::new(url.clone(), args.clickhouse_table.clone()));
+    }
+    if let Some(url) = &args.nats_url {
+        let nats = Nats::new(url.clone(), args.nats_subject.clone(), args.nats_jetstream);
+        file.nats = Some(nats);
+    }
+    if let Some(broker) = &args.mqtt_broker {
+        let mqtt = Mqtt::new(broker.clone(), args.mqtt_topic.clone(), args.mqtt_qos);
+        file.mqtt = Some(mqtt);
+    }
+    if let Some(path) = &args.output_file {
+        file.output_file = Some(JsonFile::new(path.clone(), args.output_max_size));
+    }
+    if let Some(address) = &args.statsd_addr {
+        file.statsd = Some(Statsd::new(address.clone(), args.statsd_format));
+    }
+    if let (Some(url), Some(org), Some(bucket)) =
+        (&args.influx_url, &args.influx_org, &args.influx_bucket)
+    {
+        file.influx = Some(Influx {
+            url: url.clone(),
+            org: org.clone(),
+            bucket: bucket.clone(),
+            token: args.influx_token.clone(),
+        });
+    }
+    Ok(file)
+}
 
-    let client = Client::builder()
-        .timeout(Duration::from_secs(cli.timeout as u64))
-        .build()?;
+/// Beacon pair of URLs given on the command line, DNS beacons with `dns://` URLs.
+fn custom_beacon(
+    name: &str,
+    valid_url: Url,
+    invalid_url: Url,
+    sni: Option<String>,
+) -> BeaconDefinition {
+    let kind = match valid_url.scheme() {
+        "dns" => BeaconKind::Dns,
+        _ => BeaconKind::IsBgpSafeYet,
+    };
+    BeaconDefinition {
+        name: name.to_string(),
+        kind,
+        valid_url,
+        invalid_url,
+        invalidity: Invalidity::default(),
+        timeout: None,
+        proxy: None,
+        sni,
+        pins: Vec::new(),
+        matcher: None,
+    }
+}
 
-    let valid_success = check_success(&client, valid_url).await;
+/// Build the daemon from the merged configuration.
+fn daemon(cli: &Cli, file: &ConfigFile) -> Result<Daemon> {
+    let mut config = load_config(file)?;
+    if let Some(limit) = &mut config.rate_limit {
+        limit.queue = true;
+    }
+    let checker = RovChecker::new(config)?;
+    let mut interval = file.interval.unwrap_or(Duration::from_secs(60));
+    if let Some(min_interval) = file
+        .rate_limit
+        .as_ref()
+        .and_then(|limit| limit.min_interval)
+    {
+        if interval < min_interval {
+            warn!(
+                "The interval is shorter than the minimum interval, checking every {}",
+                humantime::format_duration(min_interval)
+            );
+            interval = min_interval;
+        }
+    }
+    let daemon = Daemon::new(checker, interval)
+        .with_damping(
+            file.alert_after.unwrap_or(1),
+            file.recover_after.unwrap_or(1),
+        )
+        .with_notifiers(file.notifiers())
+        .with_ping(ping(file))
+        .with_exporters(exporters(cli, file));
+    Ok(daemon)
+}
 
-    let invalid_url = Url::parse(&cli.invalid_url)?;
-    let invalid_url = invalid_url.join(&id)?;
+/// Rebuild the daemon of a profile from the config file, reloaded from disk.
+fn reload(cli: &Cli, args: &CheckArgs, profile: Option<String>) -> Reload {
+    let (cli, args) = (cli.clone(), args.clone());
+    Box::new(move || {
+        daemon(
+            &cli,
+            &merge(&args, load_profile(&cli, profile.as_deref())?)?,
+        )
+    })
+}
 
-    let invalid_success = check_success(&client, invalid_url).await;
+/// Build the daemons of the profiles, storing their results and reloading on SIGHUP.
+fn daemons(
+    cli: &Cli,
+    args: &CheckArgs,
+    profiles: &[(Option<String>, ConfigFile)],
+) -> Result<Vec<Daemon>> {
+    profiles
+        .iter()
+        .map(|(profile, file)| {
+            let daemon = daemon(cli, file)?
+                .with_history(open_history(file)?)
+                .with_reload(reload(cli, args, profile.clone()))
+                .with_profile(profile.clone());
+            Ok(daemon)
+        })
+        .collect()
+}
 
-    if valid_success && !invalid_success {
-        info!("OK");
-    } else {
-        info!("NOK");
+async fn run(cli: Cli) -> Result<ExitCode> {
+    match &cli.command {
+        Command::Check(args) => check(&cli, args, &load_profiles(&cli, args)?).await,
+        Command::Serve { check, address } => {
+            let profiles = load_profiles(&cli, check)?;
+            let daemons = daemons(&cli, check, &profiles)?;
+            // The API serves the history of the config file, shared by the profiles
+            let history = match profiles.as_slice() {
+                [(_, file)] => open_history(file)?,
+                _ => open_history(&load_profile(&cli, None)?)?,
+            };
+            rovcheck::serve::serve(daemons, *address, history).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Agent {
+            check,
+            controller,
+            probe_id,
+            token,
+            ca_cert,
+            cert,
+            key,
+        } => {
+            let profiles = load_profiles(&cli, check)?;
+            let file = load_profile(&cli, None)?;
+            let mut agent = match (controller, &file.agent) {
+                (Some(controller), Some(agent)) => Agent {
+                    controller: controller.clone(),
+                    ..agent.clone()
+                },
+                (Some(controller), None) => Agent::new(controller.clone()),
+                (None, Some(agent)) => agent.clone(),
+                (None, None) => bail!(
+                    "a collector is required, with --controller or the `agent` table of the config file"
+                ),
+            };
+            agent.probe_id = probe_id.clone().or(agent.probe_id);
+            agent.token = token.clone().or(agent.token);
+            agent.ca_cert = ca_cert.clone().or(agent.ca_cert);
+            if cert.is_some() {
+                (agent.cert, agent.key) = (cert.clone(), key.clone());
+            }
+            agent.run(daemons(&cli, check, &profiles)?).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Collector {
+            address,
+            grpc_address,
+            database,
+            tokens,
+            tls_cert,
+            tls_key,
+            client_ca,
+            flap_after,
+            public_key,
+        } => {
+            let mut collector = load_file(&cli)?.collector.unwrap_or_default();
+            collector.address = address.unwrap_or(collector.address);
+            collector.grpc_address = grpc_address.unwrap_or(collector.grpc_address);
+            collector.database = database.clone().unwrap_or(collector.database);
+            collector.tokens.extend(tokens.iter().cloned());
+            if tls_cert.is_some() {
+                (collector.tls_cert, collector.tls_key) = (tls_cert.clone(), tls_key.clone());
+            }
+            collector.client_ca = client_ca.clone().or(collector.client_ca);
+            collector.flap_after = flap_after.unwrap_or(collector.flap_after);
+            collector.public_keys.extend(public_key.iter().cloned());
+            collector.run().await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::History { since, verdict } => {
+            commands::show_history(&cli, &load_file(&cli)?, *since, *verdict)
+        }
+        Command::Report { html, since, check } => {
+            commands::report(&merge(check, load_file(&cli)?)?, html, *since).await
+        }
+        Command::Path {
+            beacon,
+            rounds,
+            check,
+        } => commands::path(&cli, &merge(check, load_file(&cli)?)?, beacon, *rounds).await,
+        Command::Atlas {
+            beacon,
+            asn,
+            probes,
+            probe_count,
+            atlas_key,
+            wait,
+            check,
+        } => {
+            let probes = match asn {
+                Some(asn) => Probes::Asn {
+                    asn: *asn,
+                    count: *probe_count,
+                },
+                None => Probes::Ids(probes.clone()),
+            };
+            let mut file = merge(check, load_file(&cli)?)?;
+            if let Some(key) = atlas_key {
+                let url = file.atlas.take().map_or_else(
+                    || Url::parse(atlas::DEFAULT_URL).unwrap(),
+                    |atlas| atlas.url,
+                );
+                file.atlas = Some(Atlas {
+                    key: key.clone(),
+                    url,
+                });
+            }
+            commands::atlas(&cli, &file, beacon, &probes, *wait).await
+        }
+        Command::Compare {
+            asn,
+            apnic_url,
+            check,
+        } => commands::compare(&cli, &merge(check, load_file(&cli)?)?, *asn, apnic_url).await,
+        Command::Batch {
+            input,
+            concurrency,
+            check,
+        } => {
+            commands::batch(
+                &cli,
+                check,
+                &merge(check, load_file(&cli)?)?,
+                input,
+                *concurrency,
+            )
+            .await
+        }
+        Command::Validate {
+            prefix,
+            asn,
+            source,
+            rpki_url,
+        } => {
+            let source = source.unwrap_or_default();
+            commands::validate(&cli, source, rpki_url.as_ref(), *prefix, *asn).await
+        }
+        Command::Verify { file, public_key } => commands::verify(&cli, file, public_key),
+        Command::Rtr {
+            server,
+            rtr_timeout,
+            command,
+        } => commands::rtr(&cli, server, *rtr_timeout, command).await,
+        Command::Beacons { command } => match command {
+            BeaconsCommand::List => commands::list_beacons(&cli, &load_file(&cli)?),
+        },
+        Command::Config { command } => match command {
+            ConfigCommand::Show {
+                show_secrets,
+                check,
+            } => commands::show_config(&cli, merge(check, load_file(&cli)?)?, *show_secrets),
+        },
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Man { dir } => {
+            match dir {
+                Some(dir) => clap_mangen::generate_to(Cli::command(), dir).with_context(|| {
+                    format!("failed to write the man pages to {}", dir.display())
+                })?,
+                None => clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())?,
+            }
+            Ok(ExitCode::SUCCESS)
+        }
     }
+}
 
+fn open_history(file: &ConfigFile) -> Result<Option<History>> {
+    match &file.history_db {
+        Some(path) => Ok(Some(History::open(path)?)),
+        None => Ok(None),
+    }
+}
+
+fn exporters(cli: &Cli, file: &ConfigFile) -> Vec<Exporter> {
+    let mut exporters = file.exporters();
+    if cli.otlp {
+        exporters.push(Exporter::Otlp(Otlp::new()));
+    }
+    exporters
+}
+
+fn ping(file: &ConfigFile) -> Option<Ping> {
+    file.ping_url.clone().map(Ping::new)
+}
+
+async fn send_ping(client: &Client, ping: Option<&Ping>, success: bool) {
+    if let Some(ping) = ping {
+        if let Err(e) = ping.send(client, success).await {
+            error!("Failed to ping {}: {:#}", ping.url, e);
+        }
+    }
+}
+
+async fn check(
+    cli: &Cli,
+    args: &CheckArgs,
+    profiles: &[(Option<String>, ConfigFile)],
+) -> Result<ExitCode> {
+    if profiles.iter().any(|(_, file)| file.interval.is_some()) {
+        let daemons = daemons(cli, args, profiles)?;
+        if args.tui {
+            let [daemon] = <[Daemon; 1]>::try_from(daemons)
+                .map_err(|_| anyhow!("--tui requires a single profile"))?;
+            tui::run(daemon).await?;
+        } else {
+            try_join_all(daemons.into_iter().map(|daemon| {
+                // The first result, then the verdict changes reported by the flap damping
+                let mut first = true;
+                daemon.run(move |result, transition| {
+                    if args.changes_only && !std::mem::take(&mut first) && transition.is_none() {
+                        return Ok(());
+                    }
+                    print_result(cli, result)
+                })
+            }))
+            .await?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.tui {
+        bail!("--tui requires --interval");
+    }
+
+    // The exit code is the one of the worst verdict of the profiles
+    let mut worst = EXIT_ENFORCED;
+    for (profile, file) in profiles {
+        let span = match profile {
+            Some(profile) => info_span!("profile", name = %profile),
+            None => Span::none(),
+        };
+        let code = match check_once(cli, args, profile.as_deref(), file)
+            .instrument(span)
+            .await
+        {
+            Ok(code) => code,
+            Err(e) if profiles.len() > 1 => {
+                error!(profile = profile.as_deref(), "{:#}", e);
+                EXIT_ERROR
+            }
+            Err(e) => return Err(e),
+        };
+        worst = worst_code(cli, worst, code);
+    }
+    Ok(ExitCode::from(worst))
+}
+
+/// Exit code of the worst of two verdicts.
+fn worst_code(cli: &Cli, a: u8, b: u8) -> u8 {
+    match cli.format {
+        Format::Nagios => a.max(b),
+        _ => [
+            EXIT_ENFORCED,
+            EXIT_INCONCLUSIVE,
+            EXIT_NOT_ENFORCED,
+            EXIT_ERROR,
+        ]
+        .into_iter()
+        .rfind(|code| [a, b].contains(code))
+        .unwrap_or(b),
+    }
+}
+
+/// Check a profile once, returning the exit code of its result.
+async fn check_once(
+    cli: &Cli,
+    args: &CheckArgs,
+    profile: Option<&str>,
+    file: &ConfigFile,
+) -> Result<u8> {
+    let history = open_history(file)?;
+    let previous = match (&history, args.changes_only) {
+        (Some(history), true) => history.last(profile)?,
+        (None, true) => bail!("--changes-only requires a history database"),
+        (_, false) => None,
+    };
+    let checker = RovChecker::new(load_config(file)?)?;
+    let ping = ping(file);
+    let client = Client::new();
+    let result = match checker.check().await {
+        Ok(result) => result,
+        Err(e) => {
+            send_ping(&client, ping.as_ref(), false).await;
+            return Err(e);
+        }
+    };
+    let changed = match &previous {
+        Some(previous) => previous.result.verdict != result.verdict,
+        None => true,
+    };
+    if !args.changes_only || changed {
+        print_result(cli, &result)?;
+    }
+    if let (Some(previous), true) = (&previous, changed) {
+        let event = Event {
+            transition: Some(Transition {
+                from: previous.result.verdict,
+                to: result.verdict,
+                at: result.finished_at,
+            }),
+            failures: u32::from(result.verdict != Verdict::Enforced),
+            result: result.clone(),
+        };
+        notify(&client, &file.notifiers(), &event).await;
+    }
+    send_ping(&client, ping.as_ref(), result.verdict == Verdict::Enforced).await;
+    let exporters = exporters(cli, file);
+    export(&client, &exporters, &result).await;
+    flush(&client, &exporters).await;
+    if let Some(history) = &history {
+        history.insert(&result)?;
+    }
+
+    if args.changes_only && !changed {
+        return Ok(EXIT_ENFORCED);
+    }
+    Ok(exit_code(cli, &result))
+}
+
+/// Print a value as JSON or YAML, returning whether the format is one of them, the other
+/// formats being left to the caller to print as text.
+fn print_structured(cli: &Cli, value: &impl Serialize) -> Result<bool> {
+    match cli.format {
+        Format::Json => println!("{}", serde_json::to_string(value)?),
+        Format::Yaml => print!("{}", serde_norway::to_string(value)?),
+        Format::Text | Format::Nagios | Format::Checkmk | Format::Influx | Format::Csv => {
+            return Ok(false)
+        }
+    }
+    Ok(true)
+}
+
+fn print_result(cli: &Cli, result: &RovResult) -> Result<()> {
+    if cli.quiet {
+        return Ok(());
+    }
+    match cli.format {
+        Format::Text => {
+            if result.pairs.len() > 1 {
+                for pair in &result.pairs {
+                    info!(
+                        beacon = %pair.name,
+                        family = pair.family.map(display),
+                        uplink = pair.uplink.as_deref().map(display),
+                        verdict = %pair.verdict,
+                        "{}: {}: {}",
+                        pair.label(),
+                        pair.verdict,
+                        pair.reason
+                    );
+                }
+            }
+            for pair in &result.pairs {
+                for warning in &pair.warnings {
+                    warn!(beacon = %pair.name, "{}: {}", pair.label(), warning);
+                }
+            }
+            for invalidity in &result.invalidities {
+                info!(
+                    invalidity = %invalidity.invalidity,
+                    verdict = %invalidity.verdict,
+                    "{}: {}: {}",
+                    invalidity.invalidity.routes(),
+                    invalidity.verdict,
+                    invalidity.reason
+                );
+            }
+            for pair in &result.pairs {
+                for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                    for address in &beacon.addresses {
+                        info!(
+                            beacon = %pair.name,
+                            validity = %validity,
+                            ip = %address.ip,
+                            reachable = address.success,
+                            "{} {} beacon at {}: {}",
+                            pair.label(),
+                            validity,
+                            address.ip,
+                            if address.success { "reachable" } else { "unreachable" }
+                        );
+                    }
+                    if let Some(traceroute) = &beacon.traceroute {
+                        let hops = traceroute
+                            .hops
+                            .iter()
+                            .map(|hop| hop.ip.map_or("*".to_string(), |ip| ip.to_string()))
+                            .collect::<Vec<_>>();
+                        info!(
+                            beacon = %pair.name,
+                            validity = %validity,
+                            reached = traceroute.reached,
+                            "{} {} beacon path: {}{}",
+                            pair.label(),
+                            validity,
+                            hops.join(" > "),
+                            match &traceroute.error {
+                                Some(e) => format!(" ({})", e),
+                                None if traceroute.reached => String::new(),
+                                None => " (target not reached)".to_string(),
+                            }
+                        );
+                    }
+                    if let Some(info) = &beacon.prefix_info {
+                        let routes = match (&info.error, info.prefix) {
+                            (Some(e), _) => format!("lookup failed ({})", e),
+                            (None, None) => "not announced".to_string(),
+                            (None, Some(prefix)) => {
+                                let origins = info.origins.iter().map(|origin| {
+                                    let name = origin.name.as_deref().unwrap_or("-");
+                                    match origin.validity {
+                                        Some(validity) => {
+                                            format!(
+                                                "AS{} ({}, RPKI {})",
+                                                origin.asn, name, validity
+                                            )
+                                        }
+                                        None => format!("AS{} ({})", origin.asn, name),
+                                    }
+                                });
+                                format!(
+                                    "{} from {}",
+                                    prefix,
+                                    origins.collect::<Vec<_>>().join(", ")
+                                )
+                            }
+                        };
+                        debug!(
+                            beacon = %pair.name,
+                            validity = %validity,
+                            prefix = info.prefix.map(display),
+                            "{} {} beacon prefix on {}: {}",
+                            pair.label(),
+                            validity,
+                            info.source,
+                            routes
+                        );
+                    }
+                    if let Some(rpki) = &beacon.rpki {
+                        let routes = match &rpki.error {
+                            Some(e) => format!("verification failed ({})", e),
+                            None if rpki.validations.is_empty() => "not announced".to_string(),
+                            None => rpki
+                                .validations
+                                .iter()
+                                .map(|v| format!("{} AS{} {}", v.prefix, v.asn, v.validity))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        };
+                        info!(
+                            beacon = %pair.name,
+                            validity = %validity,
+                            "{} {} beacon RPKI: {}",
+                            pair.label(),
+                            validity,
+                            routes
+                        );
+                    }
+                }
+            }
+            for pair in &result.pairs {
+                if let Some(ris) = &pair.ris {
+                    let mut routes = match (&ris.error, &ris.prefix) {
+                        (Some(e), _) => format!("RIS lookup failed: {}", e),
+                        (None, Some(prefix)) if ris.announced() => {
+                            format!("{} seen by {} RIS peers", prefix, ris.peers)
+                        }
+                        (None, _) => "not announced according to RIS".to_string(),
+                    };
+                    if !ris.upstreams.is_empty() {
+                        let upstreams = ris.upstreams.iter().map(|asn| format!("AS{}", asn));
+                        routes += &format!(
+                            ", propagated by {}",
+                            upstreams.collect::<Vec<_>>().join(", ")
+                        );
+                    }
+                    info!(
+                        beacon = %pair.name,
+                        prefix = ris.prefix.as_deref().map(display),
+                        peers = ris.peers,
+                        "{} invalid beacon prefix: {}",
+                        pair.label(),
+                        routes
+                    );
+                }
+            }
+            for pair in &result.pairs {
+                if let Some(control) = &pair.control {
+                    let fetch = match (control.reachable, &control.error) {
+                        (Some(true), _) => "reachable".to_string(),
+                        (Some(false), Some(e)) => format!("unreachable ({})", e),
+                        (Some(false), None) => "unreachable".to_string(),
+                        (None, e) => format!("lookup failed ({})", e.as_deref().unwrap_or("-")),
+                    };
+                    info!(
+                        beacon = %pair.name,
+                        reachable = control.reachable,
+                        "{} invalid beacon from the control vantage: {}",
+                        pair.label(),
+                        fetch
+                    );
+                }
+            }
+            for pair in &result.pairs {
+                for looking_glass in &pair.looking_glasses {
+                    let routes = [
+                        ("valid", &looking_glass.valid),
+                        ("invalid", &looking_glass.invalid),
+                    ]
+                    .map(|(validity, routes)| match &routes.error {
+                        Some(e) => format!("{} beacon lookup failed ({})", validity, e),
+                        None if routes.filtered > 0 => format!(
+                            "{} routes to the {} beacon, {} filtered",
+                            routes.routes, validity, routes.filtered
+                        ),
+                        None => format!("{} routes to the {} beacon", routes.routes, validity),
+                    });
+                    info!(
+                        beacon = %pair.name,
+                        looking_glass = %looking_glass.name,
+                        invalid_seen = looking_glass.invalid.seen(),
+                        "{} looking glass {}: {}",
+                        pair.label(),
+                        looking_glass.name,
+                        routes.join(", ")
+                    );
+                }
+            }
+            for uplink in &result.uplinks {
+                info!(
+                    uplink = %uplink.uplink,
+                    verdict = %uplink.verdict,
+                    "{}: {}: {}",
+                    uplink.uplink,
+                    uplink.verdict,
+                    uplink.reason
+                );
+            }
+            if let Some(preflight) = result.preflight.as_ref().filter(|p| !p.success) {
+                info!(
+                    url = %preflight.url,
+                    status = preflight.status,
+                    "Preflight failed: {}",
+                    preflight.error.as_deref().unwrap_or_default()
+                );
+            }
+            if let Some(http3) = &result.http3 {
+                info!(
+                    protocol = "http3",
+                    verdict = %http3.verdict,
+                    "HTTP/3: {}: {}",
+                    http3.verdict,
+                    http3.reason
+                );
+            }
+            if let Some(asn_info) = &result.asn_info {
+                match &asn_info.error {
+                    Some(e) => info!(
+                        asn = asn_info.asn,
+                        "AS{} lookup failed: {}", asn_info.asn, e
+                    ),
+                    None => info!(
+                        asn = asn_info.asn,
+                        holder = asn_info.holder.as_deref().map(display),
+                        country = asn_info.country.as_deref().map(display),
+                        peeringdb = asn_info.peeringdb.as_ref().map(|peeringdb| peeringdb.id),
+                        "AS{} looked up",
+                        asn_info.asn
+                    ),
+                }
+            }
+            if let Some(radar) = &result.radar {
+                let classification = radar.classification.as_deref().unwrap_or("unclassified");
+                match (&radar.error, radar.agrees) {
+                    (Some(e), _) => warn!(asn = radar.asn, "Cloudflare Radar lookup failed: {}", e),
+                    (None, Some(false)) => warn!(
+                        asn = radar.asn,
+                        classification,
+                        "Cloudflare Radar classifies AS{} as {}, disagreeing with the {} verdict",
+                        radar.asn,
+                        classification,
+                        result.verdict
+                    ),
+                    (None, _) => info!(
+                        asn = radar.asn,
+                        classification,
+                        "Cloudflare Radar classifies AS{} as {}",
+                        radar.asn,
+                        classification
+                    ),
+                }
+            }
+            if let Some(confidence) = &result.confidence {
+                let factors = match confidence.factors.as_slice() {
+                    [] => String::new(),
+                    factors => format!(": {}", factors.join(", ")),
+                };
+                info!(
+                    confidence = confidence.score,
+                    "Confidence {}{}", confidence, factors
+                );
+            }
+            let via = result
+                .network()
+                .map(|network| format!(" via {}", network))
+                .unwrap_or_default();
+            let duration = (result.finished_at - result.started_at).num_milliseconds();
+            match result.families.as_slice() {
+                [family] => info!(
+                    verdict = %result.verdict,
+                    asn = result.asn(),
+                    vrf = result.vrf.as_deref().map(display),
+                    duration_ms = duration,
+                    "{} ({}){}: {}",
+                    result.verdict,
+                    family.family,
+                    via,
+                    result.reason
+                ),
+                families => {
+                    for family in families {
+                        info!(
+                            family = %family.family,
+                            verdict = %family.verdict,
+                            "{}: {}: {}",
+                            family.family,
+                            family.verdict,
+                            family.reason
+                        );
+                    }
+                    info!(
+                        verdict = %result.verdict,
+                        asn = result.asn(),
+                        vrf = result.vrf.as_deref().map(display),
+                        duration_ms = duration,
+                        "{}{}: {}",
+                        result.verdict,
+                        via,
+                        result.reason
+                    )
+                }
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string(result)?),
+        // As a stream of YAML documents in watch mode
+        Format::Yaml => print!("---\n{}", serde_norway::to_string(result)?),
+        Format::Nagios => println!("{}", nagios::output(result)),
+        Format::Checkmk => println!("{}", checkmk::output(result)),
+        Format::Influx => println!("{}", influx::lines(result).join("\n")),
+        Format::Csv => {
+            // Only print the header before the first result in watch mode
+            static HEADER: AtomicBool = AtomicBool::new(true);
+            let header = HEADER.swap(false, Ordering::Relaxed);
+            print!("{}", csv::output(result, header)?);
+        }
+    }
     Ok(())
 }
+
+fn exit_code(cli: &Cli, result: &RovResult) -> u8 {
+    if cli.format == Format::Nagios {
+        return State::of(result).code();
+    }
+    match result.verdict {
+        Verdict::Enforced => EXIT_ENFORCED,
+        Verdict::NotEnforced => EXIT_NOT_ENFORCED,
+        Verdict::Inconclusive => EXIT_INCONCLUSIVE,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Both the aws-lc-rs and ring providers are built in through the dependencies,
+    // so rustls cannot pick one on its own
+    let _ = tokio_rustls::rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+    let cli = match Cli::try_parse_with_default() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            if e.use_stderr() {
+                return ExitCode::from(EXIT_ERROR);
+            }
+            return ExitCode::SUCCESS;
+        }
+    };
+
+    let telemetry = match set_tracing(&cli) {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    let (format, quiet) = (cli.format, cli.quiet);
+    let code = match run(cli).await {
+        Ok(code) => code,
+        Err(_) if quiet => ExitCode::from(EXIT_ERROR),
+        Err(e) if format == Format::Nagios => {
+            println!("{}", nagios::error(&e));
+            ExitCode::from(State::Unknown.code())
+        }
+        Err(e) if format == Format::Checkmk => {
+            println!("{}", checkmk::error(&e));
+            ExitCode::from(EXIT_ERROR)
+        }
+        Err(e) => {
+            error!("{:#}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    };
+
+    if let Some(otel) = telemetry.otel {
+        if let Err(e) = otel.shutdown() {
+            error!("Failed to export telemetry: {:#}", e);
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn layering() {
+        let path = std::env::temp_dir().join(format!(
+            "rovcheck-test-layering-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "timings = true\nall_ips = true\nhttp3 = true\ntrials = 2\n",
+        )
+        .unwrap();
+        std::env::set_var("ROVCHECK_HTTP3", "false");
+        std::env::set_var("ROVCHECK_ALL_IPS", "true");
+        std::env::set_var("ROVCHECK_TRIALS", "3");
+        let line = format!(
+            "rovcheck check -c {} --all-ips=false --trials 4 --strict",
+            path.display()
+        );
+        let cli = Cli::try_parse_from(line.split_whitespace()).unwrap();
+        for name in ["ROVCHECK_HTTP3", "ROVCHECK_ALL_IPS", "ROVCHECK_TRIALS"] {
+            std::env::remove_var(name);
+        }
+        let Command::Check(check) = &cli.command else {
+            unreachable!();
+        };
+        let config = load_config(&merge(check, load_file(&cli).unwrap()).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // The defaults, overridden by the file, the environment and the command line in turn
+        assert!(!config.dual_stack);
+        assert!(config.timings);
+        assert!(!config.http3);
+        assert!(!config.all_ips);
+        assert_eq!(config.trials, 4);
+        assert!(config.strict);
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// How the response of an `http` beacon is told successful, for the beacons not answering the
+/// isbgpsafeyet JSON. All the given conditions must hold.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Matcher {
+    /// Expected HTTP status codes [default: any success]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<u16>,
+    /// Expected body, exactly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Regular expression the body must match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex: Option<Pattern>,
+    /// JSONPath of a value the JSON body must have, e.g. `$.status` or `$.results[0].ok`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_path: Option<JsonPath>,
+    /// Expected value at the JSONPath [default: any value]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+/// Regular expression, serialized as its pattern.
+#[derive(Clone, Debug)]
+pub struct Pattern(pub Regex);
+
+impl Serialize for Pattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Pattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Subset of JSONPath selecting a single value: member names (`.name` or `['name']`) and array
+/// indexes (`[0]`) from the root `$`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct JsonPath {
+    path: String,
+    segments: Vec<Segment>,
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(mut rest) = s.strip_prefix('$') else {
+            bail!("invalid JSONPath `{}`, expected to start with `$`", s);
+        };
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(member) = rest.strip_prefix('.') {
+                let end = member.find(['.', '[']).unwrap_or(member.len());
+                if end == 0 {
+                    bail!("invalid JSONPath `{}`, empty member name", s);
+                }
+                segments.push(Segment::Key(member[..end].to_string()));
+                rest = &member[end..];
+            } else if let Some(bracket) = rest.strip_prefix('[') {
+                let end = bracket
+                    .find(']')
+                    .with_context(|| format!("invalid JSONPath `{}`, unclosed `[`", s))?;
+                let selector = &bracket[..end];
+                let quoted = selector
+                    .strip_prefix('\'')
+                    .and_then(|key| key.strip_suffix('\''))
+                    .or_else(|| {
+                        selector
+                            .strip_prefix('"')
+                            .and_then(|key| key.strip_suffix('"'))
+                    });
+                segments.push(match quoted {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(selector.parse().with_context(|| {
+                        format!("invalid JSONPath `{}`, invalid index `{}`", s, selector)
+                    })?),
+                });
+                rest = &bracket[end + 1..];
+            } else {
+                bail!("invalid JSONPath `{}` at `{}`", s, rest);
+            }
+        }
+        Ok(JsonPath {
+            path: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for JsonPath {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<JsonPath> for String {
+    fn from(path: JsonPath) -> Self {
+        path.path
+    }
+}
+
+impl std::fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)
+    }
+}
+
+impl JsonPath {
+    /// Value selected in a JSON document, if any.
+    pub fn select<'a>(&self, document: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(document, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.get(index),
+            })
+    }
+}
+
+impl Matcher {
+    /// Check the status and body of a response, telling why it does not match.
+    pub fn check(&self, status: u16, body: &[u8]) -> Result<()> {
+        if self.status.is_empty() && !(200..400).contains(&status) {
+            bail!("HTTP status {}", status);
+        }
+        if !self.status.is_empty() && !self.status.contains(&status) {
+            bail!("unexpected HTTP status {}", status);
+        }
+        let text = String::from_utf8_lossy(body);
+        if let Some(expected) = &self.body {
+            if text != expected.as_str() {
+                bail!("unexpected body");
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.0.is_match(&text) {
+                bail!("body not matching `{}`", regex.0.as_str());
+            }
+        }
+        if let Some(path) = &self.json_path {
+            let document: Value = serde_json::from_slice(body).context("invalid JSON body")?;
+            match (path.select(&document), &self.value) {
+                (None, _) => bail!("no value at {}", path),
+                (Some(value), Some(expected)) if value != expected => {
+                    bail!("unexpected value {} at {}", value, path)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(s: &str) -> JsonPath {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_json_paths() {
+        assert!(path("$").segments.is_empty());
+        assert_eq!(
+            path("$.results[0]['ok']").segments,
+            [
+                Segment::Key("results".to_string()),
+                Segment::Index(0),
+                Segment::Key("ok".to_string())
+            ]
+        );
+        assert_eq!(
+            path("$[\"a.b\"]").segments,
+            [Segment::Key("a.b".to_string())]
+        );
+        assert_eq!(path("$.status").to_string(), "$.status");
+        for invalid in ["status", "$.", "$..a", "$[0", "$[a]", "$[-1]", "$a"] {
+            assert!(invalid.parse::<JsonPath>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn select_values() {
+        let document = json!({"results": [{"ok": true}], "a.b": 1});
+        assert_eq!(
+            path("$.results[0].ok").select(&document),
+            Some(&json!(true))
+        );
+        assert_eq!(path("$['a.b']").select(&document), Some(&json!(1)));
+        assert_eq!(path("$").select(&document), Some(&document));
+        assert_eq!(path("$.results[1]").select(&document), None);
+        assert_eq!(path("$.results.ok").select(&document), None);
+    }
+
+    #[test]
+    fn check_responses() {
+        let any = Matcher::default();
+        assert!(any.check(200, b"").is_ok());
+        assert!(any.check(301, b"").is_ok());
+        assert!(any.check(404, b"").is_err());
+
+        let matcher = Matcher {
+            status: vec![404],
+            regex: Some(Pattern(Regex::new("^not (found|here)$").unwrap())),
+            ..Matcher::default()
+        };
+        assert!(matcher.check(404, b"not here").is_ok());
+        assert!(matcher.check(200, b"not here").is_err());
+        assert!(matcher.check(404, b"found").is_err());
+
+        let matcher = Matcher {
+            body: Some("ok".to_string()),
+            ..Matcher::default()
+        };
+        assert!(matcher.check(200, b"ok").is_ok());
+        assert!(matcher.check(200, b"ok\n").is_err());
+
+        let matcher = Matcher {
+            json_path: Some(path("$.status")),
+            value: Some(json!("valid")),
+            ..Matcher::default()
+        };
+        assert!(matcher.check(200, br#"{"status": "valid"}"#).is_ok());
+        let error = matcher.check(200, br#"{"status": "invalid"}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected value \"invalid\" at $.status"
+        );
+        assert!(matcher.check(200, br#"{"state": "valid"}"#).is_err());
+        assert!(matcher.check(200, b"valid").is_err());
+        // Any value without an expected one
+        let matcher = Matcher {
+            value: None,
+            ..matcher
+        };
+        assert!(matcher.check(200, br#"{"status": null}"#).is_ok());
+    }
+
+    #[test]
+    fn deserialize_matchers() {
+        let matcher: Matcher =
+            toml::from_str("status = [200]\nregex = \"ok\"\njson_path = \"$.a[0]\"").unwrap();
+        assert_eq!(matcher.status, [200]);
+        assert!(toml::from_str::<Matcher>("regex = \"(\"").is_err());
+        assert!(toml::from_str::<Matcher>("json_path = \"a\"").is_err());
+        assert!(toml::from_str::<Matcher>("state = 200").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// Path of a network namespace: a name as created by `ip netns add`,
+/// or the path of a namespace file such as `/proc/<pid>/ns/net`.
+fn path(netns: &str) -> PathBuf {
+    if netns.contains('/') {
+        PathBuf::from(netns)
+    } else {
+        Path::new("/run/netns").join(netns)
+    }
+}
+
+/// Move the current thread into a network namespace, the sockets it creates then belonging to it.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn enter(netns: &str) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::File::open(path(netns))
+        .with_context(|| format!("failed to open network namespace {}", netns))?;
+    // SAFETY: the file descriptor is valid for the duration of the call
+    if unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("failed to enter network namespace {}", netns));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn enter(netns: &str) -> Result<()> {
+    anyhow::bail!(
+        "network namespace {} ({}) is not supported on this platform",
+        netns,
+        path(netns).display()
+    );
+}
+
+/// Run a future on a dedicated thread inside a network namespace,
+/// the rest of the process staying in its own namespace.
+pub async fn run<F, T>(netns: &str, future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let netns = netns.to_string();
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    std::thread::Builder::new()
+        .name(format!("netns-{}", netns))
+        .spawn(move || {
+            let result = enter(&netns).and_then(|()| {
+                // Sockets are created by the thread polling the future, and the threads of the
+                // blocking pool used for name resolution inherit the namespace of this thread
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(future)
+            });
+            let _ = sender.send(result);
+        })?;
+    receiver
+        .await
+        .context("network namespace thread stopped unexpectedly")?
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{debug, error};
+use url::Url;
+
+use crate::beacon::RetryPolicy;
+use crate::checker::RovResult;
+use crate::watcher::Transition;
+
+pub mod discord;
+pub mod email;
+pub mod ping;
+pub mod slack;
+pub mod telegram;
+pub mod webhook;
+
+pub use discord::Discord;
+pub use email::Email;
+pub use ping::Ping;
+pub use slack::Slack;
+pub use telegram::Telegram;
+pub use webhook::Webhook;
+
+/// The result of a daemon check, along with the verdict transition it triggered.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub transition: Option<Transition>,
+    /// Number of consecutive checks, up to this one, which verdict was not ENFORCED
+    pub failures: u32,
+    pub result: RovResult,
+}
+
+impl Event {
+    /// Human-readable summary of the event.
+    pub fn summary(&self) -> String {
+        let asn = match self.result.asn() {
+            Some(asn) => format!(" via AS{}", asn),
+            None => String::new(),
+        };
+        match &self.transition {
+            Some(transition) => format!(
+                "ROV verdict changed from {} to {}{}: {}",
+                transition.from, transition.to, asn, self.result.reason
+            ),
+            None => format!(
+                "ROV verdict {} for {} consecutive checks{}: {}",
+                self.result.verdict, self.failures, asn, self.result.reason
+            ),
+        }
+    }
+
+    /// Render a template, replacing the `{{from}}`, `{{to}}`, `{{at}}`, `{{reason}}`,
+    /// `{{asn}}`, `{{failures}}`, `{{summary}}` and `{{result}}` (JSON) placeholders.
+    pub fn render(&self, template: &str) -> Result<String> {
+        let asn = self
+            .result
+            .asn()
+            .map(|asn| asn.to_string())
+            .unwrap_or_default();
+        let from = self
+            .transition
+            .as_ref()
+            .map(|transition| transition.from.to_string())
+            .unwrap_or_default();
+        let at = self
+            .transition
+            .as_ref()
+            .map_or(self.result.finished_at, |transition| transition.at);
+        Ok(template
+            .replace("{{from}}", &from)
+            .replace("{{to}}", &self.result.verdict.to_string())
+            .replace("{{at}}", &at.to_rfc3339())
+            .replace("{{failures}}", &self.failures.to_string())
+            .replace("{{reason}}", &self.result.reason)
+            .replace("{{asn}}", &asn)
+            .replace("{{summary}}", &self.summary())
+            .replace("{{result}}", &serde_json::to_string(&self.result)?))
+    }
+}
+
+/// A destination of the verdict transition notifications.
+#[derive(Clone, Debug)]
+pub enum Notifier {
+    Webhook(Webhook),
+    Slack(Slack),
+    Discord(Discord),
+    Telegram(Telegram),
+    Email(Email),
+}
+
+impl Notifier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Notifier::Webhook(_) => "webhook",
+            Notifier::Slack(_) => "slack",
+            Notifier::Discord(_) => "discord",
+            Notifier::Telegram(_) => "telegram",
+            Notifier::Email(_) => "email",
+        }
+    }
+
+    /// Whether the notifier should be notified of the event.
+    pub fn triggered(&self, event: &Event) -> bool {
+        match self {
+            Notifier::Email(email) => email.triggered(event),
+            _ => event.transition.is_some(),
+        }
+    }
+
+    pub async fn notify(&self, client: &Client, event: &Event) -> Result<()> {
+        match self {
+            Notifier::Webhook(webhook) => webhook.send(client, event).await,
+            Notifier::Slack(slack) => slack.send(client, event).await,
+            Notifier::Discord(discord) => discord.send(client, event).await,
+            Notifier::Telegram(telegram) => telegram.send(client, event).await,
+            Notifier::Email(email) => email.send(event).await,
+        }
+    }
+}
+
+/// Send an event to the notifiers it triggers, logging their failures.
+pub async fn notify(client: &Client, notifiers: &[Notifier], event: &Event) {
+    for notifier in notifiers.iter().filter(|n| n.triggered(event)) {
+        if let Err(e) = notifier.notify(client, event).await {
+            error!("Failed to notify {}: {:#}", notifier.name(), e);
+        }
+    }
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+/// Render the message text of an event, from a template or its summary.
+fn message(event: &Event, template: Option<&str>) -> Result<String> {
+    match template {
+        Some(template) => event.render(template),
+        None => Ok(event.summary()),
+    }
+}
+
+/// POST a JSON body to `url`, retrying with exponential backoff on failure.
+async fn post(client: &Client, url: &Url, body: String, retries: u32) -> Result<()> {
+    let retry = RetryPolicy {
+        retries,
+        backoff: Duration::from_secs(1),
+    };
+
+    let mut attempt = 0;
+    loop {
+        // Strip the URL from errors, as it may contain credentials (e.g. bot tokens)
+        let response = client
+            .post(url.clone())
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.without_url());
+        match response {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < retry.retries => {
+                let delay = retry.delay(attempt);
+                debug!("Notification failed ({}), retrying in {:?}", e, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
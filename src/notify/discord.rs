@@ -0,0 +1,26 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use url::Url;
+
+use super::{default_retries, message, post, Event};
+
+/// Discord channel webhook.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Discord {
+    pub webhook_url: Url,
+    /// Message template, defaults to the event summary
+    pub template: Option<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+impl Discord {
+    pub async fn send(&self, client: &Client, event: &Event) -> Result<()> {
+        let content = message(event, self.template.as_deref())?;
+        let body = json!({ "content": content }).to_string();
+        post(client, &self.webhook_url, body, self.retries).await
+    }
+}
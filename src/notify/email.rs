@@ -0,0 +1,92 @@
+use anyhow::Result;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+
+use super::{message, Event};
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Implicit TLS, usually on port 465
+    Tls,
+    /// Upgrade the connection with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// Plaintext connection, only meant for local relays
+    None,
+}
+
+/// Email sent through an SMTP server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Email {
+    /// SMTP server hostname
+    pub server: String,
+    /// SMTP server port, defaults to the port of the TLS mode
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+    /// Subject template, defaults to the verdict
+    pub subject: Option<String>,
+    /// Body template, defaults to the event summary
+    pub template: Option<String>,
+    /// Send an email on verdict transitions
+    #[serde(default = "default_on_transition")]
+    pub on_transition: bool,
+    /// Send an email after this number of consecutive checks not ENFORCED
+    pub after_failures: Option<u32>,
+}
+
+fn default_on_transition() -> bool {
+    true
+}
+
+impl Email {
+    pub fn triggered(&self, event: &Event) -> bool {
+        (self.on_transition && event.transition.is_some())
+            || self.after_failures == Some(event.failures)
+    }
+
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let mut builder = match self.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.server)?,
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.server)?
+            }
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.server),
+        };
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(builder.build())
+    }
+
+    pub async fn send(&self, event: &Event) -> Result<()> {
+        let subject = match &self.subject {
+            Some(template) => event.render(template)?,
+            None => format!("rovcheck: {}", event.result.verdict),
+        };
+        let body = message(event, self.template.as_deref())?;
+
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+
+        self.transport()?.send(builder.body(body)?).await?;
+        Ok(())
+    }
+}
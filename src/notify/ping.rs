@@ -0,0 +1,34 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use url::Url;
+
+/// Dead man's switch endpoint (e.g. healthchecks.io, Uptime Kuma), pinged after each check.
+///
+/// The URL is pinged when ROV is enforced, and its `/fail` endpoint otherwise.
+#[derive(Clone, Debug)]
+pub struct Ping {
+    pub url: Url,
+}
+
+impl Ping {
+    pub fn new(url: Url) -> Self {
+        Ping { url }
+    }
+
+    pub async fn send(&self, client: &Client, success: bool) -> Result<()> {
+        let mut url = self.url.clone();
+        if !success {
+            url.path_segments_mut()
+                .map_err(|_| anyhow!("invalid ping URL {}", self.url))?
+                .pop_if_empty()
+                .push("fail");
+        }
+        client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.without_url())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use url::Url;
+
+use super::{default_retries, message, post, Event};
+
+/// Telegram bot, sending messages to a chat through the Bot API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Telegram {
+    /// Bot token, as given by BotFather
+    pub token: String,
+    /// Chat ID (or `@channel` username) to send the messages to
+    pub chat_id: String,
+    /// Message template, defaults to the event summary
+    pub template: Option<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+impl Telegram {
+    pub async fn send(&self, client: &Client, event: &Event) -> Result<()> {
+        let text = message(event, self.template.as_deref())?;
+        let url = Url::parse(&format!(
+            "https://api.telegram.org/bot{}/sendMessage",
+            self.token
+        ))?;
+        let body = json!({ "chat_id": self.chat_id, "text": text }).to_string();
+        post(client, &url, body, self.retries).await
+    }
+}
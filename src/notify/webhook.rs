@@ -0,0 +1,36 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{default_retries, post, Event};
+
+/// Generic webhook, receiving a JSON payload on each verdict transition.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: Url,
+    /// Body template, defaults to the JSON serialized event
+    pub template: Option<String>,
+    /// Number of retries if the webhook request fails
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+impl Webhook {
+    pub fn new(url: Url) -> Self {
+        Webhook {
+            url,
+            template: None,
+            retries: default_retries(),
+        }
+    }
+
+    pub async fn send(&self, client: &Client, event: &Event) -> Result<()> {
+        let body = match &self.template {
+            Some(template) => event.render(template)?,
+            None => serde_json::to_string(event)?,
+        };
+        post(client, &self.url, body, self.retries).await
+    }
+}
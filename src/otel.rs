@@ -0,0 +1,57 @@
+use anyhow::Result;
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{MetricExporter, SpanExporter};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// OpenTelemetry providers, exporting the traces and metrics of the checks over OTLP.
+///
+/// The exporters are configured with the standard `OTEL_*` environment variables
+/// (e.g. `OTEL_EXPORTER_OTLP_ENDPOINT` and `OTEL_EXPORTER_OTLP_PROTOCOL`).
+pub struct Otel {
+    tracer: SdkTracerProvider,
+    meter: SdkMeterProvider,
+}
+
+impl Otel {
+    /// Build the providers, and install the meter provider globally.
+    pub fn init() -> Result<Self> {
+        let mut resource = Resource::builder();
+        if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+            resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+        }
+        let resource = resource.build();
+
+        let tracer = SdkTracerProvider::builder()
+            .with_batch_exporter(SpanExporter::builder().build()?)
+            .with_resource(resource.clone())
+            .build();
+        let meter = SdkMeterProvider::builder()
+            .with_periodic_exporter(MetricExporter::builder().build()?)
+            .with_resource(resource)
+            .build();
+        global::set_meter_provider(meter.clone());
+
+        Ok(Otel { tracer, meter })
+    }
+
+    /// Tracing layer exporting the spans.
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, SdkTracer>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.tracer.tracer(env!("CARGO_PKG_NAME")))
+    }
+
+    /// Flush the pending spans and metrics.
+    pub fn shutdown(self) -> Result<()> {
+        self.tracer.shutdown()?;
+        self.meter.shutdown()?;
+        Ok(())
+    }
+}
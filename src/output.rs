@@ -0,0 +1,88 @@
+use crate::checker::{PairResult, RovResult};
+use crate::verdict::Verdict;
+
+pub mod checkmk;
+pub mod csv;
+pub mod html;
+pub mod influx;
+pub mod nagios;
+
+/// Service state of monitoring systems (Nagios, Icinga, Checkmk).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl State {
+    /// State of a check result: WARNING if ROV is enforced but some beacon pairs are not.
+    pub fn of(result: &RovResult) -> Self {
+        match result.verdict {
+            Verdict::Enforced
+                if result
+                    .pairs
+                    .iter()
+                    .any(|pair| pair.verdict != Verdict::Enforced) =>
+            {
+                State::Warning
+            }
+            Verdict::Enforced => State::Ok,
+            Verdict::NotEnforced => State::Critical,
+            Verdict::Inconclusive => State::Unknown,
+        }
+    }
+
+    /// Plugin exit code of the state.
+    pub fn code(self) -> u8 {
+        match self {
+            State::Ok => 0,
+            State::Warning => 1,
+            State::Critical => 2,
+            State::Unknown => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            State::Ok => "OK",
+            State::Warning => "WARNING",
+            State::Critical => "CRITICAL",
+            State::Unknown => "UNKNOWN",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Name of a beacon pair in tables, along with the uplink it was checked through
+/// and whether it was requested over HTTP/3.
+pub fn beacon_name(pair: &PairResult) -> String {
+    let through = pair
+        .uplink
+        .clone()
+        .into_iter()
+        .chain(pair.http3.then(|| "HTTP/3".to_string()))
+        .collect::<Vec<_>>();
+    if through.is_empty() {
+        return pair.name.clone();
+    }
+    format!("{} ({})", pair.name, through.join(", "))
+}
+
+/// Metric label of a beacon request, e.g. `cloudflare_invalid_ipv6` or `cloudflare_invalid_ipv6_eth1`.
+pub fn label(pair: &PairResult, validity: &str) -> String {
+    let mut label = format!("{}_{}", pair.name, validity);
+    if let Some(family) = pair.family {
+        label.push_str(&format!("_{}", family));
+    }
+    if let Some(uplink) = &pair.uplink {
+        label.push_str(&format!("_{}", uplink));
+    }
+    if pair.http3 {
+        label.push_str("_http3");
+    }
+    label
+}
@@ -0,0 +1,104 @@
+use super::{label, State};
+use crate::checker::RovResult;
+
+/// Service name of the Checkmk local check.
+const SERVICE: &str = "ROV";
+
+/// Metric name, without the separators of the local check line.
+fn metric(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || "_-.".contains(c) => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Details of the local check line, on a single line.
+fn text(text: &str) -> String {
+    text.replace('\n', "\\n")
+}
+
+/// Checkmk local check line, with the beacon request durations and attempts as metrics.
+pub fn output(result: &RovResult) -> String {
+    let mut metrics = Vec::new();
+    for pair in &result.pairs {
+        for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+            let label = metric(&label(pair, validity));
+            metrics.push(format!(
+                "{}={:.3};;;0",
+                label,
+                beacon.duration().as_secs_f64()
+            ));
+            metrics.push(format!("{}_attempts={};;;0", label, beacon.attempts));
+        }
+    }
+    let metrics = if metrics.is_empty() {
+        "-".to_string()
+    } else {
+        metrics.join("|")
+    };
+    format!(
+        "{} \"{}\" {} {}: {}",
+        State::of(result).code(),
+        SERVICE,
+        metrics,
+        result.verdict,
+        text(&result.reason)
+    )
+}
+
+/// Checkmk local check line of a failed check.
+pub fn error(error: &anyhow::Error) -> String {
+    format!(
+        "{} \"{}\" - {}",
+        State::Unknown.code(),
+        SERVICE,
+        text(&format!("{:#}", error))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+    use crate::verdict::Verdict;
+
+    #[test]
+    fn enforced() {
+        assert_eq!(
+            output(&result()),
+            "0 \"ROV\" cloudflare_valid_ipv4=0.120;;;0|cloudflare_valid_ipv4_attempts=1;;;0|\
+             cloudflare_invalid_ipv4=3.000;;;0|cloudflare_invalid_ipv4_attempts=1;;;0 \
+             ENFORCED: valid beacon reachable, invalid beacon unreachable"
+        );
+    }
+
+    #[test]
+    fn states() {
+        let mut result = result();
+        let mut pair = result.pairs[0].clone();
+        (pair.name, pair.verdict) = ("my beacon|2".to_string(), Verdict::NotEnforced);
+        result.pairs.push(pair);
+        result.reason = "1/2 beacon pairs\nquorum".to_string();
+        let line = output(&result);
+        assert!(line.starts_with("1 \"ROV\" "));
+        assert!(line.contains("|my_beacon_2_invalid_ipv4_attempts=1;;;0 "));
+        assert!(line.ends_with(" ENFORCED: 1/2 beacon pairs\\nquorum"));
+
+        result.verdict = Verdict::NotEnforced;
+        assert!(output(&result).starts_with("2 \"ROV\" "));
+        result.verdict = Verdict::Inconclusive;
+        result.pairs.clear();
+        assert_eq!(
+            output(&result),
+            "3 \"ROV\" - INCONCLUSIVE: 1/2 beacon pairs\\nquorum"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = anyhow::anyhow!("timed out").context("check failed");
+        assert_eq!(super::error(&error), "3 \"ROV\" - check failed: timed out");
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+
+use crate::checker::RovResult;
+
+use super::label;
+
+/// CSV header of a check result, with columns for each beacon of its pairs.
+pub fn header(result: &RovResult) -> Vec<String> {
+    let mut header = ["started_at", "finished_at", "verdict", "asn", "reason"]
+        .map(String::from)
+        .to_vec();
+    for pair in &result.pairs {
+        for validity in ["valid", "invalid"] {
+            let label = label(pair, validity);
+            header.push(format!("{}_reachable", label));
+            header.push(format!("{}_latency", label));
+        }
+    }
+    header
+}
+
+/// CSV record of a check result, matching its [`header`].
+pub fn record(result: &RovResult) -> Vec<String> {
+    let mut record = vec![
+        result.started_at.to_rfc3339(),
+        result.finished_at.to_rfc3339(),
+        result.verdict.to_string(),
+        result.asn().map(|asn| asn.to_string()).unwrap_or_default(),
+        result.reason.clone(),
+    ];
+    for pair in &result.pairs {
+        for beacon in [&pair.valid, &pair.invalid] {
+            record.push(beacon.success.to_string());
+            record.push(format!("{:.3}", beacon.duration().as_secs_f64()));
+        }
+    }
+    record
+}
+
+/// Format a check result as CSV, optionally preceded by its header.
+pub fn output(result: &RovResult, with_header: bool) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if with_header {
+        writer.write_record(header(result))?;
+    }
+    writer.write_record(record(result))?;
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+
+    #[test]
+    fn with_header() {
+        assert_eq!(
+            output(&result(), true).unwrap(),
+            "started_at,finished_at,verdict,asn,reason,\
+             cloudflare_valid_ipv4_reachable,cloudflare_valid_ipv4_latency,\
+             cloudflare_invalid_ipv4_reachable,cloudflare_invalid_ipv4_latency\n\
+             2026-01-01T00:00:00+00:00,2026-01-01T00:00:03+00:00,ENFORCED,64496,\
+             \"valid beacon reachable, invalid beacon unreachable\",true,0.120,false,3.000\n"
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        let mut result = result();
+        result.reason = "invalid beacon \"reachable\"\nagain".to_string();
+        let line = output(&result, false).unwrap();
+        assert_eq!(line.lines().count(), 2);
+        assert!(line.contains(",\"invalid beacon \"\"reachable\"\"\nagain\","));
+        assert_eq!(header(&result).len(), record(&result).len());
+    }
+}
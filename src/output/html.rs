@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use url::form_urlencoded;
+
+use crate::checker::RovResult;
+use crate::history::{Entry, Summary};
+use crate::verdict::Verdict;
+
+use super::{beacon_name, label};
+
+const WIDTH: f64 = 960.0;
+const TIMELINE_HEIGHT: f64 = 32.0;
+const CHART_HEIGHT: f64 = 240.0;
+const PALETTE: &[&str] = &[
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 1000px; color: #222; }
+h1 { margin-bottom: 0.2em; }
+table { border-collapse: collapse; margin: 1em 0; width: 100%; }
+th, td { border: 1px solid #ddd; padding: 0.3em 0.6em; text-align: left; font-size: 0.9em; }
+th { background: #f5f5f5; }
+.ENFORCED { color: #2e7d32; }
+.NOT_ENFORCED { color: #c62828; }
+.INCONCLUSIVE { color: #9e9e9e; }
+.legend span { margin-right: 1.5em; }
+svg { border: 1px solid #ddd; }
+nav a, nav strong, nav button { margin-right: 1em; }
+";
+
+/// Check button of the dashboard, checking the profile shown and reloading the dashboard once
+/// the check is likely done.
+const SCRIPT: &str = "
+function check(button) {
+  button.disabled = true;
+  button.textContent = 'Checking...';
+  fetch(button.dataset.check, { method: 'POST' }).then(() => setTimeout(() => location.reload(), 5000));
+}
+";
+
+/// Time windows of the dashboard.
+pub const WINDOWS: &[&str] = &["1h", "24h", "7d", "30d"];
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn color(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Enforced => "#2e7d32",
+        Verdict::NotEnforced => "#c62828",
+        Verdict::Inconclusive => "#9e9e9e",
+    }
+}
+
+/// Horizontal position of a check on the charts, proportional to its start time.
+fn position(entries: &[Entry], index: usize) -> f64 {
+    let first = entries[0].result.started_at;
+    let span = (entries[entries.len() - 1].result.started_at - first).num_milliseconds();
+    if span == 0 {
+        return 0.0;
+    }
+    let offset = (entries[index].result.started_at - first).num_milliseconds();
+    offset as f64 / span as f64 * WIDTH
+}
+
+/// SVG strip of the verdicts, each check spanning until the next one.
+fn timeline(entries: &[Entry]) -> String {
+    let mut svg = format!(
+        r#"<svg width="{}" height="{}" xmlns="http://www.w3.org/2000/svg">"#,
+        WIDTH, TIMELINE_HEIGHT
+    );
+    for (index, entry) in entries.iter().enumerate() {
+        let x = position(entries, index);
+        let end = if index + 1 < entries.len() {
+            position(entries, index + 1)
+        } else {
+            WIDTH
+        };
+        let _ = write!(
+            svg,
+            r#"<rect x="{:.1}" y="0" width="{:.1}" height="{}" fill="{}"><title>{} {}</title></rect>"#,
+            x,
+            (end - x).max(1.0),
+            TIMELINE_HEIGHT,
+            color(entry.result.verdict),
+            entry.result.started_at.to_rfc3339(),
+            entry.result.verdict
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// SVG line chart of the beacon request latencies, with its legend.
+fn latencies(entries: &[Entry]) -> String {
+    let mut series: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        for pair in &entry.result.pairs {
+            for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                series
+                    .entry(label(pair, validity))
+                    .or_default()
+                    .push((position(entries, index), beacon.duration().as_secs_f64()));
+            }
+        }
+    }
+    let max = series
+        .values()
+        .flatten()
+        .map(|(_, latency)| *latency)
+        .fold(0.0, f64::max)
+        .max(0.001);
+
+    let mut svg = format!(
+        r#"<svg width="{}" height="{}" xmlns="http://www.w3.org/2000/svg">"#,
+        WIDTH, CHART_HEIGHT
+    );
+    let _ = write!(
+        svg,
+        r##"<text x="4" y="14" font-size="12" fill="#666">{:.3}s</text>"##,
+        max
+    );
+    let mut legend = String::from(r#"<p class="legend">"#);
+    for (index, (name, points)) in series.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        let points = points
+            .iter()
+            .map(|(x, latency)| {
+                let y = CHART_HEIGHT - latency / max * (CHART_HEIGHT - 20.0);
+                format!("{:.1},{:.1}", x, y)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = write!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+            points, color
+        );
+        let _ = write!(
+            legend,
+            r#"<span style="color: {}">&#9632; {}</span>"#,
+            color,
+            escape(name)
+        );
+    }
+    svg.push_str("</svg>");
+    legend.push_str("</p>");
+    svg + &legend
+}
+
+/// Table of the beacon results of a check.
+fn details(result: &RovResult) -> String {
+    let mut table = String::from(
+        "<table><tr><th>Beacon</th><th>Family</th><th>Verdict</th><th>Validity</th>\
+         <th>URL</th><th>Reachable</th><th>Latency</th><th>Attempts</th><th>ASN</th><th>Error</th></tr>",
+    );
+    for pair in &result.pairs {
+        for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+            let _ = write!(
+                table,
+                r#"<tr><td>{}</td><td>{}</td><td class="{}">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.3}s</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                escape(&beacon_name(pair)),
+                pair.family
+                    .map(|family| family.to_string())
+                    .unwrap_or_default(),
+                pair.verdict,
+                pair.verdict,
+                validity,
+                escape(&beacon.url),
+                if beacon.success { "yes" } else { "no" },
+                beacon.duration().as_secs_f64(),
+                beacon.attempts,
+                beacon
+                    .asn
+                    .map(|asn| format!("AS{}", asn))
+                    .unwrap_or_default(),
+                escape(beacon.error.as_deref().unwrap_or_default())
+            );
+        }
+    }
+    table.push_str("</table>");
+    table
+}
+
+/// Table of the verdict transitions.
+fn changes(entries: &[Entry]) -> String {
+    let mut table =
+        String::from("<table><tr><th>Time</th><th>From</th><th>To</th><th>Reason</th></tr>");
+    for window in entries.windows(2) {
+        let (from, to) = (&window[0].result, &window[1].result);
+        if from.verdict != to.verdict {
+            let _ = write!(
+                table,
+                r#"<tr><td>{}</td><td class="{}">{}</td><td class="{}">{}</td><td>{}</td></tr>"#,
+                to.started_at.to_rfc3339(),
+                from.verdict,
+                from.verdict,
+                to.verdict,
+                to.verdict,
+                escape(&to.reason)
+            );
+        }
+    }
+    table.push_str("</table>");
+    table
+}
+
+/// Standalone HTML report of check results: summary, verdict timeline,
+/// latency chart, verdict transitions and the beacon details of the last check.
+pub fn report(entries: &[Entry]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>rovcheck report</title>\
+         <style>{}</style></head><body><h1>rovcheck report</h1>",
+        STYLE
+    );
+    html.push_str(&content(entries));
+    html.push_str("</body></html>");
+    html
+}
+
+/// Web dashboard of the check results over `window`, one of the [`WINDOWS`], refreshed every
+/// minute and with a button checking right away. With several `profiles`, the results can be
+/// filtered by `profile`.
+pub fn dashboard(
+    entries: &[Entry],
+    window: &str,
+    profiles: &[String],
+    profile: Option<&str>,
+) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"60\">\
+         <title>rovcheck</title><style>{}</style><script>{}</script></head><body><h1>rovcheck</h1><nav>",
+        STYLE, SCRIPT
+    );
+    let query = |pairs: &[(&str, Option<&str>)]| {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in pairs {
+            if let Some(value) = value {
+                query.append_pair(key, value);
+            }
+        }
+        match query.finish().as_str() {
+            "" => String::new(),
+            query => format!("?{}", escape(query)),
+        }
+    };
+    for option in WINDOWS {
+        match *option == window {
+            true => {
+                let _ = write!(html, "<strong>{}</strong>", option);
+            }
+            false => {
+                let _ = write!(
+                    html,
+                    r#"<a href="{}">{}</a>"#,
+                    query(&[("window", Some(option)), ("profile", profile)]),
+                    option
+                );
+            }
+        }
+    }
+    if profiles.len() > 1 {
+        html.push_str("</nav><nav>");
+        let options = std::iter::once(None).chain(profiles.iter().map(|name| Some(name.as_str())));
+        for option in options {
+            let name = escape(option.unwrap_or("all profiles"));
+            match option == profile {
+                true => {
+                    let _ = write!(html, "<strong>{}</strong>", name);
+                }
+                false => {
+                    let query = query(&[("window", Some(window)), ("profile", option)]);
+                    let _ = write!(html, r#"<a href="{}">{}</a>"#, query, name);
+                }
+            }
+        }
+    }
+    let query = query(&[("profile", profile)]);
+    let _ = write!(
+        html,
+        r#"<button data-check="check{}" onclick="check(this)">Check now</button><a href="status{}">JSON</a><a href="metrics">Metrics</a></nav>"#,
+        query, query
+    );
+    html.push_str(&content(entries));
+    html.push_str("</body></html>");
+    html
+}
+
+/// Summary, charts and details of the check results.
+fn content(entries: &[Entry]) -> String {
+    let summary = Summary::new(entries);
+    let mut html = String::new();
+    let Some(last) = entries.last() else {
+        html.push_str("<p>No check results.</p>");
+        return html;
+    };
+
+    let verdicts = summary
+        .verdicts
+        .iter()
+        .map(|(verdict, count)| {
+            format!(r#"<span class="{}">{} {}</span>"#, verdict, count, verdict)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let asn = last
+        .result
+        .network()
+        .or_else(|| last.asn.map(|asn| format!("AS{}", asn)))
+        .map(|network| format!(" via {}", escape(&network)))
+        .unwrap_or_default();
+    let _ = write!(
+        html,
+        r#"<p>Current verdict: <strong class="{}">{}</strong>{}: {}</p>
+<p>{} checks from {} to {} ({}), {} verdict changes</p>"#,
+        last.result.verdict,
+        last.result.verdict,
+        asn,
+        escape(&last.result.reason),
+        summary.checks,
+        summary
+            .first_check
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default(),
+        summary
+            .last_check
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default(),
+        verdicts,
+        summary.changes
+    );
+
+    if entries.len() > 1 {
+        let _ = write!(html, "<h2>Verdict timeline</h2>{}", timeline(entries));
+        let _ = write!(html, "<h2>Latencies</h2>{}", latencies(entries));
+        if summary.changes > 0 {
+            let _ = write!(html, "<h2>Verdict changes</h2>{}", changes(entries));
+        }
+    }
+    let _ = write!(
+        html,
+        "<h2>Last check ({})</h2>{}",
+        last.result.started_at.to_rfc3339(),
+        details(&last.result)
+    );
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashboard_of_profile() {
+        let profiles = ["vlan 10".to_string(), "a&b".to_string()];
+        let html = dashboard(&[], "24h", &profiles, Some("vlan 10"));
+        assert!(
+            html.contains(r#"data-check="check?profile=vlan+10""#),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains(r#"href="status?profile=vlan+10""#),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains(r#"href="?window=1h&amp;profile=vlan+10""#),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains(r#"href="?window=24h&amp;profile=a%26b">a&amp;b</a>"#),
+            "{}",
+            html
+        );
+
+        let html = dashboard(&[], "24h", &[], None);
+        assert!(html.contains(r#"data-check="check""#), "{}", html);
+        assert!(html.contains(r#"href="?window=7d""#), "{}", html);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+/// Escape a tag value of the line protocol.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// InfluxDB line protocol points of a check result: an overall `rovcheck`
+/// point, and a `rovcheck_beacon` point per beacon pair, tagged with the labels of the result.
+pub fn lines(result: &RovResult) -> Vec<String> {
+    let timestamp = result.finished_at.timestamp_nanos_opt().unwrap_or_default();
+    let enforced = |verdict: Verdict| (verdict == Verdict::Enforced) as u8;
+    let labels = result
+        .labels
+        .iter()
+        .map(|(name, value)| format!(",{}={}", escape(name), escape(value)))
+        .collect::<String>();
+
+    let mut lines = vec![format!(
+        "rovcheck,verdict={}{} enforced={}i,reason=\"{}\" {}",
+        result.verdict,
+        labels,
+        enforced(result.verdict),
+        result.reason.replace('\\', "\\\\").replace('"', "\\\""),
+        timestamp
+    )];
+    for pair in &result.pairs {
+        let mut tags = format!("beacon={}", escape(&pair.name));
+        if let Some(family) = pair.family {
+            tags.push_str(&format!(",family={}", family));
+        }
+        if let Some(uplink) = &pair.uplink {
+            tags.push_str(&format!(",uplink={}", escape(uplink)));
+        }
+        if pair.http3 {
+            tags.push_str(",protocol=http3");
+        }
+        tags.push_str(&labels);
+        lines.push(format!(
+            "rovcheck_beacon,{} enforced={}i,reachable_valid={}i,reachable_invalid={}i,latency_valid={:.3},latency_invalid={:.3} {}",
+            tags,
+            enforced(pair.verdict),
+            pair.valid.success as u8,
+            pair.invalid.success as u8,
+            pair.valid.duration().as_secs_f64(),
+            pair.invalid.duration().as_secs_f64(),
+            timestamp
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+
+    #[test]
+    fn points() {
+        assert_eq!(
+            lines(&result()),
+            [
+                "rovcheck,verdict=ENFORCED enforced=1i,\
+                 reason=\"valid beacon reachable, invalid beacon unreachable\" \
+                 1767225603000000000",
+                "rovcheck_beacon,beacon=cloudflare,family=ipv4 enforced=1i,\
+                 reachable_valid=1i,reachable_invalid=0i,latency_valid=0.120,latency_invalid=3.000 \
+                 1767225603000000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        let mut result = result();
+        result.verdict = Verdict::NotEnforced;
+        result.reason = r#"invalid beacon "reachable" \ again"#.to_string();
+        result
+            .labels
+            .insert("site name".to_string(), "paris,fr=1".to_string());
+        result.pairs[0].name = "my beacon".to_string();
+        result.pairs[0].uplink = Some("eth0 backup".to_string());
+        result.pairs[0].http3 = true;
+        let lines = lines(&result);
+        assert_eq!(
+            lines[0],
+            "rovcheck,verdict=NOT_ENFORCED,site\\ name=paris\\,fr\\=1 enforced=0i,\
+             reason=\"invalid beacon \\\"reachable\\\" \\\\ again\" 1767225603000000000"
+        );
+        assert!(lines[1].starts_with(
+            "rovcheck_beacon,beacon=my\\ beacon,family=ipv4,uplink=eth0\\ backup,\
+             protocol=http3,site\\ name=paris\\,fr\\=1 enforced=1i,"
+        ));
+    }
+}
@@ -0,0 +1,87 @@
+use super::{label, State};
+use crate::checker::RovResult;
+
+/// Text of the output, the pipe separating the perfdata.
+fn text(text: &str) -> String {
+    text.replace('|', "/")
+}
+
+/// Nagios plugin output line, with the beacon request durations and attempts as perfdata.
+pub fn output(result: &RovResult) -> String {
+    let mut perfdata = Vec::new();
+    for pair in &result.pairs {
+        for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+            // Quotes are doubled in the quoted labels
+            let label = label(pair, validity).replace('\'', "''");
+            perfdata.push(format!(
+                "'{}'={:.3}s;;;0",
+                label,
+                beacon.duration().as_secs_f64()
+            ));
+            perfdata.push(format!("'{}_attempts'={};;;0", label, beacon.attempts));
+        }
+    }
+    let via = result
+        .network()
+        .map(|network| format!(" via {}", network))
+        .unwrap_or_default();
+    format!(
+        "ROV {} - {}{}: {} | {}",
+        State::of(result),
+        result.verdict,
+        text(&via),
+        text(&result.reason),
+        perfdata.join(" ")
+    )
+}
+
+/// Nagios plugin output line of a failed check.
+pub fn error(error: &anyhow::Error) -> String {
+    format!("ROV {} - {}", State::Unknown, text(&format!("{:#}", error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+    use crate::verdict::Verdict;
+
+    #[test]
+    fn enforced() {
+        assert_eq!(
+            output(&result()),
+            "ROV OK - ENFORCED: valid beacon reachable, invalid beacon unreachable | \
+             'cloudflare_valid_ipv4'=0.120s;;;0 'cloudflare_valid_ipv4_attempts'=1;;;0 \
+             'cloudflare_invalid_ipv4'=3.000s;;;0 'cloudflare_invalid_ipv4_attempts'=1;;;0"
+        );
+    }
+
+    #[test]
+    fn states() {
+        let mut result = result();
+        let mut pair = result.pairs[0].clone();
+        (pair.name, pair.verdict) = ("o'brien".to_string(), Verdict::NotEnforced);
+        result.pairs.push(pair);
+        result.reason = "1/2 beacon pairs | quorum".to_string();
+        let line = output(&result);
+        assert!(line.starts_with("ROV WARNING - ENFORCED: 1/2 beacon pairs / quorum | "));
+        assert!(line.ends_with(" 'o''brien_invalid_ipv4_attempts'=1;;;0"));
+        assert_eq!(State::of(&result).code(), 1);
+
+        result.verdict = Verdict::NotEnforced;
+        assert!(output(&result).starts_with("ROV CRITICAL - NOT_ENFORCED: "));
+        assert_eq!(State::of(&result).code(), 2);
+        result.verdict = Verdict::Inconclusive;
+        assert!(output(&result).starts_with("ROV UNKNOWN - INCONCLUSIVE: "));
+        assert_eq!(State::of(&result).code(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        let error = anyhow::anyhow!("timed out | retried").context("check failed");
+        assert_eq!(
+            super::error(&error),
+            "ROV UNKNOWN - check failed: timed out / retried"
+        );
+    }
+}
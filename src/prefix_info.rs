@@ -0,0 +1,245 @@
+use anyhow::{anyhow, bail, Context, Result};
+use ipnet::IpNet;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::probe::Connect;
+use crate::rpki::{Validator, Validity};
+
+/// Whois server of bgp.tools.
+pub const BGP_TOOLS_URL: &str = "whois://bgp.tools:43";
+/// Base URL of the BGPView API.
+pub const BGPVIEW_URL: &str = "https://api.bgpview.io/";
+/// Timeout of the whois queries, the HTTP ones using the timeout of the client.
+const WHOIS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Source of the routing data of the beacon prefixes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrefixSource {
+    /// Whois interface of bgp.tools
+    #[default]
+    BgpTools,
+    /// BGPView API
+    Bgpview,
+}
+
+impl FromStr for PrefixSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bgp-tools" => Ok(PrefixSource::BgpTools),
+            "bgpview" => Ok(PrefixSource::Bgpview),
+            _ => bail!("unknown prefix source `{}`", s),
+        }
+    }
+}
+
+impl std::fmt::Display for PrefixSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefixSource::BgpTools => write!(f, "bgp-tools"),
+            PrefixSource::Bgpview => write!(f, "bgpview"),
+        }
+    }
+}
+
+impl PrefixSource {
+    /// Default URL of the routing data.
+    pub fn url(&self) -> Url {
+        match self {
+            PrefixSource::BgpTools => Url::parse(BGP_TOOLS_URL).unwrap(),
+            PrefixSource::Bgpview => Url::parse(BGPVIEW_URL).unwrap(),
+        }
+    }
+}
+
+/// Lookup of the prefixes announced covering the beacons, reported apart from the verdict.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrefixLookup {
+    #[serde(default)]
+    pub source: PrefixSource,
+    /// URL of the routing data [default: the source URL]
+    pub url: Option<Url>,
+}
+
+/// Origin of a beacon prefix, with the RPKI validity of its route.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Origin {
+    pub asn: u32,
+    pub name: Option<String>,
+    pub validity: Option<Validity>,
+}
+
+/// Prefix announced covering the address of a beacon, telling a renumbered or withdrawn beacon.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrefixInfo {
+    pub source: PrefixSource,
+    pub address: Option<IpAddr>,
+    /// Most specific prefix announced covering the address, none if it is not announced
+    pub prefix: Option<IpNet>,
+    pub origins: Vec<Origin>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BgpviewResponse {
+    data: BgpviewIp,
+}
+
+#[derive(Deserialize)]
+struct BgpviewIp {
+    #[serde(default)]
+    prefixes: Vec<BgpviewPrefix>,
+}
+
+#[derive(Deserialize)]
+struct BgpviewPrefix {
+    prefix: IpNet,
+    asn: BgpviewAsn,
+}
+
+#[derive(Deserialize)]
+struct BgpviewAsn {
+    asn: u32,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Announcements covering an address in the verbose whois output of bgp.tools, e.g.
+/// `13335 | 1.1.1.1 | 1.1.1.0/24 | US | ARIN | 2010-07-14 | Cloudflare, Inc.`.
+fn whois_routes(output: &str) -> Vec<(IpNet, u32, Option<String>)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split('|').map(str::trim).collect::<Vec<_>>();
+            let asn = fields.first()?.parse().ok().filter(|asn| *asn != 0)?;
+            let prefix = fields.get(2)?.parse().ok()?;
+            let name = fields.get(6).filter(|name| !name.is_empty());
+            Some((prefix, asn, name.map(|name| name.to_string())))
+        })
+        .collect()
+}
+
+async fn whois(url: &Url, query: &str) -> Result<String> {
+    let host = url.host_str().context("whois URL without host")?;
+    let query = async {
+        let mut stream = TcpStream::connect((host, url.port().unwrap_or(43)))
+            .await
+            .with_context(|| format!("failed to connect to {}", host))?;
+        stream
+            .write_all(format!("{}\r\n", query).as_bytes())
+            .await?;
+        let mut output = String::new();
+        stream.read_to_string(&mut output).await?;
+        Ok::<_, anyhow::Error>(output)
+    };
+    tokio::time::timeout(WHOIS_TIMEOUT, query)
+        .await
+        .map_err(|_| anyhow!("whois query timed out"))?
+}
+
+impl PrefixLookup {
+    /// Announcements of the most specific prefix covering an address.
+    async fn routes(
+        &self,
+        client: &Client,
+        ip: IpAddr,
+    ) -> Result<Vec<(IpNet, u32, Option<String>)>> {
+        let url = self.url.clone().unwrap_or_else(|| self.source.url());
+        let routes = match self.source {
+            PrefixSource::BgpTools => whois_routes(&whois(&url, &format!(" -v {}", ip)).await?),
+            PrefixSource::Bgpview => {
+                let url = url.join(&format!("ip/{}", ip))?;
+                let response = client.get(url).send().await?.error_for_status()?;
+                let response: BgpviewResponse =
+                    response.json().await.context("invalid BGPView response")?;
+                response
+                    .data
+                    .prefixes
+                    .into_iter()
+                    .map(|prefix| (prefix.prefix, prefix.asn.asn, prefix.asn.name))
+                    .collect()
+            }
+        };
+        let longest = routes
+            .iter()
+            .map(|(prefix, _, _)| prefix.prefix_len())
+            .max();
+        Ok(routes
+            .into_iter()
+            .filter(|(prefix, _, _)| Some(prefix.prefix_len()) == longest)
+            .collect())
+    }
+
+    async fn lookup(
+        &self,
+        client: &Client,
+        validator: Option<&Validator>,
+        url: &Url,
+        connect: &Connect,
+        info: &mut PrefixInfo,
+    ) -> Result<()> {
+        let ip = connect.address(url).await?;
+        info.address = Some(ip);
+        for (prefix, asn, name) in self.routes(client, ip).await? {
+            info.prefix = Some(prefix);
+            let validity = match validator {
+                Some(validator) => validator
+                    .validate(client, prefix, asn)
+                    .await
+                    .inspect_err(|e| debug!("Failed to validate the route: {}", e))
+                    .ok()
+                    .map(|validation| validation.validity),
+                None => None,
+            };
+            info.origins.push(Origin {
+                asn,
+                name,
+                validity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Look up the prefix announced covering the address of a beacon, validating its routes
+    /// if a validator could be loaded.
+    #[instrument(level = "debug", name = "prefix_info", skip_all, fields(url = %url))]
+    pub async fn check(
+        &self,
+        client: &Client,
+        validator: Option<&Validator>,
+        url: &Url,
+        connect: &Connect,
+    ) -> PrefixInfo {
+        let mut info = PrefixInfo {
+            source: self.source,
+            address: None,
+            prefix: None,
+            origins: Vec::new(),
+            error: None,
+        };
+        if let Err(e) = self
+            .lookup(client, validator, url, connect, &mut info)
+            .await
+        {
+            info.error = Some(e.to_string());
+        }
+        debug!(
+            prefix = info.prefix.map(|prefix| prefix.to_string()),
+            origins = info.origins.len(),
+            error = info.error,
+            "Prefix lookup finished"
+        );
+        info
+    }
+}
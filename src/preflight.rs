@@ -0,0 +1,106 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::beacon::RequestHeader;
+
+/// Connectivity check URL answering an empty `204 No Content`, unless rewritten by a captive portal.
+pub const DEFAULT_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+
+/// Fetch of a well-known URL before judging ROV, telling a captive portal or a filtering proxy
+/// rewriting the responses apart from an ISP not enforcing ROV.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preflight {
+    #[serde(default = "default_url")]
+    pub url: Url,
+    /// Expected status of the response, redirections being followed
+    #[serde(default = "default_status")]
+    pub status: u16,
+    /// Expected body of the response, leading and trailing whitespace trimmed
+    #[serde(default)]
+    pub body: String,
+    /// Headers expected in the response, as `NAME: VALUE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<RequestHeader>,
+}
+
+fn default_url() -> Url {
+    Url::parse(DEFAULT_URL).unwrap()
+}
+
+fn default_status() -> u16 {
+    204
+}
+
+impl Default for Preflight {
+    fn default() -> Self {
+        Preflight::new(default_url())
+    }
+}
+
+impl Preflight {
+    /// Preflight of a URL expected to answer an empty `204 No Content`.
+    pub fn new(url: Url) -> Self {
+        Preflight {
+            url,
+            status: default_status(),
+            body: String::new(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PreflightResult {
+    pub url: String,
+    pub success: bool,
+    pub status: Option<u16>,
+    /// Why the response did not match the expectations
+    pub error: Option<String>,
+}
+
+/// Mismatch between a response and the expectations of the preflight, if any.
+async fn mismatch(
+    client: &Client,
+    preflight: &Preflight,
+    status: &mut Option<u16>,
+) -> Option<String> {
+    let response = match client.get(preflight.url.clone()).send().await {
+        Ok(response) => response,
+        Err(e) => return Some(e.to_string()),
+    };
+    *status = Some(response.status().as_u16());
+    if response.status().as_u16() != preflight.status {
+        return Some(format!(
+            "status {} instead of {}, a captive portal may be intercepting the requests",
+            response.status().as_u16(),
+            preflight.status
+        ));
+    }
+    for expected in &preflight.headers {
+        if response.headers().get(&expected.name) != Some(&expected.value) {
+            return Some(format!("unexpected {} header", expected.name));
+        }
+    }
+    match response.text().await {
+        Ok(body) if body.trim() == preflight.body.trim() => None,
+        Ok(_) => Some("unexpected body, the response may have been rewritten".to_string()),
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+/// Fetch the preflight URL and check its response.
+#[instrument(level = "debug", name = "preflight", skip_all, fields(url = %preflight.url))]
+pub async fn check(client: &Client, preflight: &Preflight) -> PreflightResult {
+    let mut status = None;
+    let error = mismatch(client, preflight, &mut status).await;
+    debug!(status, error, "Preflight finished");
+    PreflightResult {
+        url: preflight.url.to_string(),
+        success: error.is_none(),
+        status,
+        error,
+    }
+}
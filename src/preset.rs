@@ -0,0 +1,52 @@
+use anyhow::{anyhow, Result};
+use url::Url;
+
+use crate::beacon::{BeaconKind, BeaconPair, Invalidity};
+
+/// Known beacon presets, as (name, kind, valid URL, invalid URL).
+const PRESETS: &[(&str, BeaconKind, &str, &str)] = &[
+    (
+        "cloudflare",
+        BeaconKind::IsBgpSafeYet,
+        "https://valid.rpki.isbgpsafeyet.com",
+        "https://invalid.rpki.isbgpsafeyet.com",
+    ),
+    (
+        "meerval",
+        BeaconKind::Http,
+        "https://rpki-valid-beacon.meerval.net",
+        "https://rpki-invalid-beacon.meerval.net",
+    ),
+];
+
+/// Names of the known presets.
+pub fn names() -> Vec<&'static str> {
+    PRESETS.iter().map(|(name, ..)| *name).collect()
+}
+
+/// Get the beacon pair of a preset by name.
+pub fn get(name: &str) -> Result<BeaconPair> {
+    let (name, kind, valid_url, invalid_url) = PRESETS
+        .iter()
+        .find(|(preset, ..)| *preset == name)
+        .ok_or_else(|| {
+            anyhow!(
+                "unknown preset `{}` (known presets: {})",
+                name,
+                names().join(", ")
+            )
+        })?;
+
+    Ok(BeaconPair {
+        name: name.to_string(),
+        kind: *kind,
+        valid_url: Url::parse(valid_url)?,
+        invalid_url: Url::parse(invalid_url)?,
+        invalidity: Invalidity::default(),
+        timeout: None,
+        proxy: None,
+        sni: None,
+        pins: Vec::new(),
+        matcher: None,
+    })
+}
@@ -0,0 +1,252 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio_rustls::rustls::{pki_types::ServerName, ClientConfig};
+use tokio_rustls::TlsConnector;
+use tracing::{debug_span, Instrument};
+use url::{Host, Url};
+
+use crate::beacon::RequestHeader;
+use crate::resolver::{AddressFamily, Resolver};
+use crate::tls::{self, Fingerprint};
+
+/// Duration of each phase of a beacon request, in milliseconds.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Timings {
+    pub dns_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    pub tls_ms: Option<f64>,
+    pub ttfb_ms: Option<f64>,
+    /// Negotiated TLS version, e.g. `TLSv1.3`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_version: Option<String>,
+    /// Negotiated TLS cipher suite, e.g. `TLS13_AES_128_GCM_SHA256`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cipher: Option<String>,
+    pub error: Option<String>,
+}
+
+fn elapsed_ms(start: Instant) -> Option<f64> {
+    Some(start.elapsed().as_secs_f64() * 1000.0)
+}
+
+async fn first_byte<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    url: &Url,
+    headers: &[RequestHeader],
+) -> Result<()> {
+    let host = url.host_str().unwrap_or_default();
+    let mut request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+        &url[url::Position::BeforePath..],
+        host
+    )
+    .into_bytes();
+    for header in headers {
+        request.extend_from_slice(header.name.as_str().as_bytes());
+        request.extend_from_slice(b": ");
+        request.extend_from_slice(header.value.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(b"\r\n");
+    stream.write_all(&request).await?;
+    let mut buf = [0u8; 1];
+    if stream.read(&mut buf).await? == 0 {
+        return Err(anyhow!("connection closed before response"));
+    }
+    Ok(())
+}
+
+/// How the probe connections are established.
+#[derive(Clone, Debug)]
+pub struct Connect {
+    /// Resolver of the beacon hostnames
+    pub resolver: Resolver,
+    /// TCP connect timeout
+    pub timeout: Option<Duration>,
+    /// Local address the connections are bound to
+    pub source_ip: Option<IpAddr>,
+    /// Network interface the connections are bound to
+    pub interface: Option<String>,
+    /// TLS configuration of the connections
+    pub tls: Arc<ClientConfig>,
+}
+
+impl Connect {
+    async fn connect(&self, addr: SocketAddr) -> Result<TcpStream> {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = &self.interface {
+            socket
+                .bind_device(Some(interface.as_bytes()))
+                .with_context(|| format!("failed to bind to interface {}", interface))?;
+        }
+        if let Some(source_ip) = self.source_ip {
+            socket.bind(SocketAddr::new(source_ip, 0))?;
+        }
+        let connect = socket
+            .connect(addr)
+            .instrument(debug_span!("connect", %addr));
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect)
+                .await
+                .map_err(|_| anyhow!("connect timed out"))?
+                .map_err(Into::into),
+            None => Ok(connect.await?),
+        }
+    }
+
+    /// Address of the host of `url`, resolved unless literal.
+    pub async fn address(&self, url: &Url) -> Result<IpAddr> {
+        match url.host() {
+            Some(Host::Ipv4(ip)) => Ok(IpAddr::V4(ip)),
+            Some(Host::Ipv6(ip)) => Ok(IpAddr::V6(ip)),
+            // The IPv4 addresses of the URLs of non-special schemes, like `dns://`, are opaque hosts
+            Some(Host::Domain(host)) => match host.parse() {
+                Ok(ip) => Ok(ip),
+                Err(_) => Ok(self.resolver.lookup(host).await?[0]),
+            },
+            None => Err(anyhow!("URL without host")),
+        }
+    }
+
+    /// UDP socket to reach an address, bound like the TCP connections.
+    pub async fn udp(&self, addr: SocketAddr) -> Result<std::net::UdpSocket> {
+        let local = self
+            .source_ip
+            .unwrap_or_else(|| AddressFamily::of(&addr.ip()).unspecified());
+        let socket = UdpSocket::bind(SocketAddr::new(local, 0)).await?;
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = &self.interface {
+            socket
+                .bind_device(Some(interface.as_bytes()))
+                .with_context(|| format!("failed to bind to interface {}", interface))?;
+        }
+        Ok(socket.into_std()?)
+    }
+}
+
+async fn probe(
+    url: &Url,
+    connect: &Connect,
+    headers: &[RequestHeader],
+    timings: &mut Timings,
+) -> Result<()> {
+    let host = url.host_str().context("URL without host")?;
+    let port = url.port_or_known_default().context("URL without port")?;
+
+    let ip = match url.host() {
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        _ => {
+            let start = Instant::now();
+            let addrs = connect.resolver.lookup(host).await?;
+            timings.dns_ms = elapsed_ms(start);
+            addrs[0]
+        }
+    };
+    let addr = SocketAddr::new(ip, port);
+
+    let start = Instant::now();
+    let mut stream = connect.connect(addr).await?;
+    timings.connect_ms = elapsed_ms(start);
+
+    if url.scheme() != "https" {
+        let start = Instant::now();
+        first_byte(&mut stream, url, headers)
+            .instrument(debug_span!("request"))
+            .await?;
+        timings.ttfb_ms = elapsed_ms(start);
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    let connector = TlsConnector::from(connect.tls.clone());
+    let server_name = ServerName::try_from(host.to_string())?;
+    let mut stream = connector
+        .connect(server_name, stream)
+        .instrument(debug_span!("tls"))
+        .await?;
+    timings.tls_ms = elapsed_ms(start);
+    (timings.tls_version, timings.tls_cipher) = tls::negotiated(stream.get_ref().1);
+
+    let start = Instant::now();
+    first_byte(&mut stream, url, headers)
+        .instrument(debug_span!("request"))
+        .await?;
+    timings.ttfb_ms = elapsed_ms(start);
+    Ok(())
+}
+
+/// Measure the duration of the DNS resolution, TCP connect, TLS handshake
+/// and time to first byte of a request to `url`, over a dedicated connection.
+#[tracing::instrument(level = "debug", name = "timings", skip_all, fields(url = %url))]
+pub async fn timings(
+    url: &Url,
+    timeout: Duration,
+    connect: &Connect,
+    headers: &[RequestHeader],
+) -> Timings {
+    let mut timings = Timings::default();
+    let probe = probe(url, connect, headers, &mut timings);
+    match tokio::time::timeout(timeout, probe).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => timings.error = Some(format!("{:#}", e)),
+        Err(_) => timings.error = Some("timed out".to_string()),
+    }
+    timings
+}
+
+/// Open a TCP connection to the host of `url` on `port`, closed right away,
+/// returning the address connected to.
+#[tracing::instrument(level = "debug", name = "tcp", skip_all, fields(url = %url))]
+pub async fn tcp(url: &Url, port: u16, timeout: Duration, connect: &Connect) -> Result<SocketAddr> {
+    let open = async {
+        let addr = SocketAddr::new(connect.address(url).await?, port);
+        connect.connect(addr).await?;
+        Ok(addr)
+    };
+    tokio::time::timeout(timeout, open)
+        .await
+        .map_err(|_| anyhow!("timed out"))?
+}
+
+async fn handshake(url: &Url, connect: &Connect) -> Result<Vec<Fingerprint>> {
+    let host = url.host_str().context("URL without host")?;
+    let port = url.port_or_known_default().context("URL without port")?;
+    let (ip, server_name) = match url.host() {
+        Some(Host::Ipv4(ip)) => (IpAddr::V4(ip), ServerName::from(IpAddr::V4(ip))),
+        Some(Host::Ipv6(ip)) => (IpAddr::V6(ip), ServerName::from(IpAddr::V6(ip))),
+        _ => (
+            connect.resolver.lookup(host).await?[0],
+            ServerName::try_from(host.to_string())?,
+        ),
+    };
+    let stream = connect.connect(SocketAddr::new(ip, port)).await?;
+    let stream = TlsConnector::from(connect.tls.clone())
+        .connect(server_name, stream)
+        .await?;
+    let certificates = stream.get_ref().1.peer_certificates().unwrap_or_default();
+    Ok(certificates.iter().map(Fingerprint::of).collect())
+}
+
+/// Fingerprints of the certificate chain presented by the host of `url`, leaf first,
+/// over a dedicated connection.
+#[tracing::instrument(level = "debug", name = "certificates", skip_all, fields(url = %url))]
+pub async fn certificates(
+    url: &Url,
+    timeout: Duration,
+    connect: &Connect,
+) -> Result<Vec<Fingerprint>> {
+    tokio::time::timeout(timeout, handshake(url, connect))
+        .await
+        .map_err(|_| anyhow!("timed out"))?
+}
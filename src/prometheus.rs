@@ -0,0 +1,162 @@
+use anyhow::Result;
+use metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Label,
+};
+use metrics_exporter_prometheus::{
+    Matcher, PrometheusBuilder, PrometheusHandle, PrometheusRecorder,
+};
+
+use crate::checker::RovResult;
+use crate::verdict::Verdict;
+
+const LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+fn builder() -> Result<PrometheusBuilder> {
+    Ok(PrometheusBuilder::new().set_buckets_for_metric(
+        Matcher::Full("rovcheck_request_duration_seconds".to_string()),
+        LATENCY_BUCKETS,
+    )?)
+}
+
+/// Install the global Prometheus recorder and return a handle to render metrics.
+pub fn install() -> Result<PrometheusHandle> {
+    let handle = builder()?.install_recorder()?;
+    describe();
+    Ok(handle)
+}
+
+/// Build a standalone Prometheus recorder, to be used with [`metrics::with_local_recorder`].
+pub fn recorder() -> Result<PrometheusRecorder> {
+    let recorder = builder()?.build_recorder();
+    metrics::with_local_recorder(&recorder, describe);
+    Ok(recorder)
+}
+
+fn describe() {
+    describe_gauge!(
+        "rovcheck_rov_enforced",
+        "Whether ROV is enforced (1) or not (0)"
+    );
+    describe_gauge!(
+        "rovcheck_verdict",
+        "Current verdict of the check (1 for the current verdict, 0 otherwise)"
+    );
+    describe_gauge!(
+        "rovcheck_beacon_reachable",
+        "Whether the beacon was reachable (1) or not (0)"
+    );
+    describe_gauge!(
+        "rovcheck_confidence",
+        "Confidence in the verdict, from 0 to 1 (0 if INCONCLUSIVE)"
+    );
+    describe_histogram!(
+        "rovcheck_request_duration_seconds",
+        "Beacon request duration in seconds"
+    );
+    describe_counter!("rovcheck_checks_total", "Number of checks performed");
+    describe_gauge!(
+        "rovcheck_last_check_timestamp_seconds",
+        "Timestamp of the last check"
+    );
+}
+
+/// Record the metrics of a check result, labelled with its labels.
+pub fn record(result: &RovResult) {
+    let labels = |extra: &[(&'static str, String)]| {
+        result
+            .labels
+            .iter()
+            .map(|(name, value)| Label::new(name.clone(), value.clone()))
+            .chain(
+                extra
+                    .iter()
+                    .map(|(name, value)| Label::new(*name, value.clone())),
+            )
+            .collect::<Vec<_>>()
+    };
+    let enforced = result.verdict == Verdict::Enforced;
+    gauge!("rovcheck_rov_enforced", labels(&[])).set(if enforced { 1.0 } else { 0.0 });
+    for verdict in [
+        Verdict::Enforced,
+        Verdict::NotEnforced,
+        Verdict::Inconclusive,
+    ] {
+        gauge!(
+            "rovcheck_verdict",
+            labels(&[("verdict", verdict.to_string())])
+        )
+        .set(if result.verdict == verdict { 1.0 } else { 0.0 });
+    }
+    gauge!("rovcheck_confidence", labels(&[])).set(
+        result
+            .confidence
+            .as_ref()
+            .map_or(0.0, |confidence| confidence.score),
+    );
+
+    for family in &result.families {
+        gauge!(
+            "rovcheck_rov_enforced",
+            labels(&[("family", family.family.to_string())])
+        )
+        .set(if family.verdict == Verdict::Enforced {
+            1.0
+        } else {
+            0.0
+        });
+    }
+
+    for uplink in &result.uplinks {
+        gauge!(
+            "rovcheck_rov_enforced",
+            labels(&[("uplink", uplink.uplink.clone())])
+        )
+        .set(if uplink.verdict == Verdict::Enforced {
+            1.0
+        } else {
+            0.0
+        });
+    }
+
+    if let Some(http3) = &result.http3 {
+        gauge!(
+            "rovcheck_rov_enforced",
+            labels(&[("protocol", "http3".to_string())])
+        )
+        .set(if http3.verdict == Verdict::Enforced {
+            1.0
+        } else {
+            0.0
+        });
+    }
+
+    for pair in &result.pairs {
+        for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+            let mut extra = vec![
+                ("beacon", pair.name.clone()),
+                ("validity", validity.to_string()),
+            ];
+            if let Some(family) = pair.family {
+                extra.push(("family", family.to_string()));
+            }
+            if let Some(uplink) = &pair.uplink {
+                extra.push(("uplink", uplink.clone()));
+            }
+            if pair.http3 {
+                extra.push(("protocol", "http3".to_string()));
+            }
+            let labels = labels(&extra);
+            gauge!("rovcheck_beacon_reachable", labels.clone()).set(if beacon.success {
+                1.0
+            } else {
+                0.0
+            });
+            histogram!("rovcheck_request_duration_seconds", labels)
+                .record(beacon.duration().as_secs_f64());
+        }
+    }
+
+    counter!("rovcheck_checks_total", labels(&[])).increment(1);
+    gauge!("rovcheck_last_check_timestamp_seconds", labels(&[]))
+        .set(result.finished_at.timestamp_millis() as f64 / 1000.0);
+}
@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::verdict::Verdict;
+
+/// Base URL of the Cloudflare API, serving the Radar endpoints.
+pub const DEFAULT_URL: &str = "https://api.cloudflare.com/client/v4/";
+
+/// Cloudflare Radar API, classifying the ASNs by their ROV deployment as on isbgpsafeyet.com.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Radar {
+    /// API token allowed to read Radar
+    pub token: String,
+    #[serde(default = "default_url")]
+    pub url: Url,
+}
+
+fn default_url() -> Url {
+    Url::parse(DEFAULT_URL).unwrap()
+}
+
+impl Radar {
+    pub fn new(token: String) -> Self {
+        Radar {
+            token,
+            url: default_url(),
+        }
+    }
+}
+
+/// ROV classification of an ASN by Cloudflare Radar, advisory only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RadarResult {
+    pub asn: u32,
+    /// Classification of the ASN, e.g. `safe`, `partially_safe` or `unsafe`
+    pub classification: Option<String>,
+    /// Verdict the classification stands for, unknown for the unknown classifications
+    pub verdict: Option<Verdict>,
+    /// Whether the classification agrees with the local verdict, unknown if either is
+    /// inconclusive
+    pub agrees: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Entity,
+}
+
+#[derive(Deserialize)]
+struct Entity {
+    asn: AsnEntity,
+}
+
+#[derive(Deserialize)]
+struct AsnEntity {
+    #[serde(default, alias = "rovStatus", alias = "rov_status")]
+    rov: Option<String>,
+}
+
+/// Verdict of a classification: safe ASNs enforce ROV, unsafe ones do not and the partially
+/// safe ones only on some of their routes.
+fn verdict(classification: &str) -> Option<Verdict> {
+    match classification
+        .to_ascii_lowercase()
+        .replace([' ', '-'], "_")
+        .as_str()
+    {
+        "safe" => Some(Verdict::Enforced),
+        "partially_safe" => Some(Verdict::Inconclusive),
+        "unsafe" => Some(Verdict::NotEnforced),
+        _ => None,
+    }
+}
+
+impl Radar {
+    async fn classification(&self, client: &Client, asn: u32) -> Result<Option<String>> {
+        let url = self.url.join(&format!("radar/entities/asns/{}", asn))?;
+        let response = client
+            .get(url)
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+        let response: Response = response
+            .json()
+            .await
+            .context("invalid Cloudflare Radar response")?;
+        Ok(response.result.asn.rov)
+    }
+
+    /// ROV classification of an ASN, compared with the local verdict.
+    #[instrument(level = "debug", name = "radar", skip(self, client, local))]
+    pub async fn check(&self, client: &Client, asn: u32, local: Verdict) -> RadarResult {
+        let mut result = RadarResult {
+            asn,
+            classification: None,
+            verdict: None,
+            agrees: None,
+            error: None,
+        };
+        match self.classification(client, asn).await {
+            Ok(classification) => {
+                result.verdict = classification.as_deref().and_then(verdict);
+                result.agrees = result.verdict.and_then(|verdict| verdict.agrees(local));
+                result.classification = classification;
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+        debug!(
+            classification = result.classification,
+            error = result.error,
+            "Radar lookup finished"
+        );
+        result
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::duration;
+
+/// Name of the default state file, in the state directory of the user.
+const STATE_FILE: &str = "rate-limit.json";
+/// Window of the requests per minute.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits of the requests to the beacons, by host. The times of the last requests are kept in
+/// a state file, so that the limits hold across the runs of rovcheck, e.g. from cron, and the
+/// profiles.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Maximum number of requests to a beacon per minute, the next ones waiting
+    pub per_minute: Option<u32>,
+    /// Minimum interval between two checks of a beacon, the checks in between being refused
+    #[serde(default, with = "duration::seconds")]
+    pub min_interval: Option<Duration>,
+    /// State file [default: `rate-limit.json` in `$XDG_STATE_HOME/rovcheck` or
+    /// `~/.local/state/rovcheck`]
+    pub state: Option<PathBuf>,
+    /// Wait for the next allowed check instead of refusing it, as the daemons do
+    #[serde(skip)]
+    pub queue: bool,
+}
+
+/// Times of the last checks and requests of the beacon hosts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    #[serde(default)]
+    checks: BTreeMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    requests: BTreeMap<String, Vec<DateTime<Utc>>>,
+}
+
+/// Check refused as a beacon was checked less than the minimum interval ago.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub host: String,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rate limited: {} was checked less than the minimum interval ago, next check allowed in {}s",
+            self.host,
+            self.retry_after.as_secs().max(1)
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl RateLimit {
+    /// Whether any limit is set.
+    pub fn is_enabled(&self) -> bool {
+        self.per_minute.is_some() || self.min_interval.is_some()
+    }
+
+    /// State file, the default one being in a directory private to the user, created if needed.
+    fn path(&self) -> Result<PathBuf> {
+        if let Some(state) = &self.state {
+            return Ok(state.clone());
+        }
+        let directory = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|directory| directory.is_absolute())
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".local/state")))
+            .context(
+                "no state directory for the rate limits, set HOME or the rate limit state file",
+            )?
+            .join("rovcheck");
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&directory)
+            .with_context(|| format!("failed to create {}", directory.display()))?;
+        Ok(directory.join(STATE_FILE))
+    }
+
+    /// Update the state file, a missing or corrupted one starting over. The file is locked
+    /// while it is read and written, so that the concurrent runs and profiles wait for each other.
+    fn update<T>(&self, update: impl FnOnce(&mut State) -> T) -> Result<T> {
+        let path = self.path()?;
+        let context = || format!("failed to update the rate limit state {}", path.display());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path)
+            .with_context(context)?;
+        // Released when the file is closed
+        file.lock().with_context(context)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).with_context(context)?;
+        let mut state = serde_json::from_slice(&content).unwrap_or_default();
+        let value = update(&mut state);
+        file.set_len(0).with_context(context)?;
+        file.rewind().with_context(context)?;
+        file.write_all(&serde_json::to_vec(&state)?)
+            .with_context(context)?;
+        Ok(value)
+    }
+
+    /// Update the state file on a blocking thread, not to stall the other checks while waiting
+    /// for the lock.
+    async fn update_blocking<T: Send + 'static>(
+        &self,
+        update: impl FnOnce(&mut State) -> T + Send + 'static,
+    ) -> Result<T> {
+        let limit = self.clone();
+        tokio::task::spawn_blocking(move || limit.update(update)).await?
+    }
+
+    /// Start a check of the beacon hosts, refused with [`RateLimited`] if one of them was
+    /// checked less than the minimum interval ago. When queued, the check is rather delayed
+    /// until the next allowed time, which it reserves.
+    pub async fn start(&self, hosts: &[String]) -> Result<()> {
+        let Some(min_interval) = self.min_interval else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let min = chrono::Duration::from_std(min_interval)?;
+        let (hosts, queue) = (hosts.to_vec(), self.queue);
+        let reserved = self
+            .update_blocking(move |state| {
+                let next = hosts
+                    .iter()
+                    .filter_map(|host| Some((host.clone(), *state.checks.get(host)? + min)))
+                    .filter(|(_, next)| *next > now)
+                    .max_by_key(|(_, next)| *next);
+                if let (Some((host, next)), false) = (&next, queue) {
+                    return Err(RateLimited {
+                        host: host.clone(),
+                        retry_after: (*next - now).to_std().unwrap_or_default(),
+                    });
+                }
+                let at = next.as_ref().map_or(now, |(_, next)| *next);
+                for host in hosts {
+                    state.checks.insert(host, at);
+                }
+                Ok(next)
+            })
+            .await??;
+        if let Some((host, next)) = reserved {
+            let wait = (next - now).to_std().unwrap_or_default();
+            info!(
+                "{} was checked less than the minimum interval ago, checking in {}s",
+                host,
+                wait.as_secs().max(1)
+            );
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// Wait until a request to the beacon host is allowed, then account for it.
+    pub async fn acquire(&self, host: &str) -> Result<()> {
+        let Some(per_minute) = self.per_minute else {
+            return Ok(());
+        };
+        loop {
+            let now = Utc::now();
+            let requested = host.to_string();
+            let wait = self
+                .update_blocking(move |state| {
+                    state.requests.retain(|_, requests| {
+                        requests.retain(|at| (now - *at).to_std().unwrap_or_default() < WINDOW);
+                        !requests.is_empty()
+                    });
+                    let requests = state.requests.entry(requested).or_default();
+                    if requests.len() < per_minute as usize {
+                        requests.push(now);
+                        return None;
+                    }
+                    let oldest = requests.iter().min().copied().unwrap_or(now);
+                    Some(WINDOW.saturating_sub((now - oldest).to_std().unwrap_or_default()))
+                })
+                .await?;
+            match wait {
+                Some(wait) => {
+                    debug!(
+                        "{} requests to {} in the last minute, waiting {:?}",
+                        per_minute, host, wait
+                    );
+                    tokio::time::sleep(wait).await;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(name: &str, per_minute: Option<u32>, min_interval: Option<Duration>) -> RateLimit {
+        let state = std::env::temp_dir().join(format!(
+            "rovcheck-test-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&state);
+        RateLimit {
+            per_minute,
+            min_interval,
+            state: Some(state),
+            queue: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn min_interval() {
+        let limit = limit("interval", None, Some(Duration::from_secs(60)));
+        let hosts = ["a.example".to_string()];
+        limit.start(&hosts).await.unwrap();
+        let error = limit.start(&hosts).await.unwrap_err();
+        let refused = error.downcast_ref::<RateLimited>().unwrap();
+        assert_eq!(refused.host, "a.example");
+        assert!(refused.retry_after > Duration::from_secs(55));
+        // Other beacons are not limited
+        limit.start(&["b.example".to_string()]).await.unwrap();
+        std::fs::remove_file(limit.state.unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn corrupted_state() {
+        let limit = limit("corrupted", None, Some(Duration::from_secs(60)));
+        std::fs::write(limit.state.as_ref().unwrap(), "{").unwrap();
+        limit.start(&["a.example".to_string()]).await.unwrap();
+        assert!(limit.start(&["a.example".to_string()]).await.is_err());
+        std::fs::remove_file(limit.state.unwrap()).unwrap();
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        let limit = limit("concurrent", Some(1000), None);
+        let threads = (0..8)
+            .map(|_| {
+                let limit = limit.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        limit
+                            .update(|state| {
+                                let requests = state.requests.entry("a".to_string()).or_default();
+                                requests.push(Utc::now());
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let count = limit.update(|state| state.requests["a"].len()).unwrap();
+        assert_eq!(count, 200);
+        std::fs::remove_file(limit.state.unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::{bail, Context, Result};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug_span, Instrument};
+
+use crate::dns::NameServer;
+
+/// IP address family used to reach the beacons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Family of an IP address.
+    pub fn of(ip: &IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+
+    pub fn matches(&self, addr: &SocketAddr) -> bool {
+        match self {
+            AddressFamily::Ipv4 => addr.is_ipv4(),
+            AddressFamily::Ipv6 => addr.is_ipv6(),
+        }
+    }
+
+    /// The unspecified local address of the family, used to bind outgoing connections.
+    pub fn unspecified(&self) -> IpAddr {
+        match self {
+            AddressFamily::Ipv4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            AddressFamily::Ipv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+}
+
+impl std::fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => write!(f, "ipv4"),
+            AddressFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+/// Addresses of a host and port, overriding their resolution, like the `--resolve` option of curl.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl FromStr for ResolveOverride {
+    type Err = String;
+
+    /// `HOST:PORT:ADDR[,ADDR...]`, the IPv6 addresses being optionally enclosed in brackets.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid resolve override `{}`, expected HOST:PORT:ADDR[,ADDR...]",
+                s
+            )
+        };
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = port.parse().map_err(|_| invalid())?;
+        let addrs = addrs
+            .split(',')
+            .map(|addr| {
+                let addr = match addr.strip_prefix('[') {
+                    Some(addr) => addr.strip_suffix(']').ok_or_else(invalid)?,
+                    None => addr,
+                };
+                addr.parse().map_err(|_| invalid())
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ResolveOverride {
+            host: host.to_string(),
+            port,
+            addrs,
+        })
+    }
+}
+
+impl TryFrom<String> for ResolveOverride {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ResolveOverride> for String {
+    fn from(resolve: ResolveOverride) -> Self {
+        resolve.to_string()
+    }
+}
+
+impl std::fmt::Display for ResolveOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let addrs = self
+            .addrs
+            .iter()
+            .map(|addr| match addr {
+                IpAddr::V4(addr) => addr.to_string(),
+                IpAddr::V6(addr) => format!("[{}]", addr),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(f, "{}:{}:{}", self.host, self.port, addrs)
+    }
+}
+
+/// Resolution of a beacon hostname.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Resolution {
+    /// Resolver of the hostname: `system`, `override` or the DNS server
+    pub resolver: String,
+    pub addrs: Vec<IpAddr>,
+    pub error: Option<String>,
+}
+
+/// Resolver of the beacon hostnames: the system resolver unless a DNS server is given,
+/// only returning addresses of the forced family if any.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    family: Option<AddressFamily>,
+    server: Option<NameServer>,
+    /// Timeout of the queries to the DNS server
+    timeout: Duration,
+    /// Addresses of the hosts whose resolution is overridden
+    overrides: Vec<(String, Vec<IpAddr>)>,
+}
+
+impl Resolver {
+    pub fn new(
+        family: Option<AddressFamily>,
+        server: Option<NameServer>,
+        timeout: Duration,
+    ) -> Self {
+        Resolver {
+            family,
+            server,
+            timeout,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Override the addresses the given hosts resolve to.
+    pub fn with_overrides(mut self, overrides: Vec<(String, Vec<IpAddr>)>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    fn overridden(&self, host: &str) -> Option<&Vec<IpAddr>> {
+        self.overrides
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(host))
+            .map(|(_, addrs)| addrs)
+    }
+
+    /// Resolution of a hostname, along with the resolver used.
+    pub async fn resolve(&self, host: &str) -> Resolution {
+        let resolver = match (self.overridden(host), &self.server) {
+            (Some(_), _) => "override".to_string(),
+            (None, Some(server)) => server.to_string(),
+            (None, None) => "system".to_string(),
+        };
+        match self.lookup(host).await {
+            Ok(addrs) => Resolution {
+                resolver,
+                addrs,
+                error: None,
+            },
+            Err(e) => Resolution {
+                resolver,
+                addrs: Vec::new(),
+                error: Some(format!("{:#}", e)),
+            },
+        }
+    }
+
+    /// Addresses of a hostname.
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let span = debug_span!("resolve", host, family = ?self.family, server = ?self.server);
+        async {
+            let addrs = match (self.overridden(host), &self.server) {
+                (Some(addrs), _) => addrs
+                    .iter()
+                    .copied()
+                    .filter(|ip| {
+                        self.family
+                            .is_none_or(|family| family == AddressFamily::of(ip))
+                    })
+                    .collect(),
+                (None, Some(server)) => server.lookup(host, self.family, self.timeout).await?,
+                (None, None) => tokio::net::lookup_host((host, 0))
+                    .await
+                    .with_context(|| format!("failed to resolve {}", host))?
+                    .filter(|addr| self.family.is_none_or(|family| family.matches(addr)))
+                    .map(|addr| addr.ip())
+                    .collect(),
+            };
+            if addrs.is_empty() {
+                match self.family {
+                    Some(family) => bail!("no {} address found for {}", family, host),
+                    None => bail!("no address found for {}", host),
+                }
+            }
+            Ok(addrs)
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver
+                .lookup(name.as_str())
+                .await
+                .map_err(|e| format!("{:#}", e))?;
+            let addrs = addrs.into_iter().map(|ip| SocketAddr::new(ip, 0));
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_overrides() {
+        let resolve: ResolveOverride = "valid.example:443:192.0.2.1".parse().unwrap();
+        assert_eq!(
+            resolve,
+            ResolveOverride {
+                host: "valid.example".to_string(),
+                port: 443,
+                addrs: vec!["192.0.2.1".parse().unwrap()],
+            }
+        );
+        let resolve: ResolveOverride = "valid.example:80:[2001:db8::1],192.0.2.1,2001:db8::2"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            resolve.addrs,
+            ["2001:db8::1", "192.0.2.1", "2001:db8::2"].map(|addr| addr.parse::<IpAddr>().unwrap())
+        );
+        assert_eq!(
+            resolve.to_string(),
+            "valid.example:80:[2001:db8::1],192.0.2.1,[2001:db8::2]"
+        );
+        assert_eq!(
+            resolve.to_string().parse::<ResolveOverride>().unwrap(),
+            resolve
+        );
+    }
+
+    #[test]
+    fn malformed_resolve_overrides() {
+        for resolve in [
+            "valid.example",
+            "valid.example:443",
+            "valid.example:443:",
+            ":443:192.0.2.1",
+            "valid.example::192.0.2.1",
+            "valid.example:https:192.0.2.1",
+            "valid.example:65536:192.0.2.1",
+            "valid.example:443:192.0.2.1,",
+            "valid.example:443:[2001:db8::1",
+            "valid.example:443:[192.0.2.1]x",
+            "valid.example:443:valid.example",
+        ] {
+            assert_eq!(
+                resolve.parse::<ResolveOverride>().unwrap_err(),
+                format!(
+                    "invalid resolve override `{}`, expected HOST:PORT:ADDR[,ADDR...]",
+                    resolve
+                ),
+                "{}",
+                resolve
+            );
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::probe::Connect;
+
+/// Base URL of the RIPEstat data API, serving the routes collected by RIPE RIS.
+pub const DEFAULT_URL: &str = "https://stat.ripe.net/data/";
+
+/// Cross-check of the invalid beacon prefix against the routes seen by RIPE RIS, telling a
+/// filtered invalid route apart from a withdrawn beacon.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ris {
+    #[serde(default = "default_url")]
+    pub url: Url,
+    /// ASNs of the upstreams, checked for propagating the invalid prefix
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub upstreams: Vec<u32>,
+}
+
+fn default_url() -> Url {
+    Url::parse(DEFAULT_URL).unwrap()
+}
+
+impl Default for Ris {
+    fn default() -> Self {
+        Ris::new(Vec::new())
+    }
+}
+
+impl Ris {
+    /// Cross-check through the public RIPEstat API.
+    pub fn new(upstreams: Vec<u32>) -> Self {
+        Ris {
+            url: default_url(),
+            upstreams,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RisResult {
+    /// Most specific prefix announced covering the invalid beacon
+    pub prefix: Option<String>,
+    /// Origin ASNs of the prefix
+    pub origins: Vec<u32>,
+    /// Number of RIS peers having a route to the prefix
+    pub peers: usize,
+    /// Upstreams found on the AS paths of these routes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub upstreams: Vec<u32>,
+    pub error: Option<String>,
+}
+
+impl RisResult {
+    /// Whether the prefix is announced, as far as RIS can tell.
+    pub fn announced(&self) -> bool {
+        self.error.is_none() && self.peers > 0
+    }
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct NetworkInfo {
+    #[serde(default)]
+    asns: Vec<String>,
+    #[serde(default)]
+    prefix: String,
+}
+
+#[derive(Deserialize)]
+struct LookingGlass {
+    #[serde(default)]
+    rrcs: Vec<Collector>,
+}
+
+#[derive(Deserialize)]
+struct Collector {
+    #[serde(default)]
+    peers: Vec<Peer>,
+}
+
+#[derive(Deserialize)]
+struct Peer {
+    #[serde(default)]
+    as_path: String,
+}
+
+async fn get<T: for<'de> Deserialize<'de>>(
+    client: &Client,
+    ris: &Ris,
+    call: &str,
+    resource: &str,
+) -> Result<T> {
+    let mut url = ris.url.join(&format!("{}/data.json", call))?;
+    url.query_pairs_mut()
+        .append_pair("resource", resource)
+        .append_pair("sourceapp", "rovcheck");
+    let response = client.get(url).send().await?.error_for_status()?;
+    let response: Response<T> = response
+        .json()
+        .await
+        .with_context(|| format!("invalid RIPEstat {} response", call))?;
+    Ok(response.data)
+}
+
+/// Most specific prefix announced covering an address, along with its origin ASNs.
+pub async fn route(client: &Client, ris: &Ris, ip: IpAddr) -> Result<Option<(IpNet, Vec<u32>)>> {
+    let info: NetworkInfo = get(client, ris, "network-info", &ip.to_string()).await?;
+    if info.prefix.is_empty() {
+        return Ok(None);
+    }
+    let prefix = info
+        .prefix
+        .parse()
+        .with_context(|| format!("invalid prefix `{}`", info.prefix))?;
+    let origins = info
+        .asns
+        .iter()
+        .filter_map(|asn| asn.parse().ok())
+        .collect();
+    Ok(Some((prefix, origins)))
+}
+
+async fn lookup(
+    client: &Client,
+    ris: &Ris,
+    url: &Url,
+    connect: &Connect,
+    result: &mut RisResult,
+) -> Result<()> {
+    let ip = connect.address(url).await?;
+    let Some((prefix, origins)) = route(client, ris, ip).await? else {
+        return Ok(());
+    };
+    result.origins = origins;
+    result.prefix = Some(prefix.to_string());
+
+    let glass: LookingGlass = get(client, ris, "looking-glass", &prefix.to_string()).await?;
+    let paths = glass
+        .rrcs
+        .iter()
+        .flat_map(|collector| &collector.peers)
+        .map(|peer| {
+            peer.as_path
+                .split_whitespace()
+                .filter_map(|asn| asn.parse::<u32>().ok())
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+    result.peers = paths.len();
+    result.upstreams = ris
+        .upstreams
+        .iter()
+        .copied()
+        .filter(|upstream| paths.iter().any(|path| path.contains(upstream)))
+        .collect();
+    Ok(())
+}
+
+/// Look up the routes to the address of the invalid beacon seen by RIS.
+#[instrument(level = "debug", name = "ris", skip_all, fields(url = %url))]
+pub async fn check(client: &Client, ris: &Ris, url: &Url, connect: &Connect) -> RisResult {
+    let mut result = RisResult {
+        prefix: None,
+        origins: Vec::new(),
+        peers: 0,
+        upstreams: Vec::new(),
+        error: None,
+    };
+    if let Err(e) = lookup(client, ris, url, connect, &mut result).await {
+        result.error = Some(e.to_string());
+    }
+    debug!(
+        prefix = result.prefix,
+        peers = result.peers,
+        error = result.error,
+        "RIS lookup finished"
+    );
+    result
+}
@@ -0,0 +1,603 @@
+use anyhow::{bail, Context, Result};
+use ipnet::IpNet;
+use reqwest::Client;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::probe::Connect;
+use crate::ris::{self, Ris};
+use crate::rtr;
+
+/// RIPEstat RPKI validation API.
+pub const RIPESTAT_URL: &str = "https://stat.ripe.net/data/rpki-validation/data.json";
+/// Validated ROA payloads exported by the Cloudflare RPKI validator.
+pub const CLOUDFLARE_URL: &str = "https://rpki.cloudflare.com/rpki.json";
+/// HTTP server of a local Routinator instance.
+pub const ROUTINATOR_URL: &str = "http://localhost:8323/";
+/// Local RTR cache, on the port assigned by IANA.
+pub const RTR_URL: &str = "rtr://localhost:323";
+
+/// Timeout of the transfer of the data of an RTR cache.
+const RTR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Route origin validation state of RFC 6811.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Validity {
+    /// A ROA covering the prefix authorizes its origin and length
+    Valid,
+    /// ROAs cover the prefix, none of them authorizing its origin and length
+    Invalid,
+    /// No ROA covers the prefix
+    NotFound,
+}
+
+impl std::fmt::Display for Validity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Validity::Valid => write!(f, "VALID"),
+            Validity::Invalid => write!(f, "INVALID"),
+            Validity::NotFound => write!(f, "NOT_FOUND"),
+        }
+    }
+}
+
+/// Source of the RPKI data a route is validated against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RpkiSource {
+    /// RIPEstat RPKI validation API
+    #[default]
+    Ripestat,
+    /// Validated ROA payloads of the Cloudflare RPKI validator, validated locally
+    Cloudflare,
+    /// Validity API of a Routinator instance
+    Routinator,
+    /// Validated ROA payloads of an RTR cache, validated locally
+    Rtr,
+}
+
+impl FromStr for RpkiSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ripestat" => Ok(RpkiSource::Ripestat),
+            "cloudflare" => Ok(RpkiSource::Cloudflare),
+            "routinator" => Ok(RpkiSource::Routinator),
+            "rtr" => Ok(RpkiSource::Rtr),
+            _ => bail!("unknown RPKI source `{}`", s),
+        }
+    }
+}
+
+impl std::fmt::Display for RpkiSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpkiSource::Ripestat => write!(f, "ripestat"),
+            RpkiSource::Cloudflare => write!(f, "cloudflare"),
+            RpkiSource::Routinator => write!(f, "routinator"),
+            RpkiSource::Rtr => write!(f, "rtr"),
+        }
+    }
+}
+
+impl RpkiSource {
+    /// Default URL of the RPKI data.
+    pub fn url(&self) -> Url {
+        match self {
+            RpkiSource::Ripestat => Url::parse(RIPESTAT_URL).unwrap(),
+            RpkiSource::Cloudflare => Url::parse(CLOUDFLARE_URL).unwrap(),
+            RpkiSource::Routinator => Url::parse(ROUTINATOR_URL).unwrap(),
+            RpkiSource::Rtr => Url::parse(RTR_URL).unwrap(),
+        }
+    }
+}
+
+/// Parse an ASN, with or without its `AS` prefix.
+pub fn parse_asn(s: &str) -> Result<u32> {
+    let asn = s
+        .strip_prefix("AS")
+        .or_else(|| s.strip_prefix("as"))
+        .unwrap_or(s);
+    asn.parse().with_context(|| format!("invalid ASN `{}`", s))
+}
+
+/// ASN given as a number or as a string, e.g. `"AS13335"`.
+fn deserialize_asn<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Asn {
+        Number(u32),
+        Text(String),
+    }
+    match Asn::deserialize(deserializer)? {
+        Asn::Number(asn) => Ok(asn),
+        Asn::Text(asn) => parse_asn(&asn).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Maximum length given as a number or as a string, as by Routinator.
+fn deserialize_max_length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Length {
+        Number(u8),
+        Text(String),
+    }
+    match Length::deserialize(deserializer)? {
+        Length::Number(length) => Ok(length),
+        Length::Text(length) => length.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Route origin authorization, as a validated ROA payload.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Roa {
+    pub prefix: IpNet,
+    #[serde(alias = "maxLength", deserialize_with = "deserialize_max_length")]
+    pub max_length: u8,
+    #[serde(alias = "origin", deserialize_with = "deserialize_asn")]
+    pub asn: u32,
+}
+
+impl Roa {
+    /// Whether the ROA covers a prefix.
+    pub fn covers(&self, prefix: &IpNet) -> bool {
+        self.prefix.contains(prefix)
+    }
+
+    /// Whether the ROA authorizes the announcement of a prefix by an origin,
+    /// AS0 never being a valid origin.
+    pub fn matches(&self, prefix: &IpNet, asn: u32) -> bool {
+        self.covers(prefix) && asn != 0 && self.asn == asn && prefix.prefix_len() <= self.max_length
+    }
+}
+
+impl std::fmt::Display for Roa {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{} AS{}", self.prefix, self.max_length, self.asn)
+    }
+}
+
+/// Validation of a route against the ROAs covering its prefix.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Validation {
+    pub prefix: IpNet,
+    pub asn: u32,
+    pub validity: Validity,
+    pub source: RpkiSource,
+    /// ROAs covering the prefix
+    pub roas: Vec<Roa>,
+}
+
+/// Validate a route against a set of ROAs, as in RFC 6811.
+pub fn validate(roas: &[Roa], prefix: IpNet, asn: u32) -> (Validity, Vec<Roa>) {
+    let prefix = prefix.trunc();
+    let covering = roas
+        .iter()
+        .filter(|roa| roa.covers(&prefix))
+        .cloned()
+        .collect::<Vec<_>>();
+    let validity = if covering.iter().any(|roa| roa.matches(&prefix, asn)) {
+        Validity::Valid
+    } else if covering.is_empty() {
+        Validity::NotFound
+    } else {
+        Validity::Invalid
+    };
+    (validity, covering)
+}
+
+#[derive(Deserialize)]
+struct Payloads {
+    roas: Vec<Roa>,
+}
+
+/// Fetch the validated ROA payloads exported as JSON by a validator, in the format of
+/// the Cloudflare RPKI validator also used by Routinator and rpki-client.
+pub async fn fetch_roas(client: &Client, url: Url) -> Result<Vec<Roa>> {
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    let payloads: Payloads = response
+        .json()
+        .await
+        .context("invalid validated ROA payloads")?;
+    debug!(%url, roas = payloads.roas.len(), "Fetched the validated ROA payloads");
+    Ok(payloads.roas)
+}
+
+#[derive(Deserialize)]
+struct RipestatResponse {
+    data: RipestatValidation,
+}
+
+#[derive(Deserialize)]
+struct RipestatValidation {
+    status: String,
+    #[serde(default)]
+    validating_roas: Vec<Roa>,
+}
+
+#[derive(Deserialize)]
+struct RoutinatorResponse {
+    validated_route: RoutinatorRoute,
+}
+
+#[derive(Deserialize)]
+struct RoutinatorRoute {
+    validity: RoutinatorValidity,
+}
+
+#[derive(Deserialize)]
+struct RoutinatorValidity {
+    state: String,
+    #[serde(rename = "VRPs", default)]
+    vrps: RoutinatorVrps,
+}
+
+#[derive(Default, Deserialize)]
+struct RoutinatorVrps {
+    #[serde(default)]
+    matched: Vec<Roa>,
+    #[serde(default)]
+    unmatched_as: Vec<Roa>,
+    #[serde(default)]
+    unmatched_length: Vec<Roa>,
+}
+
+/// Validity of a validation state, as named by an API.
+fn validity(state: &str) -> Result<Validity> {
+    match state {
+        "valid" => Ok(Validity::Valid),
+        "invalid" | "invalid_asn" | "invalid_length" => Ok(Validity::Invalid),
+        "unknown" | "not-found" | "not_found" => Ok(Validity::NotFound),
+        _ => bail!("unknown RPKI validation status `{}`", state),
+    }
+}
+
+/// RPKI data the routes are validated against: an API, or validated ROA payloads loaded
+/// once and validated locally.
+#[derive(Clone, Debug)]
+pub enum Validator {
+    Api { source: RpkiSource, url: Url },
+    Roas { source: RpkiSource, roas: Vec<Roa> },
+}
+
+impl Validator {
+    /// Validator of the RPKI data of a source, served at `url` [default: the source URL].
+    pub async fn load(client: &Client, source: RpkiSource, url: Option<&Url>) -> Result<Self> {
+        let url = url.cloned().unwrap_or_else(|| source.url());
+        match source {
+            RpkiSource::Ripestat | RpkiSource::Routinator => Ok(Validator::Api { source, url }),
+            RpkiSource::Cloudflare => Ok(Validator::Roas {
+                source,
+                roas: fetch_roas(client, url).await?,
+            }),
+            RpkiSource::Rtr => {
+                let host = url.host_str().context("RTR URL without host")?;
+                let server = format!("{}:{}", host, url.port().unwrap_or(rtr::DEFAULT_PORT));
+                let snapshot = rtr::fetch(&server, RTR_TIMEOUT).await?;
+                Ok(Validator::Roas {
+                    source,
+                    roas: snapshot.roas,
+                })
+            }
+        }
+    }
+
+    /// Validate a route.
+    pub async fn validate(&self, client: &Client, prefix: IpNet, asn: u32) -> Result<Validation> {
+        let prefix = prefix.trunc();
+        let (source, validity, roas) = match self {
+            Validator::Api {
+                source: RpkiSource::Routinator,
+                url,
+            } => {
+                let url = url.join(&format!("api/v1/validity/AS{}/{}", asn, prefix))?;
+                let response = client.get(url).send().await?.error_for_status()?;
+                let response: RoutinatorResponse = response
+                    .json()
+                    .await
+                    .context("invalid Routinator validity response")?;
+                let validity = response.validated_route.validity;
+                let vrps = validity.vrps;
+                let roas = [vrps.matched, vrps.unmatched_as, vrps.unmatched_length].concat();
+                (
+                    RpkiSource::Routinator,
+                    self::validity(&validity.state)?,
+                    roas,
+                )
+            }
+            Validator::Api { source, url } => {
+                let mut url = url.clone();
+                url.query_pairs_mut()
+                    .append_pair("resource", &asn.to_string())
+                    .append_pair("prefix", &prefix.to_string())
+                    .append_pair("sourceapp", "rovcheck");
+                let response = client.get(url).send().await?.error_for_status()?;
+                let response: RipestatResponse = response
+                    .json()
+                    .await
+                    .context("invalid RIPEstat rpki-validation response")?;
+                let validity = validity(&response.data.status)?;
+                (*source, validity, response.data.validating_roas)
+            }
+            Validator::Roas { source, roas } => {
+                let (validity, roas) = validate(roas, prefix, asn);
+                (*source, validity, roas)
+            }
+        };
+        Ok(Validation {
+            prefix,
+            asn,
+            validity,
+            source,
+            roas,
+        })
+    }
+}
+
+/// Verification of the RPKI state of the routes to the beacons before judging ROV.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rpki {
+    #[serde(default)]
+    pub source: RpkiSource,
+    /// URL of the RPKI data [default: the source URL]
+    pub url: Option<Url>,
+}
+
+/// RPKI state of the routes to a beacon.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BeaconRpki {
+    pub address: Option<IpAddr>,
+    /// Validations of the prefix announced covering the address, for each of its origins
+    pub validations: Vec<Validation>,
+    pub error: Option<String>,
+}
+
+impl BeaconRpki {
+    fn failed(error: String) -> Self {
+        BeaconRpki {
+            address: None,
+            validations: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    /// Validation of a route to the beacon not having a validity, `None` if all have it
+    /// or if the routes are unknown.
+    pub fn unexpected(&self, validity: Validity) -> Option<&Validation> {
+        self.validations
+            .iter()
+            .find(|validation| validation.validity != validity)
+    }
+}
+
+async fn routes(
+    client: &Client,
+    validator: &Validator,
+    ris: &Ris,
+    url: &Url,
+    connect: &Connect,
+    rpki: &mut BeaconRpki,
+) -> Result<()> {
+    let ip = connect.address(url).await?;
+    rpki.address = Some(ip);
+    let Some((prefix, origins)) = ris::route(client, ris, ip).await? else {
+        return Ok(());
+    };
+    for asn in origins {
+        rpki.validations
+            .push(validator.validate(client, prefix, asn).await?);
+    }
+    Ok(())
+}
+
+/// Validate the routes announced to a beacon, found through the RIPEstat API.
+#[instrument(level = "debug", name = "rpki", skip_all, fields(url = %url))]
+pub async fn verify(
+    client: &Client,
+    validator: &Result<Validator, String>,
+    ris: &Ris,
+    url: &Url,
+    connect: &Connect,
+) -> BeaconRpki {
+    let validator = match validator {
+        Ok(validator) => validator,
+        Err(e) => return BeaconRpki::failed(e.clone()),
+    };
+    let mut rpki = BeaconRpki {
+        address: None,
+        validations: Vec::new(),
+        error: None,
+    };
+    if let Err(e) = routes(client, validator, ris, url, connect, &mut rpki).await {
+        rpki.error = Some(e.to_string());
+    }
+    debug!(
+        validations = rpki.validations.len(),
+        error = rpki.error,
+        "RPKI verification finished"
+    );
+    rpki
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn roa(prefix: &str, max_length: u8, asn: u32) -> Roa {
+        Roa {
+            prefix: prefix.parse().unwrap(),
+            max_length,
+            asn,
+        }
+    }
+
+    /// API answering a single request with a JSON body.
+    async fn api(body: &'static str) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        Url::parse(&url).unwrap()
+    }
+
+    async fn validate_api(source: RpkiSource, body: &'static str) -> Result<Validation> {
+        let validator = Validator::Api {
+            source,
+            url: api(body).await,
+        };
+        let prefix = "192.0.2.0/24".parse().unwrap();
+        validator.validate(&Client::new(), prefix, 64496).await
+    }
+
+    #[test]
+    fn asns() {
+        assert_eq!(parse_asn("AS13335").unwrap(), 13335);
+        assert_eq!(parse_asn("as13335").unwrap(), 13335);
+        assert_eq!(parse_asn("13335").unwrap(), 13335);
+        assert_eq!(
+            parse_asn("ASN13335").unwrap_err().to_string(),
+            "invalid ASN `ASN13335`"
+        );
+        assert!(parse_asn("AS4294967296").is_err());
+        assert!(parse_asn("").is_err());
+    }
+
+    #[test]
+    fn roas() {
+        let roas: Vec<Roa> = serde_json::from_str(
+            r#"[
+                {"prefix": "192.0.2.0/24", "maxLength": 24, "asn": "AS64496"},
+                {"prefix": "2001:db8::/32", "max_length": "48", "origin": 64497}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            roas,
+            [
+                roa("192.0.2.0/24", 24, 64496),
+                roa("2001:db8::/32", 48, 64497)
+            ]
+        );
+        let malformed = r#"{"prefix": "192.0.2.0/24", "maxLength": 24, "asn": "ASx"}"#;
+        assert!(serde_json::from_str::<Roa>(malformed).is_err());
+        let prefix = r#"{"prefix": "192.0.2.0/33", "maxLength": 24, "asn": 64496}"#;
+        assert!(serde_json::from_str::<Roa>(prefix).is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let roas = [
+            roa("192.0.2.0/23", 24, 64496),
+            roa("198.51.100.0/24", 24, 0),
+        ];
+        let prefix = |prefix: &str| prefix.parse::<IpNet>().unwrap();
+        assert_eq!(
+            validate(&roas, prefix("192.0.2.0/24"), 64496).0,
+            Validity::Valid
+        );
+        // Another origin, or more specific than the maximum length
+        assert_eq!(
+            validate(&roas, prefix("192.0.2.0/24"), 64497).0,
+            Validity::Invalid
+        );
+        assert_eq!(
+            validate(&roas, prefix("192.0.2.0/25"), 64496).0,
+            Validity::Invalid
+        );
+        // Never valid for AS0
+        assert_eq!(
+            validate(&roas, prefix("198.51.100.0/24"), 0).0,
+            Validity::Invalid
+        );
+        let (validity, covering) = validate(&roas, prefix("203.0.113.0/24"), 64496);
+        assert_eq!(validity, Validity::NotFound);
+        assert!(covering.is_empty());
+        // The host bits are truncated
+        let (validity, covering) = validate(&roas, prefix("192.0.3.1/24"), 64496);
+        assert_eq!(validity, Validity::Valid);
+        assert_eq!(covering, [roas[0].clone()]);
+    }
+
+    #[test]
+    fn validation_states() {
+        assert_eq!(validity("valid").unwrap(), Validity::Valid);
+        for state in ["invalid", "invalid_asn", "invalid_length"] {
+            assert_eq!(validity(state).unwrap(), Validity::Invalid);
+        }
+        for state in ["unknown", "not-found", "not_found"] {
+            assert_eq!(validity(state).unwrap(), Validity::NotFound);
+        }
+        assert_eq!(
+            validity("VALID").unwrap_err().to_string(),
+            "unknown RPKI validation status `VALID`"
+        );
+    }
+
+    #[tokio::test]
+    async fn ripestat() {
+        let body = r#"{"data": {"status": "valid", "validating_roas": [
+            {"origin": "64496", "prefix": "192.0.2.0/24", "max_length": 24, "validity": "valid"}
+        ]}}"#;
+        let validation = validate_api(RpkiSource::Ripestat, body).await.unwrap();
+        assert_eq!(validation.validity, Validity::Valid);
+        assert_eq!(validation.roas, [roa("192.0.2.0/24", 24, 64496)]);
+
+        let body = r#"{"data": {"status": "unknown"}}"#;
+        let validation = validate_api(RpkiSource::Ripestat, body).await.unwrap();
+        assert_eq!(validation.validity, Validity::NotFound);
+        assert!(validation.roas.is_empty());
+    }
+
+    #[tokio::test]
+    async fn routinator() {
+        let body = r#"{"validated_route": {"validity": {"state": "invalid", "VRPs": {
+            "matched": [],
+            "unmatched_as": [{"asn": "AS64497", "prefix": "192.0.2.0/24", "max_length": "24"}],
+            "unmatched_length": []
+        }}}}"#;
+        let validation = validate_api(RpkiSource::Routinator, body).await.unwrap();
+        assert_eq!(validation.validity, Validity::Invalid);
+        assert_eq!(validation.source, RpkiSource::Routinator);
+        assert_eq!(validation.roas, [roa("192.0.2.0/24", 24, 64497)]);
+    }
+
+    #[tokio::test]
+    async fn malformed_responses() {
+        let error = validate_api(RpkiSource::Ripestat, r#"{"status": "valid"}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid RIPEstat rpki-validation response"
+        );
+        let body = r#"{"data": {"status": "pending"}}"#;
+        let error = validate_api(RpkiSource::Ripestat, body).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown RPKI validation status `pending`"
+        );
+        let error = validate_api(RpkiSource::Routinator, "<html></html>")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "invalid Routinator validity response");
+    }
+}
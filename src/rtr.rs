@@ -0,0 +1,389 @@
+use anyhow::{anyhow, bail, Context, Result};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, instrument};
+
+use crate::rpki::Roa;
+
+/// RTR port assigned by IANA, validators often listening on another one
+/// (3323 for Routinator, 8282 for StayRTR).
+pub const DEFAULT_PORT: u16 = 323;
+
+const SERIAL_NOTIFY: u8 = 0;
+const RESET_QUERY: u8 = 2;
+const CACHE_RESPONSE: u8 = 3;
+const IPV4_PREFIX: u8 = 4;
+const IPV6_PREFIX: u8 = 6;
+const END_OF_DATA: u8 = 7;
+const CACHE_RESET: u8 = 8;
+const ROUTER_KEY: u8 = 9;
+const ERROR_REPORT: u8 = 10;
+
+/// Error code of the error reports for an unsupported protocol version.
+const UNSUPPORTED_VERSION: u16 = 4;
+/// Largest PDU accepted, the router keys being the largest ones.
+const MAX_PDU_LENGTH: usize = 64 * 1024;
+
+/// Data of an RTR cache: its session, serial and validated ROA payloads.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub server: String,
+    /// Version of the protocol negotiated, 1 (RFC 8210) or 0 (RFC 6810)
+    pub version: u8,
+    pub session_id: u16,
+    pub serial: u32,
+    /// Intervals advised by the cache, in seconds, since version 1
+    pub refresh: Option<u32>,
+    pub retry: Option<u32>,
+    pub expire: Option<u32>,
+    #[serde(skip)]
+    pub roas: Vec<Roa>,
+    pub ipv4_prefixes: usize,
+    pub ipv6_prefixes: usize,
+    pub router_keys: usize,
+    /// Duration of the transfer of the data
+    pub duration_ms: f64,
+}
+
+struct Pdu {
+    version: u8,
+    kind: u8,
+    /// Session ID, error code or flags, depending on the type
+    field: u16,
+    body: Vec<u8>,
+}
+
+async fn read_pdu(stream: &mut TcpStream) -> Result<Pdu> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).await?;
+    let length = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    if !(8..=MAX_PDU_LENGTH).contains(&length) {
+        bail!("invalid PDU length {}", length);
+    }
+    let mut body = vec![0u8; length - 8];
+    stream.read_exact(&mut body).await?;
+    Ok(Pdu {
+        version: header[0],
+        kind: header[1],
+        field: u16::from_be_bytes([header[2], header[3]]),
+        body,
+    })
+}
+
+fn u32_at(body: &[u8], offset: usize) -> Result<u32> {
+    body.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| anyhow!("truncated PDU"))
+}
+
+/// Validated ROA payload of an IPv4 or IPv6 prefix PDU, if announced rather than withdrawn.
+fn prefix(pdu: &Pdu) -> Result<Option<Roa>> {
+    let (address, asn) = match pdu.kind {
+        IPV4_PREFIX if pdu.body.len() >= 12 => {
+            let octets: [u8; 4] = pdu.body[4..8].try_into().unwrap();
+            (IpAddr::V4(Ipv4Addr::from(octets)), u32_at(&pdu.body, 8)?)
+        }
+        IPV6_PREFIX if pdu.body.len() >= 24 => {
+            let octets: [u8; 16] = pdu.body[4..20].try_into().unwrap();
+            (IpAddr::V6(Ipv6Addr::from(octets)), u32_at(&pdu.body, 20)?)
+        }
+        _ => bail!("truncated prefix PDU"),
+    };
+    let announce = pdu.body[0] & 1 == 1;
+    let prefix = IpNet::new(address, pdu.body[1]).context("invalid prefix length")?;
+    Ok(announce.then_some(Roa {
+        prefix,
+        max_length: pdu.body[2],
+        asn,
+    }))
+}
+
+/// Error code and text of an error report.
+fn error_report(pdu: &Pdu) -> String {
+    let text = u32_at(&pdu.body, 0)
+        .ok()
+        .and_then(|pdu_length| {
+            let offset = 4 + pdu_length as usize;
+            let text_length = u32_at(&pdu.body, offset).ok()? as usize;
+            pdu.body.get(offset + 4..offset + 4 + text_length)
+        })
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default();
+    format!("error report {}: {}", pdu.field, text)
+}
+
+/// Reset query of a version, then the cache response up to its end of data.
+/// Tells if the version is supported by the cache.
+async fn transfer(stream: &mut TcpStream, version: u8, snapshot: &mut Snapshot) -> Result<bool> {
+    stream
+        .write_all(&[version, RESET_QUERY, 0, 0, 0, 0, 0, 8])
+        .await?;
+    loop {
+        let pdu = read_pdu(stream).await?;
+        match pdu.kind {
+            ERROR_REPORT if pdu.field == UNSUPPORTED_VERSION && version > 0 => return Ok(false),
+            ERROR_REPORT => bail!("{}", error_report(&pdu)),
+            _ if pdu.version != version => {
+                bail!("unexpected version {} of a PDU", pdu.version)
+            }
+            CACHE_RESPONSE => snapshot.session_id = pdu.field,
+            IPV4_PREFIX | IPV6_PREFIX => {
+                if let Some(roa) = prefix(&pdu)? {
+                    match roa.prefix {
+                        IpNet::V4(_) => snapshot.ipv4_prefixes += 1,
+                        IpNet::V6(_) => snapshot.ipv6_prefixes += 1,
+                    }
+                    snapshot.roas.push(roa);
+                }
+            }
+            ROUTER_KEY => snapshot.router_keys += 1,
+            END_OF_DATA => {
+                snapshot.session_id = pdu.field;
+                snapshot.serial = u32_at(&pdu.body, 0)?;
+                if version >= 1 {
+                    snapshot.refresh = Some(u32_at(&pdu.body, 4)?);
+                    snapshot.retry = Some(u32_at(&pdu.body, 8)?);
+                    snapshot.expire = Some(u32_at(&pdu.body, 12)?);
+                }
+                return Ok(true);
+            }
+            CACHE_RESET => bail!("cache reset, the cache has no data yet"),
+            SERIAL_NOTIFY => {}
+            kind => bail!("unexpected PDU type {}", kind),
+        }
+    }
+}
+
+async fn connect(server: &str) -> Result<TcpStream> {
+    let stream = match server.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => TcpStream::connect(server).await,
+        _ => TcpStream::connect((server, DEFAULT_PORT)).await,
+    };
+    stream.with_context(|| format!("failed to connect to {}", server))
+}
+
+/// Fetch all the data of an RTR cache over TCP, with a reset query of version 1,
+/// falling back to version 0 for the caches not supporting it.
+#[instrument(level = "debug", name = "rtr", skip(timeout))]
+pub async fn fetch(server: &str, timeout: Duration) -> Result<Snapshot> {
+    let fetch = async {
+        let start = Instant::now();
+        for version in [1, 0] {
+            let mut snapshot = Snapshot {
+                server: server.to_string(),
+                version,
+                session_id: 0,
+                serial: 0,
+                refresh: None,
+                retry: None,
+                expire: None,
+                roas: Vec::new(),
+                ipv4_prefixes: 0,
+                ipv6_prefixes: 0,
+                router_keys: 0,
+                duration_ms: 0.0,
+            };
+            let mut stream = connect(server).await?;
+            if transfer(&mut stream, version, &mut snapshot).await? {
+                snapshot.duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+                debug!(
+                    session_id = snapshot.session_id,
+                    serial = snapshot.serial,
+                    roas = snapshot.roas.len(),
+                    "Fetched the RTR cache data"
+                );
+                return Ok(snapshot);
+            }
+            debug!(version, "Version not supported by the cache");
+        }
+        bail!("no version of the protocol supported by the cache")
+    };
+    tokio::time::timeout(timeout, fetch)
+        .await
+        .map_err(|_| anyhow!("timed out"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn pdu(version: u8, kind: u8, field: u16, body: &[u8]) -> Vec<u8> {
+        let mut pdu = vec![version, kind];
+        pdu.extend_from_slice(&field.to_be_bytes());
+        pdu.extend_from_slice(&(8 + body.len() as u32).to_be_bytes());
+        pdu.extend_from_slice(body);
+        pdu
+    }
+
+    fn ipv4_prefix(flags: u8, prefix: [u8; 4], length: u8, max_length: u8, asn: u32) -> Vec<u8> {
+        let mut body = vec![flags, length, max_length, 0];
+        body.extend_from_slice(&prefix);
+        body.extend_from_slice(&asn.to_be_bytes());
+        body
+    }
+
+    /// Cache answering the reset queries of each version with its PDUs.
+    async fn cache(responses: Vec<(u8, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for (version, response) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut query = [0u8; 8];
+                stream.read_exact(&mut query).await.unwrap();
+                assert_eq!(query, [version, RESET_QUERY, 0, 0, 0, 0, 0, 8]);
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+        server
+    }
+
+    #[test]
+    fn parse_prefixes() {
+        let announce = Pdu {
+            version: 1,
+            kind: IPV4_PREFIX,
+            field: 0,
+            body: ipv4_prefix(1, [192, 0, 2, 0], 24, 24, 64500),
+        };
+        assert_eq!(
+            prefix(&announce).unwrap(),
+            Some(Roa {
+                prefix: "192.0.2.0/24".parse().unwrap(),
+                max_length: 24,
+                asn: 64500,
+            })
+        );
+
+        let mut body = vec![1, 48, 64, 0];
+        body.extend_from_slice(&"2001:db8::".parse::<Ipv6Addr>().unwrap().octets());
+        body.extend_from_slice(&64501u32.to_be_bytes());
+        let announce = Pdu {
+            version: 1,
+            kind: IPV6_PREFIX,
+            field: 0,
+            body,
+        };
+        assert_eq!(
+            prefix(&announce).unwrap().unwrap().prefix,
+            "2001:db8::/48".parse::<IpNet>().unwrap()
+        );
+
+        let withdraw = Pdu {
+            version: 1,
+            kind: IPV4_PREFIX,
+            field: 0,
+            body: ipv4_prefix(0, [192, 0, 2, 0], 24, 24, 64500),
+        };
+        assert!(prefix(&withdraw).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_malformed_prefixes() {
+        let truncated = Pdu {
+            version: 1,
+            kind: IPV4_PREFIX,
+            field: 0,
+            body: ipv4_prefix(1, [192, 0, 2, 0], 24, 24, 64500)[..11].to_vec(),
+        };
+        assert!(prefix(&truncated).is_err());
+        let invalid = Pdu {
+            body: ipv4_prefix(1, [192, 0, 2, 0], 33, 33, 64500),
+            ..truncated
+        };
+        assert!(prefix(&invalid).is_err());
+    }
+
+    #[test]
+    fn parse_error_report() {
+        let mut body = 8u32.to_be_bytes().to_vec();
+        body.extend_from_slice(&[1, RESET_QUERY, 0, 0, 0, 0, 0, 8]);
+        body.extend_from_slice(&4u32.to_be_bytes());
+        body.extend_from_slice(b"oops");
+        let report = Pdu {
+            version: 1,
+            kind: ERROR_REPORT,
+            field: 2,
+            body,
+        };
+        assert_eq!(error_report(&report), "error report 2: oops");
+        // A text length past the end of the PDU
+        let truncated = Pdu {
+            body: report.body[..18].to_vec(),
+            ..report
+        };
+        assert_eq!(error_report(&truncated), "error report 2: ");
+    }
+
+    #[tokio::test]
+    async fn fetch_snapshot() {
+        let mut end_of_data = 42u32.to_be_bytes().to_vec();
+        for interval in [3600u32, 600, 7200] {
+            end_of_data.extend_from_slice(&interval.to_be_bytes());
+        }
+        let response = [
+            pdu(1, CACHE_RESPONSE, 7, &[]),
+            pdu(
+                1,
+                IPV4_PREFIX,
+                0,
+                &ipv4_prefix(1, [192, 0, 2, 0], 24, 24, 64500),
+            ),
+            pdu(1, ROUTER_KEY, 0, &[0; 24]),
+            pdu(1, END_OF_DATA, 7, &end_of_data),
+        ]
+        .concat();
+        let server = cache(vec![(1, response)]).await;
+        let snapshot = fetch(&server, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(
+            (snapshot.version, snapshot.session_id, snapshot.serial),
+            (1, 7, 42)
+        );
+        assert_eq!(
+            (snapshot.refresh, snapshot.retry, snapshot.expire),
+            (Some(3600), Some(600), Some(7200))
+        );
+        assert_eq!(
+            (
+                snapshot.ipv4_prefixes,
+                snapshot.ipv6_prefixes,
+                snapshot.router_keys
+            ),
+            (1, 0, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_version_0() {
+        let unsupported = pdu(0, ERROR_REPORT, UNSUPPORTED_VERSION, &[0; 8]);
+        let response = [
+            pdu(0, CACHE_RESPONSE, 7, &[]),
+            pdu(0, END_OF_DATA, 7, &42u32.to_be_bytes()),
+        ]
+        .concat();
+        let server = cache(vec![(1, unsupported), (0, response)]).await;
+        let snapshot = fetch(&server, Duration::from_secs(5)).await.unwrap();
+        assert_eq!((snapshot.version, snapshot.serial), (0, 42));
+        assert_eq!(snapshot.refresh, None);
+        assert!(snapshot.roas.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_invalid_pdus() {
+        // PDU shorter than its header
+        let server = cache(vec![(1, vec![1, CACHE_RESPONSE, 0, 7, 0, 0, 0, 4])]).await;
+        assert!(fetch(&server, Duration::from_secs(5)).await.is_err());
+
+        let server = cache(vec![(1, pdu(1, CACHE_RESET, 0, &[]))]).await;
+        let error = fetch(&server, Duration::from_secs(5)).await.unwrap_err();
+        assert_eq!(error.to_string(), "cache reset, the cache has no data yet");
+
+        let server = cache(vec![(1, pdu(0, CACHE_RESPONSE, 7, &[]))]).await;
+        assert!(fetch(&server, Duration::from_secs(5)).await.is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::Result;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::info;
+
+use crate::checker::RovResult;
+use crate::daemon::{Daemon, Shutdown};
+use crate::duration;
+use crate::history::{Entry, History, Summary};
+use crate::output::html;
+use crate::prometheus;
+use crate::verdict::Verdict;
+
+#[derive(Clone)]
+struct AppState {
+    handle: PrometheusHandle,
+    /// Result of the last check of each profile, none until the first one is done
+    latest: Arc<Mutex<BTreeMap<Option<String>, RovResult>>>,
+    history: Option<Arc<Mutex<History>>>,
+    /// Check triggers of the daemons, by profile
+    triggers: Arc<BTreeMap<Option<String>, Arc<Notify>>>,
+}
+
+impl AppState {
+    /// Names of the profiles, when running several of them.
+    fn profiles(&self) -> Vec<String> {
+        self.triggers.keys().flatten().cloned().collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfileQuery {
+    profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    since: Option<DateTime<Utc>>,
+    verdict: Option<Verdict>,
+    profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DashboardQuery {
+    window: Option<String>,
+    profile: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HistoryOutput {
+    entries: Vec<Entry>,
+    summary: Summary,
+}
+
+async fn metrics(State(state): State<AppState>) -> String {
+    state.handle.render()
+}
+
+fn unknown_profile(profile: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!("unknown profile `{}`", profile),
+    )
+        .into_response()
+}
+
+fn of_profile(entry: &Entry, profile: Option<&str>) -> bool {
+    profile.is_none() || entry.result.labels.get("profile").map(String::as_str) == profile
+}
+
+/// Last result of a profile, or of the only daemon, or the last results by profile.
+async fn status(State(state): State<AppState>, Query(query): Query<ProfileQuery>) -> Response {
+    let latest = state.latest.lock().unwrap();
+    let key = match (query.profile, state.triggers.len()) {
+        (Some(profile), _) if !state.triggers.contains_key(&Some(profile.clone())) => {
+            return unknown_profile(&profile)
+        }
+        (Some(profile), _) => Some(profile),
+        (None, 1) => state.triggers.keys().next().cloned().flatten(),
+        (None, _) => {
+            let results = latest
+                .iter()
+                .filter_map(|(profile, result)| Some((profile.clone()?, result)))
+                .collect::<BTreeMap<_, _>>();
+            return Json(results).into_response();
+        }
+    };
+    match latest.get(&key) {
+        Some(result) => Json(result).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "no check done yet").into_response(),
+    }
+}
+
+/// Stored results since a time and of a verdict, queried on a blocking thread not to stall the
+/// other requests.
+async fn query(
+    history: &Arc<Mutex<History>>,
+    since: Option<DateTime<Utc>>,
+    verdict: Option<Verdict>,
+) -> Result<Vec<Entry>> {
+    let history = history.clone();
+    tokio::task::spawn_blocking(move || history.lock().unwrap().query(since, verdict)).await?
+}
+
+async fn show_history(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let Some(history) = &state.history else {
+        return (StatusCode::NOT_FOUND, "no history database configured").into_response();
+    };
+    match self::query(history, query.since, query.verdict).await {
+        Ok(mut entries) => {
+            entries.retain(|entry| of_profile(entry, query.profile.as_deref()));
+            Json(HistoryOutput {
+                summary: Summary::new(&entries),
+                entries,
+            })
+            .into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+    }
+}
+
+/// Web dashboard of the stored results, or of the last one without history.
+async fn dashboard(State(state): State<AppState>, Query(query): Query<DashboardQuery>) -> Response {
+    let window = query.window.as_deref().unwrap_or("24h");
+    if !html::WINDOWS.contains(&window) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("unknown window `{}`", window),
+        )
+            .into_response();
+    }
+    let profile = query.profile.as_deref();
+    if profile.is_some_and(|profile| !state.profiles().iter().any(|name| name == profile)) {
+        return unknown_profile(profile.unwrap_or_default());
+    }
+    let mut entries = match &state.history {
+        Some(history) => {
+            let since = duration::parse_seconds(window)
+                .ok()
+                .and_then(|window| chrono::Duration::from_std(window).ok())
+                .map(|window| Utc::now() - window);
+            match self::query(history, since, None).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()
+                }
+            }
+        }
+        None => state
+            .latest
+            .lock()
+            .unwrap()
+            .values()
+            .map(|result| Entry {
+                id: 0,
+                asn: result.asn(),
+                result: result.clone(),
+            })
+            .collect(),
+    };
+    entries.retain(|entry| of_profile(entry, profile));
+    Html(html::dashboard(
+        &entries,
+        window,
+        &state.profiles(),
+        profile,
+    ))
+    .into_response()
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Ready once a check cycle of every profile completed.
+async fn readyz(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    match state.latest.lock().unwrap().len() == state.triggers.len() {
+        true => (StatusCode::OK, "ok"),
+        false => (StatusCode::SERVICE_UNAVAILABLE, "no check done yet"),
+    }
+}
+
+/// Check a profile, or all of them, right away.
+async fn check(State(state): State<AppState>, Query(query): Query<ProfileQuery>) -> Response {
+    match query.profile {
+        Some(profile) => match state.triggers.get(&Some(profile.clone())) {
+            Some(trigger) => trigger.notify_one(),
+            None => return unknown_profile(&profile),
+        },
+        None => state
+            .triggers
+            .values()
+            .for_each(|trigger| trigger.notify_one()),
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Run the checks of the daemons and expose their results as Prometheus metrics on `address`,
+/// along with a web dashboard and a JSON API serving the last results and the `history`, and
+/// triggering checks.
+pub async fn serve(
+    daemons: Vec<Daemon>,
+    address: SocketAddr,
+    history: Option<History>,
+) -> Result<()> {
+    let mut triggers = BTreeMap::new();
+    let daemons = daemons
+        .into_iter()
+        .map(|daemon| {
+            let trigger = Arc::new(Notify::new());
+            triggers.insert(daemon.profile().map(String::from), trigger.clone());
+            daemon.with_trigger(trigger)
+        })
+        .collect::<Vec<_>>();
+    let state = AppState {
+        handle: prometheus::install()?,
+        latest: Arc::new(Mutex::new(BTreeMap::new())),
+        history: history.map(|history| Arc::new(Mutex::new(history))),
+        triggers: Arc::new(triggers),
+    };
+    let latest = state.latest.clone();
+
+    let app = Router::new()
+        .route("/", get(dashboard))
+        .route("/metrics", get(metrics))
+        .route("/status", get(status))
+        .route("/history", get(show_history))
+        .route("/check", post(check))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    info!("Listening on {}", address);
+
+    // Both stop on SIGTERM or SIGINT, once the in-flight check and requests are done
+    let mut shutdown = Shutdown::new()?;
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown.recv().await;
+    });
+    let daemons = daemons.into_iter().map(|daemon| {
+        let (profile, latest) = (daemon.profile().map(String::from), latest.clone());
+        daemon.run(move |result, _| {
+            info!("{}: {}", result.verdict, result.reason);
+            prometheus::record(result);
+            latest
+                .lock()
+                .unwrap()
+                .insert(profile.clone(), result.clone());
+            Ok(())
+        })
+    });
+    let daemons = try_join_all(daemons.collect::<Vec<_>>());
+
+    tokio::try_join!(daemons, async { Ok(server.await?) })?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::checker::RovResult;
+
+/// Ed25519 signature of a check result, over its JSON without the signature, the keys sorted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    /// Public key of the probe, base64-encoded
+    pub public_key: String,
+    /// Signature, base64-encoded
+    pub signature: String,
+}
+
+/// Whether a signing key is given inline rather than as the path of its file.
+pub fn is_inline(source: &str) -> bool {
+    source.trim_start().starts_with("-----BEGIN")
+}
+
+/// Load a PKCS#8 PEM signing key, given inline or as the path of its file, e.g. generated with
+/// `openssl genpkey -algorithm ed25519`.
+pub fn load_key(source: &str) -> Result<SigningKey> {
+    let pem = match is_inline(source) {
+        true => source.to_string(),
+        false => std::fs::read_to_string(source)
+            .with_context(|| format!("failed to read the signing key {}", source))?,
+    };
+    SigningKey::from_pkcs8_pem(&pem).map_err(|e| anyhow!("invalid signing key: {}", e))
+}
+
+/// Parse a public key: base64-encoded as in the signatures, or as a PEM file, e.g. output by
+/// `openssl pkey -pubout`.
+pub fn parse_public_key(source: &str) -> Result<VerifyingKey> {
+    if Path::new(source).exists() {
+        let pem = std::fs::read_to_string(source)
+            .with_context(|| format!("failed to read the public key {}", source))?;
+        return VerifyingKey::from_public_key_pem(&pem)
+            .map_err(|e| anyhow!("invalid public key {}: {}", source, e));
+    }
+    let bytes = STANDARD
+        .decode(source)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .with_context(|| format!("invalid public key `{}`", source))?;
+    VerifyingKey::from_bytes(&bytes).with_context(|| format!("invalid public key `{}`", source))
+}
+
+/// JSON value with the keys of its objects sorted, whatever the order kept by `serde_json`.
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries = object.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+            let entries = entries
+                .into_iter()
+                .map(|(key, value)| (key.clone(), canonical(value)));
+            Value::Object(entries.collect())
+        }
+        Value::Array(values) => Value::Array(values.iter().map(canonical).collect()),
+        value => value.clone(),
+    }
+}
+
+/// Signed bytes of a JSON result, without its signature and with sorted keys.
+fn message(result: &Value) -> Result<Vec<u8>> {
+    let mut result = canonical(result);
+    let Some(object) = result.as_object_mut() else {
+        bail!("not a check result");
+    };
+    object.remove("signature");
+    Ok(serde_json::to_vec(&result)?)
+}
+
+/// Sign a check result, replacing its signature if any.
+pub fn sign(key: &SigningKey, result: &mut RovResult) -> Result<()> {
+    result.signature = None;
+    let message = message(&serde_json::to_value(&*result)?)?;
+    result.signature = Some(Signature {
+        public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+        signature: STANDARD.encode(key.sign(&message).to_bytes()),
+    });
+    Ok(())
+}
+
+/// Verify the signature of a JSON check result, returning the base64-encoded key it was signed
+/// with. Unless `trusted` is empty, the key must be one of them.
+pub fn verify(result: &Value, trusted: &[VerifyingKey]) -> Result<String> {
+    let signature = result.get("signature").context("unsigned result")?;
+    let signature: Signature =
+        serde_json::from_value(signature.clone()).context("invalid signature")?;
+    let key = parse_public_key(&signature.public_key)?;
+    if !trusted.is_empty() && !trusted.contains(&key) {
+        bail!("signed by the untrusted key {}", signature.public_key);
+    }
+    let bytes = STANDARD
+        .decode(&signature.signature)
+        .context("invalid signature")?;
+    let bytes = ed25519_dalek::Signature::from_slice(&bytes).context("invalid signature")?;
+    key.verify(&message(result)?, &bytes)
+        .map_err(|_| anyhow!("invalid signature"))?;
+    Ok(signature.public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::tests::result;
+
+    fn signed(key: &SigningKey) -> Value {
+        let mut result = result();
+        sign(key, &mut result).unwrap();
+        serde_json::to_value(&result).unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let result = signed(&key);
+        let public_key = verify(&result, &[]).unwrap();
+        assert_eq!(public_key, STANDARD.encode(key.verifying_key().as_bytes()));
+        assert_eq!(verify(&result, &[key.verifying_key()]).unwrap(), public_key);
+    }
+
+    #[test]
+    fn tampered() {
+        let mut result = signed(&SigningKey::from_bytes(&[7; 32]));
+        result["pairs"][0]["invalid"]["success"] = Value::Bool(true);
+        assert_eq!(
+            verify(&result, &[]).unwrap_err().to_string(),
+            "invalid signature"
+        );
+    }
+
+    #[test]
+    fn untrusted() {
+        let result = signed(&SigningKey::from_bytes(&[7; 32]));
+        let trusted = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        let error = verify(&result, &[trusted]).unwrap_err();
+        assert!(error.to_string().starts_with("signed by the untrusted key"));
+    }
+
+    #[test]
+    fn key_order() {
+        let result = serde_json::json!({ "b": { "d": 1, "c": [{ "f": 2, "e": 3 }] }, "a": 0 });
+        assert_eq!(
+            message(&result).unwrap(),
+            br#"{"a":0,"b":{"c":[{"e":3,"f":2}],"d":1}}"#
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::io::{self, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+use url::Url;
+
+/// Daemon facility.
+const FACILITY: u8 = 3;
+const APP_NAME: &str = "rovcheck";
+
+enum Transport {
+    Unix(UnixDatagram, PathBuf),
+    Udp(UdpSocket),
+    Tcp(Option<TcpStream>, String),
+}
+
+impl Transport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self {
+            Transport::Unix(socket, path) => socket.send_to(message, path).map(|_| ()),
+            Transport::Udp(socket) => socket.send(message).map(|_| ()),
+            Transport::Tcp(stream, address) => {
+                // Octet-counting framing (RFC 6587), reconnecting once if the connection was lost
+                let mut frame = format!("{} ", message.len()).into_bytes();
+                frame.extend_from_slice(message);
+                if let Some(connection) = stream {
+                    if connection.write_all(&frame).is_ok() {
+                        return Ok(());
+                    }
+                }
+                let mut connection = TcpStream::connect(address.as_str())?;
+                let result = connection.write_all(&frame);
+                *stream = Some(connection);
+                result
+            }
+        }
+    }
+}
+
+/// Syslog destination, sending the logs as RFC 5424 messages.
+#[derive(Clone)]
+pub struct Syslog {
+    transport: Arc<Mutex<Transport>>,
+    hostname: String,
+}
+
+fn address(url: &Url, default_port: u16) -> Result<String> {
+    let Some(host) = url.host_str() else {
+        bail!("missing host in syslog URL {}", url);
+    };
+    Ok(format!("{}:{}", host, url.port().unwrap_or(default_port)))
+}
+
+impl Syslog {
+    /// Syslog to a local socket (`unix:///dev/log`), or to a remote server (`udp://host:514`, `tcp://host:601`).
+    pub fn new(url: &Url) -> Result<Self> {
+        let transport = match url.scheme() {
+            "unix" => Transport::Unix(UnixDatagram::unbound()?, PathBuf::from(url.path())),
+            "udp" => {
+                let socket = UdpSocket::bind("[::]:0").or_else(|_| UdpSocket::bind("0.0.0.0:0"))?;
+                socket
+                    .connect(address(url, 514)?)
+                    .with_context(|| format!("failed to resolve syslog server {}", url))?;
+                Transport::Udp(socket)
+            }
+            "tcp" => Transport::Tcp(None, address(url, 601)?),
+            scheme => bail!("unsupported syslog URL scheme `{}`", scheme),
+        };
+        let hostname = hostname::get()
+            .ok()
+            .and_then(|hostname| hostname.into_string().ok())
+            .map(|hostname| header_field(&hostname))
+            .unwrap_or_else(|| "-".to_string());
+        Ok(Syslog {
+            transport: Arc::new(Mutex::new(transport)),
+            hostname,
+        })
+    }
+
+    fn message(&self, severity: u8) -> Message {
+        Message {
+            syslog: self.clone(),
+            severity,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+/// Header field of a message, made of printable US-ASCII characters, `-` if empty.
+fn header_field(value: &str) -> String {
+    let value = value
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(255)
+        .collect::<String>();
+    match value.is_empty() {
+        true => "-".to_string(),
+        false => value,
+    }
+}
+
+/// RFC 5424 message, without structured data: the text is the free-form MSG, not escaped.
+fn format(severity: u8, timestamp: DateTime<Utc>, hostname: &str, pid: u32, text: &str) -> String {
+    format!(
+        "<{}>1 {} {} {} {} - - {}",
+        FACILITY * 8 + severity,
+        timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+        hostname,
+        APP_NAME,
+        pid,
+        text
+    )
+}
+
+/// Buffer of a single log event, sent when dropped.
+pub struct Message {
+    syslog: Syslog,
+    severity: u8,
+    buffer: Vec<u8>,
+}
+
+impl Write for Message {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Message {
+    fn drop(&mut self) {
+        let text = String::from_utf8_lossy(&self.buffer);
+        let text = text.trim_end();
+        if text.is_empty() {
+            return;
+        }
+        let message = format(
+            self.severity,
+            Utc::now(),
+            &self.syslog.hostname,
+            std::process::id(),
+            text,
+        );
+        // Nowhere to report a failure to log
+        let _ = self
+            .syslog
+            .transport
+            .lock()
+            .unwrap()
+            .send(message.as_bytes());
+    }
+}
+
+impl<'a> MakeWriter<'a> for Syslog {
+    type Writer = Message;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.message(6)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let severity = match *meta.level() {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            Level::DEBUG | Level::TRACE => 7,
+        };
+        self.message(severity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        let timestamp = DateTime::from_timestamp(1767225600, 123_456_000).unwrap();
+        assert_eq!(
+            format(6, timestamp, "probe1", 4242, "ENFORCED: valid beacon reachable"),
+            "<30>1 2026-01-01T00:00:00.123456Z probe1 rovcheck 4242 - - ENFORCED: valid beacon reachable"
+        );
+        // Daemon facility, with the severity of the level
+        assert!(format(3, timestamp, "-", 1, "error").starts_with("<27>1 "));
+        assert!(format(7, timestamp, "-", 1, "debug").starts_with("<31>1 "));
+        // Without structured data, its special characters are left as is in the text
+        assert_eq!(
+            format(4, timestamp, "probe1", 1, r#"[beacon="a\b"]"#),
+            r#"<28>1 2026-01-01T00:00:00.123456Z probe1 rovcheck 1 - - [beacon="a\b"]"#
+        );
+    }
+
+    #[test]
+    fn header_fields() {
+        assert_eq!(header_field("probe1.example"), "probe1.example");
+        assert_eq!(header_field("probe 1\u{e9}"), "probe_1_");
+        assert_eq!(header_field(""), "-");
+        assert_eq!(header_field(&"a".repeat(300)).len(), 255);
+    }
+}
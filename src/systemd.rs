@@ -0,0 +1,44 @@
+use sd_notify::NotifyState;
+use std::time::Duration;
+use tracing::debug;
+
+/// Notify systemd, for `Type=notify` units. This is a no-op when not run by systemd.
+fn notify(state: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(state) {
+        debug!("Failed to notify systemd: {}", e);
+    }
+}
+
+/// Tell systemd that the service is up.
+pub fn ready() {
+    notify(&[NotifyState::Ready]);
+}
+
+/// Set the status shown by `systemctl status`.
+pub fn status(status: &str) {
+    notify(&[NotifyState::Status(status)]);
+}
+
+/// Tell systemd that the service is reloading its configuration, until it is ready again.
+pub fn reloading() {
+    match NotifyState::monotonic_usec_now() {
+        Ok(now) => notify(&[NotifyState::Reloading, now]),
+        Err(e) => debug!("Failed to read the monotonic clock: {}", e),
+    }
+}
+
+/// Tell systemd that the service is stopping.
+pub fn stopping() {
+    notify(&[NotifyState::Stopping]);
+}
+
+/// Tell systemd that the service is alive.
+pub fn keepalive() {
+    notify(&[NotifyState::Watchdog]);
+}
+
+/// Interval at which the watchdog must be kept alive, if it is enabled for the unit:
+/// half of its `WatchdogSec=`.
+pub fn watchdog() -> Option<Duration> {
+    sd_notify::watchdog_enabled().map(|timeout| timeout / 2)
+}
@@ -0,0 +1,267 @@
+use anyhow::{bail, Context, Result};
+use rustls_platform_verifier::Verifier;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::ClientConnection;
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, version, ClientConfig, DigitallySignedStruct, SignatureScheme, SupportedProtocolVersion,
+};
+
+/// Lowest TLS version accepted from the beacons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            _ => bail!("unsupported TLS version `{}`, expected 1.2 or 1.3", s),
+        }
+    }
+}
+
+impl From<TlsVersion> for reqwest::tls::Version {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
+}
+
+/// SHA-256 fingerprint of a certificate, as lowercase hexadecimal.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Fingerprint(String);
+
+impl Fingerprint {
+    pub fn of(certificate: &CertificateDer<'_>) -> Self {
+        let digest = Sha256::digest(certificate.as_ref());
+        Fingerprint(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = String;
+
+    /// Hexadecimal SHA-256 digest, optionally colon separated like the output of
+    /// `openssl x509 -fingerprint -sha256`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digest = s.strip_prefix("sha256:").unwrap_or(s);
+        // Either no separator, or one between each byte
+        let separated = digest.contains(':');
+        let bytes = digest.split(':').collect::<Vec<_>>();
+        let hex = bytes.concat().to_ascii_lowercase();
+        if hex.len() != 64
+            || !hex.chars().all(|c| c.is_ascii_hexdigit())
+            || (separated && bytes.iter().any(|byte| byte.len() != 2))
+        {
+            return Err(format!(
+                "invalid certificate fingerprint `{}`, expected a SHA-256 digest",
+                s
+            ));
+        }
+        Ok(Fingerprint(hex))
+    }
+}
+
+impl TryFrom<String> for Fingerprint {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Fingerprint> for String {
+    fn from(fingerprint: Fingerprint) -> Self {
+        fingerprint.0
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Certificates of a PEM file.
+pub fn certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read the certificates of {}", path.display()))?;
+    if certificates.is_empty() {
+        bail!("no certificates found in {}", path.display());
+    }
+    Ok(certificates)
+}
+
+/// Verifier accepting any certificate, only checking the handshake signatures.
+#[derive(Debug)]
+struct Insecure(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for Insecure {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Client configuration of the TLS connections to the beacons, verifying the certificates with
+/// the platform verifier along with the extra CA certificates, unless `insecure`.
+pub fn client_config(
+    min_version: Option<TlsVersion>,
+    cacert: Option<&Path>,
+    insecure: bool,
+) -> Result<ClientConfig> {
+    let provider = CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(crypto::aws_lc_rs::default_provider()));
+    let versions: &[&SupportedProtocolVersion] = match min_version {
+        Some(TlsVersion::Tls13) => &[&version::TLS13],
+        Some(TlsVersion::Tls12) | None => &[&version::TLS13, &version::TLS12],
+    };
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(versions)?
+        .dangerous();
+    let builder = if insecure {
+        builder.with_custom_certificate_verifier(Arc::new(Insecure(provider)))
+    } else {
+        let roots = match cacert {
+            Some(cacert) => certificates(cacert)?,
+            None => Vec::new(),
+        };
+        let verifier = Verifier::new_with_extra_roots(roots, provider)?;
+        builder.with_custom_certificate_verifier(Arc::new(verifier))
+    };
+    Ok(builder.with_no_client_auth())
+}
+
+/// Negotiated version and cipher suite of a TLS connection, e.g. `TLSv1.3` and `TLS13_AES_128_GCM_SHA256`.
+pub fn negotiated(connection: &ClientConnection) -> (Option<String>, Option<String>) {
+    let version = connection
+        .protocol_version()
+        .map(|version| match version.as_str() {
+            Some(name) => name.replace('_', "."),
+            None => format!("{:?}", version),
+        });
+    let cipher = connection.negotiated_cipher_suite().map(|suite| {
+        let suite = suite.suite();
+        suite
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:?}", suite))
+    });
+    (version, cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 digest of the empty input.
+    const EMPTY: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn fingerprints() {
+        assert_eq!(
+            Fingerprint::of(&CertificateDer::from(Vec::new())).to_string(),
+            EMPTY
+        );
+        let colons = EMPTY
+            .as_bytes()
+            .chunks(2)
+            .map(|byte| std::str::from_utf8(byte).unwrap().to_ascii_uppercase())
+            .collect::<Vec<_>>()
+            .join(":");
+        for fingerprint in [
+            EMPTY.to_string(),
+            EMPTY.to_ascii_uppercase(),
+            colons.clone(),
+            format!("sha256:{}", EMPTY),
+            format!("sha256:{}", colons),
+        ] {
+            assert_eq!(
+                fingerprint.parse::<Fingerprint>().unwrap().to_string(),
+                EMPTY
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_fingerprints() {
+        for fingerprint in [
+            EMPTY[..62].to_string(),
+            format!("{}00", EMPTY),
+            EMPTY.replacen('e', "g", 1),
+            format!("sha256:sha256:{}", EMPTY),
+            format!("{}:{}", &EMPTY[..3], &EMPTY[3..]),
+            format!("{}:", EMPTY),
+            "e3:b0".to_string(),
+            String::new(),
+        ] {
+            assert_eq!(
+                fingerprint.parse::<Fingerprint>().unwrap_err(),
+                format!(
+                    "invalid certificate fingerprint `{}`, expected a SHA-256 digest",
+                    fingerprint
+                )
+            );
+        }
+    }
+}
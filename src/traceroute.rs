@@ -0,0 +1,583 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpSocket, UdpSocket};
+use tokio::task::JoinSet;
+use tracing::debug;
+use url::Url;
+
+use crate::icmp;
+use crate::probe::Connect;
+use crate::resolver::AddressFamily;
+
+/// Highest TTL probed.
+pub const MAX_HOPS: u8 = 30;
+/// Destination port of the UDP probe of the first hop, incremented for the next ones like traceroute.
+const UDP_PORT: u16 = 33434;
+/// Interval between the starts of the rounds of a path analysis.
+const ROUND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Protocol of the traceroute probes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TracerouteMode {
+    /// UDP datagrams to high ports, answered by a port unreachable error
+    #[default]
+    Udp,
+    /// ICMP echo requests
+    Icmp,
+    /// TCP connects to the port of the target, passing most firewalls
+    Tcp,
+}
+
+impl FromStr for TracerouteMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "udp" => Ok(TracerouteMode::Udp),
+            "icmp" => Ok(TracerouteMode::Icmp),
+            "tcp" => Ok(TracerouteMode::Tcp),
+            _ => bail!("unknown traceroute mode `{}`", s),
+        }
+    }
+}
+
+impl std::fmt::Display for TracerouteMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TracerouteMode::Udp => write!(f, "udp"),
+            TracerouteMode::Icmp => write!(f, "icmp"),
+            TracerouteMode::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// A hop of a path, without address if it did not answer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Hop {
+    pub ttl: u8,
+    pub ip: Option<IpAddr>,
+    pub rtt_ms: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Traceroute {
+    pub target: Option<IpAddr>,
+    pub mode: TracerouteMode,
+    /// Whether the probes reached the target
+    pub reached: bool,
+    pub hops: Vec<Hop>,
+    pub error: Option<String>,
+}
+
+/// Set the TTL, or hop limit, of the packets of a socket.
+fn set_ttl(socket: SockRef<'_>, target: IpAddr, ttl: u8) -> Result<()> {
+    match target {
+        IpAddr::V4(_) => socket.set_ttl_v4(ttl as u32)?,
+        IpAddr::V6(_) => socket.set_unicast_hops_v6(ttl as u32)?,
+    }
+    Ok(())
+}
+
+/// Bind a probe socket like the connections, to a known local port.
+fn bind(socket: &Socket, target: IpAddr, connect: &Connect) -> Result<u16> {
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    if let Some(interface) = &connect.interface {
+        socket
+            .bind_device(Some(interface.as_bytes()))
+            .with_context(|| format!("failed to bind to interface {}", interface))?;
+    }
+    let local = connect
+        .source_ip
+        .unwrap_or_else(|| AddressFamily::of(&target).unspecified());
+    socket.bind(&SocketAddr::new(local, 0).into())?;
+    socket.set_nonblocking(true)?;
+    let port = socket.local_addr()?.as_socket().map(|addr| addr.port());
+    port.context("socket without local port")
+}
+
+/// Probe matched by an ICMP message, identified by the key of its mode (the destination port of
+/// the UDP probes, the sequence of the echo requests, the source port of the TCP probes),
+/// along with whether the message ends the path.
+fn parse(
+    target: IpAddr,
+    mode: TracerouteMode,
+    port: u16,
+    identifier: u16,
+    packet: &[u8],
+    from: IpAddr,
+) -> Option<(u16, bool)> {
+    // Raw IPv4 sockets receive the IP header along with the ICMP message
+    let message = match target {
+        IpAddr::V4(_) => packet.get((*packet.first()? as usize & 0x0f) * 4..)?,
+        IpAddr::V6(_) => packet,
+    };
+    let (echo_reply, unreachable, time_exceeded) = match target {
+        IpAddr::V4(_) => (0, 3, 11),
+        IpAddr::V6(_) => (129, 1, 3),
+    };
+    let field = |bytes: &[u8], index: usize| -> Option<u16> {
+        Some(u16::from_be_bytes(
+            bytes.get(index..index + 2)?.try_into().ok()?,
+        ))
+    };
+    let kind = *message.first()?;
+    if kind == echo_reply {
+        let matched = mode == TracerouteMode::Icmp && from == target;
+        return (matched && field(message, 4)? == identifier).then_some((field(message, 6)?, true));
+    }
+    if kind != unreachable && kind != time_exceeded {
+        return None;
+    }
+
+    // The header of the probe, quoted by the error
+    let quoted = message.get(8..)?;
+    let (protocol, destination, transport) = match target {
+        IpAddr::V4(_) => {
+            let octets: [u8; 4] = quoted.get(16..20)?.try_into().ok()?;
+            let length = (*quoted.first()? as usize & 0x0f) * 4;
+            (*quoted.get(9)?, IpAddr::from(octets), quoted.get(length..)?)
+        }
+        IpAddr::V6(_) => {
+            let octets: [u8; 16] = quoted.get(24..40)?.try_into().ok()?;
+            (*quoted.get(6)?, IpAddr::from(octets), quoted.get(40..)?)
+        }
+    };
+    if destination != target {
+        return None;
+    }
+    let key = match (mode, protocol) {
+        (TracerouteMode::Udp, 17) if field(transport, 0)? == port => field(transport, 2)?,
+        (TracerouteMode::Icmp, 1 | 58) if field(transport, 4)? == identifier => {
+            field(transport, 6)?
+        }
+        (TracerouteMode::Tcp, 6) if field(transport, 2)? == port => field(transport, 0)?,
+        _ => return None,
+    };
+    // A destination unreachable error ends the path, be it sent by the target or a router
+    Some((key, kind == unreachable))
+}
+
+/// Answer to a probe.
+struct Reply {
+    ip: IpAddr,
+    rtt: Duration,
+    last: bool,
+}
+
+/// Probe every TTL up to `max_hops` toward `target` at once, waiting up to `timeout` for the
+/// answers, the TCP probes connecting to `port`.
+pub async fn round(
+    target: IpAddr,
+    mode: TracerouteMode,
+    port: u16,
+    max_hops: u8,
+    timeout: Duration,
+    connect: &Connect,
+) -> Result<Vec<Hop>> {
+    let domain = Domain::for_address(SocketAddr::new(target, 0));
+    let protocol = match target {
+        IpAddr::V4(_) => Protocol::ICMPV4,
+        IpAddr::V6(_) => Protocol::ICMPV6,
+    };
+    // The routers answer with ICMP errors, whatever the protocol of the probes
+    let receiver = Socket::new(domain, Type::RAW, Some(protocol))
+        .context("failed to open a raw ICMP socket, which requires root or CAP_NET_RAW")?;
+    bind(&receiver, target, connect)?;
+    let receiver = UdpSocket::from_std(std::net::UdpSocket::from(receiver))?;
+
+    let identifier = rand::random::<u16>();
+    // Probes by key, with their TTL and the time they were sent at
+    let mut probes = HashMap::new();
+    let mut connects = JoinSet::new();
+    // The UDP probes are sent from a single socket, and matched by its port
+    let (udp, port) = match mode {
+        TracerouteMode::Udp => {
+            let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+            let port = bind(&socket, target, connect)?;
+            let socket = UdpSocket::from_std(std::net::UdpSocket::from(socket))?;
+            (Some(socket), port)
+        }
+        _ => (None, port),
+    };
+    for ttl in 1..=max_hops {
+        match (mode, &udp) {
+            (TracerouteMode::Udp, Some(socket)) => {
+                set_ttl(SockRef::from(socket), target, ttl)?;
+                let key = UDP_PORT + ttl as u16;
+                probes.insert(key, (ttl, Instant::now()));
+                socket
+                    .send_to(&[0; 32], SocketAddr::new(target, key))
+                    .await?;
+            }
+            (TracerouteMode::Icmp, _) => {
+                set_ttl(SockRef::from(&receiver), target, ttl)?;
+                let request = icmp::echo_request(target, identifier, ttl as u16, &[0; 32]);
+                probes.insert(ttl as u16, (ttl, Instant::now()));
+                receiver
+                    .send_to(&request, SocketAddr::new(target, 0))
+                    .await?;
+            }
+            (TracerouteMode::Tcp, _) => {
+                let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+                let key = bind(&socket, target, connect)?;
+                set_ttl(SockRef::from(&socket), target, ttl)?;
+                let socket = TcpSocket::from_std_stream(std::net::TcpStream::from(socket));
+                probes.insert(key, (ttl, Instant::now()));
+                connects.spawn(async move {
+                    let connected = socket.connect(SocketAddr::new(target, port)).await;
+                    (ttl, connected.map(drop))
+                });
+            }
+            (TracerouteMode::Udp, None) => unreachable!(),
+        }
+    }
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut replies: HashMap<u8, Reply> = HashMap::new();
+    let mut buf = [0u8; 1500];
+    loop {
+        tokio::select! {
+            received = receiver.recv_from(&mut buf) => {
+                let (length, from) = received?;
+                let Some((key, last)) = parse(target, mode, port, identifier, &buf[..length], from.ip())
+                else {
+                    continue;
+                };
+                if let Some((ttl, sent)) = probes.get(&key) {
+                    replies.entry(*ttl).or_insert(Reply { ip: from.ip(), rtt: sent.elapsed(), last });
+                }
+            }
+            Some(Ok((ttl, connected))) = connects.join_next() => {
+                // A connection accepted or refused by the target ends the path
+                let refused = matches!(&connected, Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused);
+                if connected.is_ok() || refused {
+                    let sent = probes.values().find(|(other, _)| *other == ttl).map(|(_, sent)| *sent);
+                    let rtt = sent.map(|sent| sent.elapsed()).unwrap_or_default();
+                    replies.entry(ttl).or_insert(Reply { ip: target, rtt, last: true });
+                }
+            }
+            _ = tokio::time::sleep_until(deadline) => break,
+        }
+        // Done once every hop up to the end of the path answered
+        let end = replies
+            .iter()
+            .filter(|(_, reply)| reply.last)
+            .map(|(ttl, _)| *ttl)
+            .min();
+        if end.is_some_and(|end| (1..=end).all(|ttl| replies.contains_key(&ttl))) {
+            break;
+        }
+    }
+    connects.abort_all();
+
+    let end = replies
+        .iter()
+        .filter(|(_, reply)| reply.last)
+        .map(|(ttl, _)| *ttl)
+        .min()
+        .or_else(|| replies.keys().max().copied())
+        .unwrap_or(0);
+    let hops = (1..=end)
+        .map(|ttl| Hop {
+            ttl,
+            ip: replies.get(&ttl).map(|reply| reply.ip),
+            rtt_ms: replies
+                .get(&ttl)
+                .map(|reply| reply.rtt.as_secs_f64() * 1000.0),
+        })
+        .collect();
+    Ok(hops)
+}
+
+/// Trace the path to the host of `url`.
+#[tracing::instrument(level = "debug", name = "traceroute", skip_all, fields(url = %url, mode = %mode))]
+pub async fn trace(
+    url: &Url,
+    mode: TracerouteMode,
+    timeout: Duration,
+    connect: &Connect,
+) -> Traceroute {
+    let mut traceroute = Traceroute {
+        target: None,
+        mode,
+        reached: false,
+        hops: Vec::new(),
+        error: None,
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+    let hops = async {
+        let target = connect.address(url).await?;
+        traceroute.target = Some(target);
+        round(target, mode, port, MAX_HOPS, timeout, connect).await
+    };
+    match hops.await {
+        Ok(hops) => traceroute.hops = hops,
+        Err(e) => traceroute.error = Some(format!("{:#}", e)),
+    }
+    traceroute.reached = traceroute
+        .hops
+        .last()
+        .is_some_and(|hop| hop.ip.is_some() && hop.ip == traceroute.target);
+    debug!(
+        target = ?traceroute.target,
+        reached = traceroute.reached,
+        hops = traceroute.hops.len(),
+        "Traceroute finished"
+    );
+    traceroute
+}
+
+/// Statistics of a hop over the rounds of a path analysis, like mtr.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HopStats {
+    pub ttl: u8,
+    /// Addresses the hop answered from, several behind load balancing
+    pub ips: Vec<IpAddr>,
+    pub sent: u32,
+    pub received: u32,
+    /// Percentage of the probes left unanswered
+    pub loss: f64,
+    pub avg_ms: Option<f64>,
+    pub best_ms: Option<f64>,
+    pub worst_ms: Option<f64>,
+}
+
+/// Path to a target, analyzed over several traceroute rounds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PathAnalysis {
+    pub target: Option<IpAddr>,
+    pub mode: TracerouteMode,
+    pub rounds: u32,
+    pub hops: Vec<HopStats>,
+    pub error: Option<String>,
+}
+
+/// Statistics of each hop over the traceroute rounds, the hops beyond the end of the path of a
+/// round not being probed by it.
+fn stats(rounds: &[Vec<Hop>]) -> Vec<HopStats> {
+    let length = rounds.iter().map(Vec::len).max().unwrap_or(0);
+    (0..length)
+        .map(|index| {
+            let hops = rounds
+                .iter()
+                .filter_map(|hops| hops.get(index))
+                .collect::<Vec<_>>();
+            let mut ips = Vec::new();
+            for ip in hops.iter().filter_map(|hop| hop.ip) {
+                if !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+            let rtts = hops.iter().filter_map(|hop| hop.rtt_ms).collect::<Vec<_>>();
+            let sent = hops.len() as u32;
+            let received = rtts.len() as u32;
+            HopStats {
+                ttl: index as u8 + 1,
+                ips,
+                sent,
+                received,
+                loss: 100.0 * (sent - received) as f64 / sent.max(1) as f64,
+                avg_ms: (!rtts.is_empty()).then(|| rtts.iter().sum::<f64>() / rtts.len() as f64),
+                best_ms: rtts.iter().copied().reduce(f64::min),
+                worst_ms: rtts.iter().copied().reduce(f64::max),
+            }
+        })
+        .collect()
+}
+
+/// Analyze the path to the host of `url` over `rounds` traceroutes, a second apart like mtr.
+#[tracing::instrument(level = "debug", name = "path", skip_all, fields(url = %url, mode = %mode))]
+pub async fn analyze(
+    url: &Url,
+    mode: TracerouteMode,
+    rounds: u32,
+    timeout: Duration,
+    connect: &Connect,
+) -> PathAnalysis {
+    let mut analysis = PathAnalysis {
+        target: None,
+        mode,
+        rounds: 0,
+        hops: Vec::new(),
+        error: None,
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+    let mut traces = Vec::new();
+    let trace = async {
+        let target = connect.address(url).await?;
+        analysis.target = Some(target);
+        for index in 0..rounds {
+            let start = Instant::now();
+            traces.push(round(target, mode, port, MAX_HOPS, timeout, connect).await?);
+            debug!(
+                round = index + 1,
+                hops = traces[traces.len() - 1].len(),
+                "Round finished"
+            );
+            if index + 1 < rounds {
+                tokio::time::sleep(ROUND_INTERVAL.saturating_sub(start.elapsed())).await;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    if let Err(e) = trace.await {
+        analysis.error = Some(format!("{:#}", e));
+    }
+    analysis.rounds = traces.len() as u32;
+    analysis.hops = stats(&traces);
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "192.0.2.1";
+
+    /// IPv4 packet of a protocol from a source to a destination.
+    fn ipv4(protocol: u8, source: &str, destination: &str, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0];
+        packet.extend_from_slice(&source.parse::<std::net::Ipv4Addr>().unwrap().octets());
+        packet.extend_from_slice(&destination.parse::<std::net::Ipv4Addr>().unwrap().octets());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// ICMP error of a type from a router, quoting a probe to a destination.
+    fn error(kind: u8, destination: &str, protocol: u8, transport: &[u8]) -> Vec<u8> {
+        let mut message = vec![kind, 0, 0, 0, 0, 0, 0, 0];
+        message.extend_from_slice(&ipv4(protocol, "198.51.100.7", destination, transport));
+        ipv4(1, "203.0.113.1", "198.51.100.7", &message)
+    }
+
+    fn parse_ipv4(mode: TracerouteMode, packet: &[u8], from: &str) -> Option<(u16, bool)> {
+        parse(
+            TARGET.parse().unwrap(),
+            mode,
+            50000,
+            0x1234,
+            packet,
+            from.parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn udp_probes() {
+        // Source and destination ports of the quoted UDP header
+        let udp = [0xc3, 0x50, 0x82, 0x9b, 0, 8, 0, 0];
+        let time_exceeded = error(11, TARGET, 17, &udp);
+        assert_eq!(
+            parse_ipv4(TracerouteMode::Udp, &time_exceeded, "203.0.113.1"),
+            Some((33435, false))
+        );
+        let unreachable = error(3, TARGET, 17, &udp);
+        assert_eq!(
+            parse_ipv4(TracerouteMode::Udp, &unreachable, TARGET),
+            Some((33435, true))
+        );
+        // Probes of another socket, mode or target
+        let other = error(11, TARGET, 17, &[0xc3, 0x51, 0x82, 0x9b, 0, 8, 0, 0]);
+        assert_eq!(parse_ipv4(TracerouteMode::Udp, &other, "203.0.113.1"), None);
+        assert_eq!(
+            parse_ipv4(TracerouteMode::Tcp, &time_exceeded, "203.0.113.1"),
+            None
+        );
+        let other = error(11, "192.0.2.2", 17, &udp);
+        assert_eq!(parse_ipv4(TracerouteMode::Udp, &other, "203.0.113.1"), None);
+    }
+
+    #[test]
+    fn icmp_and_tcp_probes() {
+        let echo = [8, 0, 0, 0, 0x12, 0x34, 0, 5];
+        let time_exceeded = error(11, TARGET, 1, &echo);
+        assert_eq!(
+            parse_ipv4(TracerouteMode::Icmp, &time_exceeded, "203.0.113.1"),
+            Some((5, false))
+        );
+        let reply = ipv4(1, TARGET, "198.51.100.7", &[0, 0, 0, 0, 0x12, 0x34, 0, 5]);
+        assert_eq!(
+            parse_ipv4(TracerouteMode::Icmp, &reply, TARGET),
+            Some((5, true))
+        );
+        assert_eq!(parse_ipv4(TracerouteMode::Icmp, &reply, "192.0.2.2"), None);
+
+        // Source port of the probe, destination port of the target
+        let tcp = [0x9c, 0x40, 0xc3, 0x50, 0, 0, 0, 0];
+        let time_exceeded = error(11, TARGET, 6, &tcp);
+        assert_eq!(
+            parse_ipv4(TracerouteMode::Tcp, &time_exceeded, "203.0.113.1"),
+            Some((40000, false))
+        );
+    }
+
+    #[test]
+    fn ipv6_probes() {
+        let target = "2001:db8::1".parse::<IpAddr>().unwrap();
+        let mut message = vec![3, 0, 0, 0, 0, 0, 0, 0];
+        let mut quoted = vec![0x60, 0, 0, 0, 0, 8, 17, 1];
+        quoted.extend_from_slice(&[0; 16]);
+        if let IpAddr::V6(target) = target {
+            quoted.extend_from_slice(&target.octets());
+        }
+        quoted.extend_from_slice(&[0xc3, 0x50, 0x82, 0x9a, 0, 8, 0, 0]);
+        message.extend_from_slice(&quoted);
+        let from = "2001:db8:ffff::1".parse().unwrap();
+        let probe = parse(target, TracerouteMode::Udp, 50000, 0, &message, from);
+        assert_eq!(probe, Some((33434, false)));
+    }
+
+    #[test]
+    fn truncated_messages() {
+        let udp = [0xc3, 0x50, 0x82, 0x9b, 0, 8, 0, 0];
+        let packet = error(11, TARGET, 17, &udp);
+        for length in 0..packet.len() - 4 {
+            assert_eq!(
+                parse_ipv4(TracerouteMode::Udp, &packet[..length], "203.0.113.1"),
+                None,
+                "{} bytes",
+                length
+            );
+        }
+        // A header length past the end of the packet
+        assert_eq!(parse_ipv4(TracerouteMode::Udp, &[0x4f; 20], TARGET), None);
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(
+            "TCP".parse::<TracerouteMode>().unwrap(),
+            TracerouteMode::Tcp
+        );
+        assert!("sctp".parse::<TracerouteMode>().is_err());
+        assert_eq!(TracerouteMode::Icmp.to_string(), "icmp");
+    }
+
+    #[test]
+    fn hop_stats() {
+        let ip = "203.0.113.1".parse().unwrap();
+        let hop = |ttl, ip, rtt_ms| Hop { ttl, ip, rtt_ms };
+        let rounds = [
+            vec![hop(1, Some(ip), Some(1.0)), hop(2, None, None)],
+            vec![hop(1, Some(ip), Some(3.0))],
+        ];
+        let stats = stats(&rounds);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            (stats[0].sent, stats[0].received, stats[0].loss),
+            (2, 2, 0.0)
+        );
+        assert_eq!(stats[0].ips, [ip]);
+        assert_eq!(
+            (stats[0].avg_ms, stats[0].best_ms, stats[0].worst_ms),
+            (Some(2.0), Some(1.0), Some(3.0))
+        );
+        assert_eq!((stats[1].ttl, stats[1].sent, stats[1].loss), (2, 1, 100.0));
+        assert_eq!(stats[1].avg_ms, None);
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, GraphType, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing_subscriber::fmt::MakeWriter;
+
+use rovcheck::output::{beacon_name, label};
+use rovcheck::{Daemon, RovResult, Verdict};
+
+/// Number of check results kept for the timeline and the latency chart.
+const RESULTS: usize = 120;
+/// Number of log lines kept for the log tail.
+const LOGS: usize = 200;
+
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Tracing writer keeping the last log lines for the log tail,
+/// as the dashboard owns the terminal.
+#[derive(Clone, Copy, Default)]
+pub struct LogWriter;
+
+impl io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = LOG_LINES.lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOGS {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+fn color(verdict: Verdict) -> Color {
+    match verdict {
+        Verdict::Enforced => Color::Green,
+        Verdict::NotEnforced => Color::Red,
+        Verdict::Inconclusive => Color::DarkGray,
+    }
+}
+
+#[derive(Default)]
+struct Dashboard {
+    results: VecDeque<RovResult>,
+}
+
+impl Dashboard {
+    fn push(&mut self, result: RovResult) {
+        if self.results.len() == RESULTS {
+            self.results.pop_front();
+        }
+        self.results.push_back(result);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, beacons, timeline, latencies, logs] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(3),
+            Constraint::Length(12),
+            Constraint::Length(10),
+        ])
+        .areas(frame.area());
+        self.draw_header(frame, header);
+        self.draw_beacons(frame, beacons);
+        self.draw_timeline(frame, timeline);
+        self.draw_latencies(frame, latencies);
+        draw_logs(frame, logs);
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" rovcheck (q to quit) ");
+        let lines = match self.results.back() {
+            Some(result) => {
+                let asn = result
+                    .asn()
+                    .map(|asn| format!(" via AS{}", asn))
+                    .unwrap_or_default();
+                vec![
+                    Line::from(vec![
+                        Span::styled(result.verdict.to_string(), color(result.verdict)).bold(),
+                        Span::raw(format!("{}: {}", asn, result.reason)),
+                    ]),
+                    Line::from(format!(
+                        "Last check at {}, {} checks",
+                        result.started_at.to_rfc3339(),
+                        self.results.len()
+                    )),
+                ]
+            }
+            None => vec![Line::from("Waiting for the first check...")],
+        };
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_beacons(&self, frame: &mut Frame, area: Rect) {
+        let mut rows = Vec::new();
+        if let Some(result) = self.results.back() {
+            for pair in &result.pairs {
+                for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                    rows.push(
+                        Row::new(vec![
+                            beacon_name(pair),
+                            pair.family
+                                .map(|family| family.to_string())
+                                .unwrap_or_default(),
+                            validity.to_string(),
+                            if beacon.success { "yes" } else { "no" }.to_string(),
+                            format!("{:.3}s", beacon.duration().as_secs_f64()),
+                            beacon.attempts.to_string(),
+                            beacon.error.clone().unwrap_or_default(),
+                        ])
+                        .style(Style::new().fg(color(pair.verdict))),
+                    );
+                }
+            }
+        }
+        let widths = [
+            Constraint::Length(16),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(9),
+            Constraint::Length(9),
+            Constraint::Length(8),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(
+                Row::new([
+                    "Beacon",
+                    "Family",
+                    "Validity",
+                    "Reachable",
+                    "Latency",
+                    "Attempts",
+                    "Error",
+                ])
+                .bold(),
+            )
+            .block(Block::bordered().title(" Beacons "));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_timeline(&self, frame: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(2) as usize;
+        let spans = self
+            .results
+            .iter()
+            .skip(self.results.len().saturating_sub(width))
+            .map(|result| Span::styled("█", color(result.verdict)))
+            .collect::<Vec<_>>();
+        let paragraph =
+            Paragraph::new(Line::from(spans)).block(Block::bordered().title(" Verdicts "));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_latencies(&self, frame: &mut Frame, area: Rect) {
+        let mut series: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+        for (index, result) in self.results.iter().enumerate() {
+            for pair in &result.pairs {
+                for (validity, beacon) in [("valid", &pair.valid), ("invalid", &pair.invalid)] {
+                    series
+                        .entry(label(pair, validity))
+                        .or_default()
+                        .push((index as f64, beacon.duration().as_secs_f64()));
+                }
+            }
+        }
+        let max = series
+            .values()
+            .flatten()
+            .map(|(_, latency)| *latency)
+            .fold(0.0, f64::max)
+            .max(0.001);
+        const COLORS: [Color; 6] = [
+            Color::Cyan,
+            Color::Yellow,
+            Color::Magenta,
+            Color::Blue,
+            Color::LightGreen,
+            Color::LightRed,
+        ];
+        let datasets = series
+            .iter()
+            .enumerate()
+            .map(|(index, (name, points))| {
+                Dataset::default()
+                    .name(name.clone())
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(COLORS[index % COLORS.len()])
+                    .data(points)
+            })
+            .collect();
+        let chart = Chart::new(datasets)
+            .block(Block::bordered().title(" Latencies "))
+            .x_axis(Axis::default().bounds([0.0, (RESULTS - 1) as f64]))
+            .y_axis(
+                Axis::default()
+                    .bounds([0.0, max])
+                    .labels(["0s".to_string(), format!("{:.3}s", max)]),
+            );
+        frame.render_widget(chart, area);
+    }
+}
+
+fn draw_logs(frame: &mut Frame, area: Rect) {
+    let height = area.height.saturating_sub(2) as usize;
+    let lines = LOG_LINES.lock().unwrap();
+    let lines = lines
+        .iter()
+        .skip(lines.len().saturating_sub(height))
+        .map(|line| Line::from(line.clone()))
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Logs ")),
+        area,
+    );
+}
+
+/// Whether a key press asks to quit the dashboard.
+fn quit() -> Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press
+                && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL)))
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+async fn dashboard(terminal: &mut DefaultTerminal, daemon: Daemon) -> Result<()> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut daemon = tokio::spawn(daemon.run(move |result, _| {
+        let _ = sender.send(result.clone());
+        Ok(())
+    }));
+
+    let mut dashboard = Dashboard::default();
+    let mut tick = tokio::time::interval(Duration::from_millis(250));
+    loop {
+        terminal.draw(|frame| dashboard.draw(frame))?;
+        tokio::select! {
+            Some(result) = receiver.recv() => dashboard.push(result),
+            _ = tick.tick() => {
+                if quit()? {
+                    daemon.abort();
+                    return Ok(());
+                }
+            }
+            result = &mut daemon => return result?,
+        }
+    }
+}
+
+/// Run the daemon with a live dashboard of the check results.
+pub async fn run(daemon: Daemon) -> Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = dashboard(&mut terminal, daemon).await;
+    ratatui::restore();
+    result
+}
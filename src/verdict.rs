@@ -0,0 +1,216 @@
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Outcome of a ROV check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Verdict {
+    /// The valid beacon is reachable and the invalid one is not
+    Enforced,
+    /// Both beacons are reachable
+    NotEnforced,
+    /// The valid beacon is unreachable, nothing can be concluded
+    Inconclusive,
+}
+
+impl Verdict {
+    /// Verdict of a beacon pair, along with its reason.
+    pub fn of_pair(valid: bool, invalid: bool) -> (Verdict, &'static str) {
+        match (valid, invalid) {
+            (true, false) => (
+                Verdict::Enforced,
+                "valid beacon reachable, invalid beacon unreachable",
+            ),
+            (true, true) => (Verdict::NotEnforced, "both beacons reachable"),
+            (false, _) => (Verdict::Inconclusive, "valid beacon unreachable"),
+        }
+    }
+
+    /// Combine several verdicts: any non-enforcement wins over inconclusive results,
+    /// and ROV is considered enforced only if all verdicts are.
+    pub fn combine(verdicts: impl IntoIterator<Item = Verdict>) -> Verdict {
+        let mut combined = None;
+        for verdict in verdicts {
+            combined = match (combined, verdict) {
+                (_, Verdict::NotEnforced) | (Some(Verdict::NotEnforced), _) => {
+                    Some(Verdict::NotEnforced)
+                }
+                (_, Verdict::Inconclusive) | (Some(Verdict::Inconclusive), _) => {
+                    Some(Verdict::Inconclusive)
+                }
+                _ => Some(Verdict::Enforced),
+            };
+        }
+        combined.unwrap_or(Verdict::Inconclusive)
+    }
+
+    /// Whether two verdicts agree, unknown if either is inconclusive.
+    pub fn agrees(self, other: Verdict) -> Option<bool> {
+        (self != Verdict::Inconclusive && other != Verdict::Inconclusive).then_some(self == other)
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Enforced => write!(f, "ENFORCED"),
+            Verdict::NotEnforced => write!(f, "NOT_ENFORCED"),
+            Verdict::Inconclusive => write!(f, "INCONCLUSIVE"),
+        }
+    }
+}
+
+impl FromStr for Verdict {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().replace('-', "_").as_str() {
+            "ENFORCED" => Ok(Verdict::Enforced),
+            "NOT_ENFORCED" => Ok(Verdict::NotEnforced),
+            "INCONCLUSIVE" => Ok(Verdict::Inconclusive),
+            _ => bail!("unknown verdict `{}`", s),
+        }
+    }
+}
+
+/// Quorum policy across beacon pairs: ROV is considered enforced if at least
+/// `required` of the `total` beacon pairs are enforcing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quorum {
+    pub required: usize,
+    pub total: usize,
+}
+
+impl Quorum {
+    /// Verdict given the verdicts of the beacon pairs.
+    pub fn verdict(&self, verdicts: impl IntoIterator<Item = Verdict>) -> Verdict {
+        let (mut enforced, mut inconclusive) = (0, 0);
+        for verdict in verdicts {
+            match verdict {
+                Verdict::Enforced => enforced += 1,
+                Verdict::Inconclusive => inconclusive += 1,
+                Verdict::NotEnforced => {}
+            }
+        }
+
+        if enforced >= self.required {
+            Verdict::Enforced
+        } else if enforced + inconclusive >= self.required {
+            Verdict::Inconclusive
+        } else {
+            Verdict::NotEnforced
+        }
+    }
+}
+
+impl FromStr for Quorum {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (required, total) = s
+            .split_once('/')
+            .context("quorum must be of the form m/n")?;
+        let quorum = Quorum {
+            required: required.trim().parse()?,
+            total: total.trim().parse()?,
+        };
+        if quorum.required == 0 || quorum.required > quorum.total {
+            bail!("quorum must satisfy 0 < m <= n");
+        }
+        Ok(quorum)
+    }
+}
+
+impl std::fmt::Display for Quorum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.required, self.total)
+    }
+}
+
+impl Serialize for Quorum {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quorum {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Verdict::*;
+
+    #[test]
+    fn pair_verdicts() {
+        assert_eq!(Verdict::of_pair(true, false).0, Enforced);
+        assert_eq!(Verdict::of_pair(true, true).0, NotEnforced);
+        assert_eq!(Verdict::of_pair(false, true).0, Inconclusive);
+        assert_eq!(Verdict::of_pair(false, false).0, Inconclusive);
+    }
+
+    #[test]
+    fn combine_verdicts() {
+        assert_eq!(Verdict::combine([]), Inconclusive);
+        assert_eq!(Verdict::combine([Enforced, Enforced]), Enforced);
+        assert_eq!(Verdict::combine([Enforced, Inconclusive]), Inconclusive);
+        assert_eq!(Verdict::combine([NotEnforced, Inconclusive]), NotEnforced);
+        assert_eq!(
+            Verdict::combine([Inconclusive, NotEnforced, Enforced]),
+            NotEnforced
+        );
+    }
+
+    #[test]
+    fn parse_verdicts() {
+        for verdict in [Enforced, NotEnforced, Inconclusive] {
+            assert_eq!(verdict.to_string().parse::<Verdict>().unwrap(), verdict);
+        }
+        assert_eq!("not-enforced".parse::<Verdict>().unwrap(), NotEnforced);
+        assert!("enforcing".parse::<Verdict>().is_err());
+        assert_eq!(Enforced.agrees(Enforced), Some(true));
+        assert_eq!(Enforced.agrees(NotEnforced), Some(false));
+        assert_eq!(Inconclusive.agrees(Enforced), None);
+    }
+
+    #[test]
+    fn parse_quorums() {
+        let quorum = " 2 / 3 ".parse::<Quorum>().unwrap();
+        assert_eq!(
+            quorum,
+            Quorum {
+                required: 2,
+                total: 3
+            }
+        );
+        assert_eq!(quorum.to_string(), "2/3");
+        for invalid in ["2", "0/3", "4/3", "a/3", "2/", "-1/3"] {
+            assert!(invalid.parse::<Quorum>().is_err(), "{}", invalid);
+        }
+        let quorum: Quorum = serde_json::from_str("\"1/2\"").unwrap();
+        assert_eq!(serde_json::to_string(&quorum).unwrap(), "\"1/2\"");
+    }
+
+    #[test]
+    fn quorum_verdicts() {
+        let quorum = Quorum {
+            required: 2,
+            total: 3,
+        };
+        assert_eq!(quorum.verdict([Enforced, Enforced, NotEnforced]), Enforced);
+        assert_eq!(
+            quorum.verdict([Enforced, Inconclusive, NotEnforced]),
+            Inconclusive
+        );
+        assert_eq!(
+            quorum.verdict([Enforced, NotEnforced, NotEnforced]),
+            NotEnforced
+        );
+        assert_eq!(quorum.verdict([]), NotEnforced);
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::checker::{RovChecker, RovResult};
+use crate::verdict::Verdict;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: Verdict,
+    pub to: Verdict,
+    pub at: DateTime<Utc>,
+}
+
+/// Damping of the verdict changes, only reported once seen in enough consecutive checks.
+struct Damping {
+    /// Number of consecutive checks for a change to a non-ENFORCED verdict to be reported
+    alert_after: u32,
+    /// Number of consecutive checks for a change back to ENFORCED to be reported
+    recover_after: u32,
+    /// Last reported verdict
+    reported: Option<Verdict>,
+    /// Verdict differing from the reported one, and its number of consecutive checks
+    pending: Option<(Verdict, u32)>,
+}
+
+impl Damping {
+    /// Account for the verdict of a check, returning the transition it reports if any.
+    fn observe(&mut self, verdict: Verdict, at: DateTime<Utc>) -> Option<Transition> {
+        let Some(reported) = self.reported else {
+            self.reported = Some(verdict);
+            return None;
+        };
+        if reported == verdict {
+            self.pending = None;
+            return None;
+        }
+
+        let count = match self.pending {
+            Some((pending, count)) if pending == verdict => count + 1,
+            _ => 1,
+        };
+        let threshold = match verdict {
+            Verdict::Enforced => self.recover_after,
+            _ => self.alert_after,
+        };
+        if count < threshold {
+            debug!(
+                "Verdict {} seen in {}/{} consecutive checks",
+                verdict, count, threshold
+            );
+            self.pending = Some((verdict, count));
+            return None;
+        }
+        self.reported = Some(verdict);
+        self.pending = None;
+        Some(Transition {
+            from: reported,
+            to: verdict,
+            at,
+        })
+    }
+}
+
+/// Runs checks repeatedly and keeps track of verdict transitions.
+pub struct Watcher {
+    checker: RovChecker,
+    last: Option<RovResult>,
+    damping: Damping,
+}
+
+impl Watcher {
+    pub fn new(checker: RovChecker) -> Self {
+        Watcher {
+            checker,
+            last: None,
+            damping: Damping {
+                alert_after: 1,
+                recover_after: 1,
+                reported: None,
+                pending: None,
+            },
+        }
+    }
+
+    /// Only report verdict changes once they are seen in enough consecutive checks.
+    pub fn with_damping(mut self, alert_after: u32, recover_after: u32) -> Self {
+        self.damping.alert_after = alert_after.max(1);
+        self.damping.recover_after = recover_after.max(1);
+        self
+    }
+
+    /// Replace the checker and the damping by the ones of `other`,
+    /// keeping the verdict state to detect transitions.
+    pub fn replace(&mut self, other: Watcher) {
+        self.checker = other.checker;
+        self.damping.alert_after = other.damping.alert_after;
+        self.damping.recover_after = other.damping.recover_after;
+    }
+
+    pub fn last(&self) -> Option<&RovResult> {
+        self.last.as_ref()
+    }
+
+    /// Run a single check and return its result, along with the transition
+    /// from the previous verdict if it changed.
+    pub async fn tick(&mut self) -> Result<(RovResult, Option<Transition>)> {
+        let result = self.checker.check().await?;
+        let transition = self.damping.observe(result.verdict, result.finished_at);
+        self.last = Some(result.clone());
+        Ok((result, transition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Verdict::*;
+
+    fn damping(alert_after: u32, recover_after: u32) -> Damping {
+        Damping {
+            alert_after,
+            recover_after,
+            reported: None,
+            pending: None,
+        }
+    }
+
+    /// Targets of the transitions reported for a series of verdicts.
+    fn transitions(damping: &mut Damping, verdicts: &[Verdict]) -> Vec<Option<Verdict>> {
+        verdicts
+            .iter()
+            .map(|verdict| {
+                let transition = damping.observe(*verdict, Utc::now());
+                transition.map(|transition| transition.to)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undamped() {
+        let mut damping = damping(1, 1);
+        let transition = damping.observe(Enforced, Utc::now());
+        assert!(transition.is_none(), "the first verdict is no transition");
+        let transition = damping.observe(NotEnforced, Utc::now()).unwrap();
+        assert_eq!((transition.from, transition.to), (Enforced, NotEnforced));
+        assert_eq!(
+            transitions(&mut damping, &[NotEnforced, Enforced, Enforced]),
+            [None, Some(Enforced), None]
+        );
+    }
+
+    #[test]
+    fn damped() {
+        let mut damping = damping(3, 2);
+        assert_eq!(
+            transitions(
+                &mut damping,
+                &[Enforced, NotEnforced, NotEnforced, Enforced, NotEnforced]
+            ),
+            [None; 5],
+            "a flap resets the count"
+        );
+        assert_eq!(
+            transitions(
+                &mut damping,
+                &[NotEnforced, NotEnforced, Enforced, Enforced]
+            ),
+            [None, Some(NotEnforced), None, Some(Enforced)]
+        );
+        assert_eq!(damping.reported, Some(Enforced));
+    }
+
+    #[test]
+    fn pending_verdict_changes() {
+        // A different non-ENFORCED verdict starts the count over
+        let mut damping = damping(2, 1);
+        assert_eq!(
+            transitions(
+                &mut damping,
+                &[Enforced, NotEnforced, Inconclusive, Inconclusive]
+            ),
+            [None, None, None, Some(Inconclusive)]
+        );
+    }
+}